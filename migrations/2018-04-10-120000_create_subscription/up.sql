@@ -0,0 +1,11 @@
+create table subscription (
+  agent_id uuid,
+  room_id uuid,
+  resource text check (resource in ('agents', 'tracks')),
+  created_at timestamp not null default now(),
+
+  primary key (agent_id, room_id, resource),
+  foreign key (agent_id, room_id) references room_agent (agent_id, room_id) on delete cascade
+);
+
+create index subscription_room_id_idx on subscription (room_id);
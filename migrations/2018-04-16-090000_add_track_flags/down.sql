@@ -0,0 +1,3 @@
+alter table track
+  drop column enabled,
+  drop column muted;
@@ -0,0 +1,3 @@
+alter table track
+  add column enabled boolean not null default true,
+  add column muted boolean not null default false;
@@ -0,0 +1,11 @@
+drop trigger set_updated_at on track;
+
+drop index track_stream_id_idx;
+drop index track_owner_id_kind_idx;
+
+alter table track
+  drop column updated_at,
+  drop column created_at,
+  drop column stream_id,
+  drop column label,
+  drop column kind;
@@ -0,0 +1,24 @@
+alter table track
+  add column kind text,
+  add column label text not null default '',
+  add column stream_id text,
+  add column created_at timestamp not null default now(),
+  add column updated_at timestamp not null default now();
+
+-- Existing tracks keep the kind they had in metadata (if any) and get a stream of their own.
+update track set
+  kind = case
+    when metadata->>'kind' in ('audio', 'video', 'screen', 'data') then metadata->>'kind'
+    else 'video'
+  end,
+  stream_id = id::text;
+
+alter table track
+  alter column kind set not null,
+  alter column stream_id set not null,
+  add constraint track_kind_check check (kind in ('audio', 'video', 'screen', 'data'));
+
+create index track_owner_id_kind_idx on track (owner_id, kind);
+create index track_stream_id_idx on track (stream_id);
+
+select diesel_manage_updated_at('track');
@@ -0,0 +1,5 @@
+drop index track_room_id_idx;
+
+alter table track
+  drop constraint track_owner_id_room_id_fkey,
+  drop column room_id;
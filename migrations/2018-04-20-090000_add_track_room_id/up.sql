@@ -0,0 +1,6 @@
+alter table track
+  add column room_id uuid,
+  add constraint track_owner_id_room_id_fkey
+    foreign key (owner_id, room_id) references room_agent (agent_id, room_id) on delete cascade;
+
+create index track_room_id_idx on track (room_id);
@@ -0,0 +1,14 @@
+drop trigger set_updated_at on agent;
+
+drop index agent_account_id_idx;
+
+alter table agent
+  drop column updated_at,
+  drop column created_at,
+  drop column data,
+  drop column user_agent,
+  drop column avatar_url,
+  drop column display_name,
+  drop column account_id;
+
+drop table account;
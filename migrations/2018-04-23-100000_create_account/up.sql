@@ -0,0 +1,20 @@
+create table account (
+  id uuid,
+  created_at timestamp not null default now(),
+
+  primary key (id)
+);
+
+alter table agent
+  add column account_id uuid,
+  add column display_name text,
+  add column avatar_url text,
+  add column user_agent text,
+  add column data jsonb not null default '{}',
+  add column created_at timestamp not null default now(),
+  add column updated_at timestamp not null default now(),
+  add foreign key (account_id) references account (id) on delete cascade;
+
+create index agent_account_id_idx on agent (account_id);
+
+select diesel_manage_updated_at('agent');
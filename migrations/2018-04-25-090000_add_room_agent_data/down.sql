@@ -0,0 +1,3 @@
+alter table room_agent
+  drop column version,
+  drop column data;
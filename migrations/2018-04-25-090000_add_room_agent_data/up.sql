@@ -0,0 +1,3 @@
+alter table room_agent
+  add column data jsonb not null default '{}',
+  add column version integer not null default 0;
@@ -0,0 +1 @@
+alter table room_agent drop column role;
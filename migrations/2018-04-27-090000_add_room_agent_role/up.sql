@@ -0,0 +1,4 @@
+-- Members who joined before roles were introduced keep being able to share media.
+alter table room_agent
+  add column role text not null default 'speaker'
+    check (role in ('owner', 'moderator', 'speaker', 'listener'));
@@ -0,0 +1,13 @@
+create table room_ban (
+  room_id uuid,
+  agent_id uuid,
+  account_id uuid,
+  reason text,
+  created_at timestamp not null default now(),
+
+  primary key (room_id, agent_id),
+  foreign key (room_id) references room (id) on delete cascade,
+  foreign key (agent_id) references agent (id) on delete cascade
+);
+
+create index room_ban_room_id_account_id_idx on room_ban (room_id, account_id);
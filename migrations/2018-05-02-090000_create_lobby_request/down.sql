@@ -0,0 +1,3 @@
+drop table lobby_request;
+
+alter table room drop column lobby;
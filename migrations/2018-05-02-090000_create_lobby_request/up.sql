@@ -0,0 +1,14 @@
+alter table room add column lobby boolean not null default false;
+
+create table lobby_request (
+  room_id uuid,
+  agent_id uuid,
+  label text not null,
+  role text not null default 'speaker' check (role in ('owner', 'moderator', 'speaker', 'listener')),
+  data jsonb not null default '{}',
+  created_at timestamp not null default now(),
+
+  primary key (room_id, agent_id),
+  foreign key (room_id) references room (id) on delete cascade,
+  foreign key (agent_id) references agent (id) on delete cascade
+);
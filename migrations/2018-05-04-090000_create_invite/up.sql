@@ -0,0 +1,17 @@
+create table invite (
+  id uuid default uuid_generate_v4(),
+  room_id uuid not null,
+  label text,
+  role text not null default 'speaker' check (role in ('owner', 'moderator', 'speaker', 'listener')),
+  single_use boolean not null default false,
+  uses integer not null default 0,
+  expires_at timestamp not null,
+  created_by uuid,
+  created_at timestamp not null default now(),
+
+  primary key (id),
+  foreign key (room_id) references room (id) on delete cascade,
+  foreign key (created_by) references agent (id) on delete set null
+);
+
+create index invite_room_id_idx on invite (room_id);
@@ -0,0 +1 @@
+alter table room add column sdp_policy jsonb not null default '{}';
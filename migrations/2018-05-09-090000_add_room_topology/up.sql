@@ -0,0 +1 @@
+alter table room add column topology jsonb;
@@ -0,0 +1,4 @@
+delete from room_ban where agent_id not in (select id from agent);
+alter table room_ban
+  add constraint room_ban_agent_id_fkey
+  foreign key (agent_id) references agent (id) on delete cascade;
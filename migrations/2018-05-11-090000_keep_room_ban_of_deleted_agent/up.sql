@@ -0,0 +1,2 @@
+-- Agents are deleted once they go offline, their bans have to stay.
+alter table room_ban drop constraint room_ban_agent_id_fkey;
@@ -0,0 +1 @@
+alter table room drop column owner_account_id;
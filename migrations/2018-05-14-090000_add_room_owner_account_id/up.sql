@@ -0,0 +1,3 @@
+-- Agents of the account which has created the room own it.
+alter table room
+  add column owner_account_id uuid references account (id) on delete set null;
@@ -0,0 +1 @@
+alter table agent drop column service;
@@ -0,0 +1,3 @@
+-- The namespace the agent talks to the service in, messages to the agent go there.
+-- Agents created before namespaces were recorded have none.
+alter table agent add column service text;
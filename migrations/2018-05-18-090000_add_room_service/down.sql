@@ -0,0 +1 @@
+alter table room drop column service;
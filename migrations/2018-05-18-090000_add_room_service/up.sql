@@ -0,0 +1,3 @@
+-- The namespace the room is created in, it's served and its events are published
+-- there only. Rooms created before namespaces were recorded have none.
+alter table room add column service text;
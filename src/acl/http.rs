@@ -0,0 +1,321 @@
+use chrono::Utc;
+use failure;
+use openssl::memcmp;
+use tiny_http::{self, Method, Response};
+use url::form_urlencoded;
+
+use std::io::Read;
+use std::net::SocketAddr;
+
+use acl::{account_id, agent_id, authorize, Access, Request, Store};
+use auth;
+use {AclOptions, AuthOptions};
+
+/// HTTP endpoint for the MQTT broker's authentication plugin.
+///
+/// The broker sends `POST` requests with form encoded `username`, `password`,
+/// `clientid`, `topic` and `acc` (`1` to subscribe, `2` to publish) parameters
+/// to the following paths: `/auth` to authenticate a client, `/superuser` to check
+/// if a client bypasses ACL and `/acl` to check an access to a topic. The response
+/// status `200` allows the client and `403` denies it.
+///
+/// Agents authenticate with a JWT as the password, the service itself with
+/// its own client identifier as the username and the configured password.
+pub struct Server<S> {
+    http: tiny_http::Server,
+    store: S,
+    services: Vec<String>,
+    options: AclOptions,
+    auth: Option<AuthOptions>,
+}
+
+impl<S: Store> Server<S> {
+    pub fn bind(
+        options: AclOptions,
+        auth: Option<AuthOptions>,
+        store: S,
+        services: Vec<String>,
+    ) -> Result<Server<S>, failure::Error> {
+        let http = tiny_http::Server::http(&options.addr).map_err(|e| format_err!("{}", e))?;
+
+        Ok(Server {
+            http,
+            store,
+            services,
+            options,
+            auth,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.http.server_addr()
+    }
+
+    pub fn run(&self) {
+        for mut request in self.http.incoming_requests() {
+            let mut body = String::new();
+            let read = request.as_reader().read_to_string(&mut body);
+
+            let status = match read {
+                Ok(_) => self.handle(request.method(), request.url(), &body),
+                Err(_) => 400,
+            };
+
+            if let Err(e) = request.respond(Response::empty(status)) {
+                println!("ACL error: {}", e);
+            }
+        }
+    }
+
+    fn handle(&self, method: &Method, url: &str, body: &str) -> u16 {
+        if *method != Method::Post {
+            return 405;
+        }
+
+        let params = Params::parse(body);
+        let client_id = match params.client_id.clone() {
+            Some(client_id) => client_id,
+            None => return 400,
+        };
+
+        match url {
+            "/auth" => allow_if(self.authenticate(&client_id, &params)),
+            "/superuser" => allow_if(self.is_superuser(&client_id, &params)),
+            "/acl" => {
+                let (access, topic) = match (params.access, params.topic) {
+                    (Some(access), Some(topic)) => (access, topic),
+                    _ => return 400,
+                };
+
+                let req = Request {
+                    client_id,
+                    access,
+                    topic,
+                };
+
+                match authorize(&self.store, &self.services, &req) {
+                    Ok(is_allowed) => allow_if(is_allowed),
+                    Err(e) => {
+                        println!("ACL error: {}", e);
+                        503
+                    }
+                }
+            }
+            _ => 404,
+        }
+    }
+
+    /// Only the service may use its own client identifier or username, and only
+    /// with its password. Agents must present a token of the agent and account
+    /// their client identifier names.
+    fn authenticate(&self, client_id: &str, params: &Params) -> bool {
+        let password = match params.password {
+            Some(ref password) => password.as_bytes(),
+            None => return false,
+        };
+
+        let superuser = &self.options.superuser;
+        if client_id == superuser || params.username.as_ref() == Some(superuser) {
+            let expected = self.options.superuser_password.as_bytes();
+            return self.is_superuser(client_id, params)
+                && expected.len() == password.len()
+                && memcmp::eq(expected, password);
+        }
+
+        let claims = match self.auth {
+            Some(ref options) => {
+                let token = String::from_utf8_lossy(password);
+                auth::verify(options, &token, Utc::now().timestamp())
+            }
+            None => return false,
+        };
+
+        match (claims, agent_id(client_id), account_id(client_id)) {
+            (Ok(claims), Some(agent_id), Some(account_id)) => {
+                claims.sub == agent_id && claims.account_id == account_id
+            }
+            _ => false,
+        }
+    }
+
+    /// The broker asks about authenticated clients only, and nobody but the
+    /// service itself is authenticated with its username.
+    fn is_superuser(&self, client_id: &str, params: &Params) -> bool {
+        let superuser = &self.options.superuser;
+        client_id == superuser && params.username.as_ref() == Some(superuser)
+    }
+}
+
+fn allow_if(is_allowed: bool) -> u16 {
+    if is_allowed {
+        200
+    } else {
+        403
+    }
+}
+
+#[derive(Debug, Default)]
+struct Params {
+    username: Option<String>,
+    password: Option<String>,
+    client_id: Option<String>,
+    access: Option<Access>,
+    topic: Option<String>,
+}
+
+impl Params {
+    fn parse(body: &str) -> Params {
+        let mut params = Params::default();
+
+        for (key, value) in form_urlencoded::parse(body.as_bytes()) {
+            match &*key {
+                "username" => params.username = Some(value.into_owned()),
+                "password" => params.password = Some(value.into_owned()),
+                "clientid" => params.client_id = Some(value.into_owned()),
+                "topic" => params.topic = Some(value.into_owned()),
+                "acc" => {
+                    params.access = match &*value {
+                        "1" => Some(Access::Subscribe),
+                        "2" => Some(Access::Publish),
+                        _ => None,
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        params
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::net::TcpStream;
+    use std::thread;
+
+    use acl::tests::{build_store, services, ACCOUNT_ID, AGENT_ID, ROOM_ID};
+    use auth::tests::{hs256_options, hs256_token};
+
+    /// A tiny HTTP client playing the role of the broker's plugin.
+    fn post(addr: SocketAddr, path: &str, body: &str) -> u16 {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "POST {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\
+             Content-Type: application/x-www-form-urlencoded\r\n\
+             Content-Length: {}\r\n\r\n{}",
+            path,
+            body.len(),
+            body
+        ).unwrap();
+
+        let mut resp = String::new();
+        stream.read_to_string(&mut resp).unwrap();
+
+        resp.split_whitespace().nth(1).unwrap().parse().unwrap()
+    }
+
+    fn start_server() -> SocketAddr {
+        let options = AclOptions {
+            addr: "127.0.0.1:0".to_owned(),
+            superuser: "signals".to_owned(),
+            superuser_password: "password".to_owned(),
+        };
+
+        let server = Server::bind(options, Some(hs256_options()), build_store(), services())
+            .unwrap();
+        let addr = server.local_addr();
+        thread::spawn(move || server.run());
+        addr
+    }
+
+    fn form(params: &[(&str, &str)]) -> String {
+        form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(params)
+            .finish()
+    }
+
+    fn token(agent_id: &str, account_id: &str) -> String {
+        let claims = format!(
+            r#"{{"sub":"{}","account_id":"{}","exp":4102444800}}"#,
+            agent_id, account_id
+        );
+        hs256_token(b"secret", &claims)
+    }
+
+    #[test]
+    fn auth() {
+        let addr = start_server();
+        let client_id = format!("{}.{}", AGENT_ID, ACCOUNT_ID);
+        let token = token(AGENT_ID, ACCOUNT_ID);
+        let auth = |client_id: &str, username: &str, password: &str| {
+            let body = form(&[
+                ("clientid", client_id),
+                ("username", username),
+                ("password", password),
+            ]);
+            post(addr, "/auth", &body)
+        };
+
+        assert_eq!(auth(&client_id, "agent", &token), 200);
+        assert_eq!(auth(&client_id, "agent", "password"), 403);
+        assert_eq!(auth(&client_id, "signals", &token), 403);
+        assert_eq!(post(addr, "/auth", &form(&[("clientid", client_id.as_str())])), 403);
+
+        let another_client_id = format!("{}.{}", ROOM_ID, ACCOUNT_ID);
+        assert_eq!(auth(&another_client_id, "agent", &token), 403);
+
+        assert_eq!(auth("signals", "signals", "password"), 200);
+        assert_eq!(auth("signals", "signals", "foo"), 403);
+        assert_eq!(auth("signals", "agent", &token), 403);
+        assert_eq!(auth("foo", "foo", &token), 403);
+    }
+
+    #[test]
+    fn superuser() {
+        let addr = start_server();
+        let client_id = format!("{}.{}", AGENT_ID, ACCOUNT_ID);
+        let superuser = |client_id: &str, username: &str| {
+            let body = form(&[("clientid", client_id), ("username", username)]);
+            post(addr, "/superuser", &body)
+        };
+
+        assert_eq!(superuser("signals", "signals"), 200);
+        assert_eq!(superuser("signals", "agent"), 403);
+        assert_eq!(superuser(&client_id, "signals"), 403);
+        assert_eq!(post(addr, "/superuser", &form(&[("clientid", "signals")])), 403);
+    }
+
+    #[test]
+    fn acl() {
+        let addr = start_server();
+        let client_id = format!("{}.{}", AGENT_ID, ACCOUNT_ID);
+        let agents_topic = format!(
+            "apps/signals.netology-group.services/api/v1/rooms/{}/agents",
+            ROOM_ID
+        );
+        let tracks_topic = format!(
+            "apps/signals.netology-group.services/api/v1/rooms/{}/tracks",
+            ROOM_ID
+        );
+
+        let body = form(&[
+            ("clientid", client_id.as_str()),
+            ("topic", agents_topic.as_str()),
+            ("acc", "1"),
+        ]);
+        assert_eq!(post(addr, "/acl", &body), 200);
+
+        let body = form(&[
+            ("clientid", client_id.as_str()),
+            ("topic", tracks_topic.as_str()),
+            ("acc", "1"),
+        ]);
+        assert_eq!(post(addr, "/acl", &body), 403);
+
+        let body = form(&[("clientid", client_id.as_str()), ("topic", agents_topic.as_str())]);
+        assert_eq!(post(addr, "/acl", &body), 400);
+    }
+}
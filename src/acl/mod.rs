@@ -0,0 +1,302 @@
+use diesel::prelude::*;
+use diesel;
+use failure;
+use uuid::Uuid;
+
+use models;
+use schema::{room, room_agent, subscription};
+use topic::{AgentTopicKind, PingTopicKind, ResourceKind, Topic};
+use DbPool;
+
+pub use acl::http::Server;
+
+mod http;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Access {
+    Subscribe,
+    Publish,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Request {
+    pub client_id: String,
+    pub access: Access,
+    pub topic: String,
+}
+
+impl Request {
+    pub fn agent_id(&self) -> Option<Uuid> {
+        agent_id(&self.client_id)
+    }
+}
+
+/// Client identifiers of agents look like `$AGENT_ID.$ACCOUNT_ID`.
+pub fn agent_id(client_id: &str) -> Option<Uuid> {
+    let agent_id = client_id.split('.').next().unwrap_or("");
+    Uuid::parse_str(agent_id).ok()
+}
+
+pub fn account_id(client_id: &str) -> Option<Uuid> {
+    let mut parts = client_id.split('.');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(_), Some(account_id), None) => Uuid::parse_str(account_id).ok(),
+        _ => None,
+    }
+}
+
+pub trait Store {
+    /// Whether the room exists and is served in the namespace.
+    fn is_served_in(&self, room_id: Uuid, service: &str) -> Result<bool, failure::Error>;
+
+    fn is_member(&self, agent_id: Uuid, room_id: Uuid) -> Result<bool, failure::Error>;
+
+    fn has_subscription(
+        &self,
+        agent_id: Uuid,
+        room_id: Uuid,
+        resource: ResourceKind,
+    ) -> Result<bool, failure::Error>;
+}
+
+pub struct DbStore {
+    pool: DbPool,
+}
+
+impl DbStore {
+    pub fn new(pool: DbPool) -> DbStore {
+        DbStore { pool }
+    }
+}
+
+impl Store for DbStore {
+    fn is_served_in(&self, room_id: Uuid, service: &str) -> Result<bool, failure::Error> {
+        let conn = self.pool.get()?;
+        let room = room::table
+            .find(room_id)
+            .first::<models::Room>(&*conn)
+            .optional()?;
+
+        Ok(room.map_or(false, |room| room.is_served_in(service)))
+    }
+
+    fn is_member(&self, agent_id: Uuid, room_id: Uuid) -> Result<bool, failure::Error> {
+        let conn = self.pool.get()?;
+        let query = room_agent::table.find((agent_id, room_id));
+
+        diesel::select(diesel::dsl::exists(query))
+            .get_result(&*conn)
+            .map_err(failure::Error::from)
+    }
+
+    fn has_subscription(
+        &self,
+        agent_id: Uuid,
+        room_id: Uuid,
+        resource: ResourceKind,
+    ) -> Result<bool, failure::Error> {
+        let conn = self.pool.get()?;
+        let query = subscription::table.find((agent_id, room_id, resource));
+
+        diesel::select(diesel::dsl::exists(query))
+            .get_result(&*conn)
+            .map_err(failure::Error::from)
+    }
+}
+
+/// Decides whether an MQTT client may subscribe or publish to the topic.
+///
+/// Agents may publish to their own `out` topic and subscribe to their own `in` topic.
+/// They also publish to their own `state` topic, the last will included.
+/// An app topic of a room is available for subscription to members of the room
+/// which have created a corresponding subscription, in the namespace the room is
+/// served in. Only topics of the served services are taken into account.
+pub fn authorize<S: Store>(
+    store: &S,
+    services: &[String],
+    req: &Request,
+) -> Result<bool, failure::Error> {
+    let agent_id = match req.agent_id() {
+        Some(agent_id) => agent_id,
+        None => return Ok(false),
+    };
+
+    let topic = match Topic::parse(&req.topic) {
+        Ok(topic) => topic,
+        Err(_) => return Ok(false),
+    };
+
+    match (req.access, topic) {
+        (Access::Publish, Topic::Ping(PingTopicKind::Ping)) => Ok(true),
+        (Access::Subscribe, Topic::Ping(PingTopicKind::Pong)) => Ok(true),
+        (_, Topic::Agent(ref t)) if !services.contains(&t.service) => Ok(false),
+        (_, Topic::App(ref t)) if !services.contains(&t.service) => Ok(false),
+        (Access::Publish, Topic::Agent(t)) => {
+            Ok(t.agent_id == agent_id && t.kind == AgentTopicKind::Out)
+        }
+        (Access::Subscribe, Topic::Agent(t)) => {
+            Ok(t.agent_id == agent_id && t.kind == AgentTopicKind::In)
+        }
+        (Access::Publish, Topic::State(t)) => Ok(t.agent_id == agent_id),
+        (Access::Subscribe, Topic::App(t)) => {
+            Ok(store.is_served_in(t.room_id, &t.service)?
+                && store.is_member(agent_id, t.room_id)?
+                && store.has_subscription(agent_id, t.room_id, t.resource)?)
+        }
+        _ => Ok(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{HashMap, HashSet};
+
+    pub const AGENT_ID: &str = "e19c94cf-53eb-4048-9c94-7ae74ff6d912";
+    pub const ACCOUNT_ID: &str = "5f9e4b5c-2f8e-4d55-9b5b-1b0e8e2a7a3c";
+    pub const ROOM_ID: &str = "058df470-73ea-43a4-b36c-e4615cad468e";
+
+    #[derive(Default)]
+    pub struct TestStore {
+        /// Namespaces of rooms, the rest are served in any.
+        pub room_services: HashMap<Uuid, String>,
+        pub members: HashSet<(Uuid, Uuid)>,
+        pub subscriptions: HashSet<(Uuid, Uuid, ResourceKind)>,
+    }
+
+    impl Store for TestStore {
+        fn is_served_in(&self, room_id: Uuid, service: &str) -> Result<bool, failure::Error> {
+            Ok(self.room_services.get(&room_id).map_or(true, |s| s == service))
+        }
+
+        fn is_member(&self, agent_id: Uuid, room_id: Uuid) -> Result<bool, failure::Error> {
+            Ok(self.members.contains(&(agent_id, room_id)))
+        }
+
+        fn has_subscription(
+            &self,
+            agent_id: Uuid,
+            room_id: Uuid,
+            resource: ResourceKind,
+        ) -> Result<bool, failure::Error> {
+            Ok(self.subscriptions.contains(&(agent_id, room_id, resource)))
+        }
+    }
+
+    pub fn build_store() -> TestStore {
+        let agent_id = Uuid::parse_str(AGENT_ID).unwrap();
+        let room_id = Uuid::parse_str(ROOM_ID).unwrap();
+
+        let mut store = TestStore::default();
+        store.members.insert((agent_id, room_id));
+        store
+            .subscriptions
+            .insert((agent_id, room_id, ResourceKind::Agents));
+        store
+    }
+
+    pub fn services() -> Vec<String> {
+        vec!["signals.netology-group.services".to_owned()]
+    }
+
+    fn request(access: Access, topic: &str) -> Request {
+        Request {
+            client_id: format!("{}.{}", AGENT_ID, ACCOUNT_ID),
+            access,
+            topic: topic.to_owned(),
+        }
+    }
+
+    #[test]
+    fn agent_id_from_client_id() {
+        let req = request(Access::Publish, "ping");
+        assert_eq!(req.agent_id(), Some(Uuid::parse_str(AGENT_ID).unwrap()));
+
+        let req = Request {
+            client_id: "foo".to_owned(),
+            access: Access::Publish,
+            topic: "ping".to_owned(),
+        };
+        assert_eq!(req.agent_id(), None);
+    }
+
+    #[test]
+    fn account_id_from_client_id() {
+        let client_id = format!("{}.{}", AGENT_ID, ACCOUNT_ID);
+        assert_eq!(account_id(&client_id), Some(Uuid::parse_str(ACCOUNT_ID).unwrap()));
+        assert_eq!(account_id(AGENT_ID), None);
+        assert_eq!(account_id(&format!("{}.foo", client_id)), None);
+    }
+
+    #[test]
+    fn authorize_agent_topics() {
+        let store = build_store();
+        let out_topic = format!(
+            "agents/{}/out/signals.netology-group.services/api/v1",
+            AGENT_ID
+        );
+        let in_topic = format!(
+            "agents/{}/in/signals.netology-group.services/api/v1",
+            AGENT_ID
+        );
+        let other_topic = "agents/55e813bc-0c9b-4270-9f7f-81e5ffcfc9ff/out/signals.netology-group.services/api/v1";
+
+        assert!(authorize(&store, &services(), &request(Access::Publish, &out_topic)).unwrap());
+        assert!(!authorize(&store, &services(), &request(Access::Subscribe, &out_topic)).unwrap());
+        assert!(authorize(&store, &services(), &request(Access::Subscribe, &in_topic)).unwrap());
+        assert!(!authorize(&store, &services(), &request(Access::Publish, &in_topic)).unwrap());
+        assert!(!authorize(&store, &services(), &request(Access::Publish, other_topic)).unwrap());
+    }
+
+    #[test]
+    fn authorize_state_topics() {
+        let store = build_store();
+        let topic = format!("agents/{}/state/api/v1", AGENT_ID);
+        let other_topic = "agents/55e813bc-0c9b-4270-9f7f-81e5ffcfc9ff/state/api/v1";
+
+        assert!(authorize(&store, &services(), &request(Access::Publish, &topic)).unwrap());
+        assert!(!authorize(&store, &services(), &request(Access::Subscribe, &topic)).unwrap());
+        assert!(!authorize(&store, &services(), &request(Access::Publish, other_topic)).unwrap());
+    }
+
+    #[test]
+    fn authorize_app_topics() {
+        let store = build_store();
+        let agents_topic = format!(
+            "apps/signals.netology-group.services/api/v1/rooms/{}/agents",
+            ROOM_ID
+        );
+        let tracks_topic = format!(
+            "apps/signals.netology-group.services/api/v1/rooms/{}/tracks",
+            ROOM_ID
+        );
+
+        assert!(authorize(&store, &services(), &request(Access::Subscribe, &agents_topic)).unwrap());
+        assert!(!authorize(&store, &services(), &request(Access::Publish, &agents_topic)).unwrap());
+        assert!(!authorize(&store, &services(), &request(Access::Subscribe, &tracks_topic)).unwrap());
+        assert!(!authorize(&store, &services(), &request(Access::Subscribe, "apps/foo")).unwrap());
+
+        let topic = format!("apps/signals.example.org/api/v1/rooms/{}/agents", ROOM_ID);
+        assert!(!authorize(&store, &services(), &request(Access::Subscribe, &topic)).unwrap());
+    }
+
+    #[test]
+    fn authorize_app_topics_of_room_namespace() {
+        let mut store = build_store();
+        let room_id = Uuid::parse_str(ROOM_ID).unwrap();
+        store
+            .room_services
+            .insert(room_id, "signals.netology-group.services".to_owned());
+
+        let mut services = services();
+        services.push("signals.example.org".to_owned());
+
+        let topic = |service: &str| {
+            let topic = format!("apps/{}/api/v1/rooms/{}/agents", service, ROOM_ID);
+            request(Access::Subscribe, &topic)
+        };
+
+        assert!(authorize(&store, &services, &topic("signals.netology-group.services")).unwrap());
+        assert!(!authorize(&store, &services, &topic("signals.example.org")).unwrap());
+    }
+}
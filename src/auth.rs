@@ -0,0 +1,273 @@
+use chrono::Utc;
+use openssl::error::ErrorStack;
+use openssl::hash::MessageDigest;
+use openssl::memcmp;
+use openssl::pkey::PKey;
+use openssl::sign::{Signer, Verifier};
+use serde_json;
+use uuid::Uuid;
+
+use base64;
+use messages::{Envelope, EnvelopeSubject};
+use topic::Topic;
+use AuthOptions;
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "Missing subject")]
+    MissingSubject,
+
+    #[fail(display = "Missing token")]
+    MissingToken,
+
+    #[fail(display = "Malformed token")]
+    Malformed,
+
+    #[fail(display = "Unsupported algorithm: {}", _0)]
+    UnsupportedAlgorithm(String),
+
+    #[fail(display = "Invalid signature")]
+    InvalidSignature,
+
+    #[fail(display = "Token has expired")]
+    Expired,
+
+    #[fail(display = "Subject doesn't match the topic")]
+    SubjectMismatch,
+
+    #[fail(display = "{}", _0)]
+    Crypto(#[cause] ErrorStack),
+}
+
+impl From<ErrorStack> for Error {
+    fn from(e: ErrorStack) -> Self {
+        Error::Crypto(e)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Header {
+    alg: String,
+}
+
+/// Claims of the token the subject of an envelope is derived from.
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct Claims {
+    /// The agent identifier.
+    pub sub: Uuid,
+    pub account_id: Uuid,
+    /// Expiration time, seconds since the epoch.
+    pub exp: i64,
+}
+
+impl From<Claims> for EnvelopeSubject {
+    fn from(claims: Claims) -> Self {
+        EnvelopeSubject {
+            account_id: claims.account_id,
+            agent_id: claims.sub,
+        }
+    }
+}
+
+/// Derives the subject of the envelope from its token. The subject must be
+/// the agent the topic belongs to and match `sub` of the envelope if there's one.
+pub fn authenticate(
+    options: &AuthOptions,
+    envelope: &Envelope,
+    topic: &Topic,
+) -> Result<EnvelopeSubject, Error> {
+    let token = envelope.token.as_ref().ok_or(Error::MissingToken)?;
+    let claims = verify(options, token, Utc::now().timestamp())?;
+    let subject = EnvelopeSubject::from(claims);
+
+    let topic_agent_id = match *topic {
+        Topic::Agent(ref t) => Some(t.agent_id),
+        Topic::State(ref t) => Some(t.agent_id),
+        Topic::Ping(_) | Topic::App(_) => None,
+    };
+
+    if topic_agent_id.map_or(false, |agent_id| agent_id != subject.agent_id) {
+        return Err(Error::SubjectMismatch);
+    }
+
+    if envelope.sub.as_ref().map_or(false, |sub| *sub != subject) {
+        return Err(Error::SubjectMismatch);
+    }
+
+    Ok(subject)
+}
+
+/// Checks the signature of a JWT with one of the configured keys and its expiration time.
+pub fn verify(options: &AuthOptions, token: &str, now: i64) -> Result<Claims, Error> {
+    let parts: Vec<&str> = token.split('.').collect();
+    if parts.len() != 3 {
+        return Err(Error::Malformed);
+    }
+
+    let header = base64::decode_url(parts[0]).ok_or(Error::Malformed)?;
+    let header: Header = serde_json::from_slice(&header).map_err(|_| Error::Malformed)?;
+    let signature = base64::decode_url(parts[2]).ok_or(Error::Malformed)?;
+    let signing_input = &token[..parts[0].len() + 1 + parts[1].len()];
+
+    let is_valid = match (header.alg.as_str(), options) {
+        (
+            "HS256",
+            &AuthOptions {
+                hs256_secret: Some(ref secret),
+                ..
+            },
+        ) => {
+            let key = PKey::hmac(secret)?;
+            let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+            signer.update(signing_input.as_bytes())?;
+            let expected = signer.sign_to_vec()?;
+
+            expected.len() == signature.len() && memcmp::eq(&expected, &signature)
+        }
+        (
+            "RS256",
+            &AuthOptions {
+                rs256_public_key: Some(ref pem),
+                ..
+            },
+        ) => {
+            let key = PKey::public_key_from_pem(pem)?;
+            let mut verifier = Verifier::new(MessageDigest::sha256(), &key)?;
+            verifier.update(signing_input.as_bytes())?;
+            verifier.verify(&signature)?
+        }
+        (alg, _) => return Err(Error::UnsupportedAlgorithm(alg.to_owned())),
+    };
+
+    if !is_valid {
+        return Err(Error::InvalidSignature);
+    }
+
+    let claims = base64::decode_url(parts[1]).ok_or(Error::Malformed)?;
+    let claims: Claims = serde_json::from_slice(&claims).map_err(|_| Error::Malformed)?;
+
+    if claims.exp <= now {
+        return Err(Error::Expired);
+    }
+
+    Ok(claims)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use openssl::rsa::Rsa;
+
+    const SECRET: &[u8] = b"secret";
+    const NOW: i64 = 1_525_000_000;
+
+    fn claims() -> String {
+        r#"{
+            "sub": "1154b35c-e5b0-4a42-8ab2-d4967ce38c9e",
+            "account_id": "31517b3d-5a14-4a14-a6c5-2ba63f7375d3",
+            "exp": 1525000060
+        }"#.to_owned()
+    }
+
+    pub fn hs256_token(secret: &[u8], claims: &str) -> String {
+        let input = format!(
+            "{}.{}",
+            base64::encode_url(br#"{"alg":"HS256","typ":"JWT"}"#),
+            base64::encode_url(claims.as_bytes())
+        );
+
+        let key = PKey::hmac(secret).unwrap();
+        let mut signer = Signer::new(MessageDigest::sha256(), &key).unwrap();
+        signer.update(input.as_bytes()).unwrap();
+        let signature = signer.sign_to_vec().unwrap();
+
+        format!("{}.{}", input, base64::encode_url(&signature))
+    }
+
+    pub fn hs256_options() -> AuthOptions {
+        AuthOptions {
+            hs256_secret: Some(SECRET.to_vec()),
+            rs256_public_key: None,
+        }
+    }
+
+    #[test]
+    fn verify_hs256() {
+        let token = hs256_token(SECRET, &claims());
+        let claims = verify(&hs256_options(), &token, NOW).unwrap();
+
+        assert_eq!(
+            claims.sub,
+            Uuid::parse_str("1154b35c-e5b0-4a42-8ab2-d4967ce38c9e").unwrap()
+        );
+        assert_eq!(claims.exp, 1_525_000_060);
+    }
+
+    #[test]
+    fn verify_hs256_with_another_secret() {
+        let token = hs256_token(b"another secret", &claims());
+
+        match verify(&hs256_options(), &token, NOW) {
+            Err(Error::InvalidSignature) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn verify_expired() {
+        let token = hs256_token(SECRET, &claims());
+
+        match verify(&hs256_options(), &token, NOW + 60) {
+            Err(Error::Expired) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn verify_unconfigured_algorithm() {
+        let token = hs256_token(SECRET, &claims());
+        let options = AuthOptions {
+            hs256_secret: None,
+            rs256_public_key: Some(vec![]),
+        };
+
+        match verify(&options, &token, NOW) {
+            Err(Error::UnsupportedAlgorithm(ref alg)) if alg == "HS256" => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn verify_rs256() {
+        let rsa = Rsa::generate(2048).unwrap();
+        let key = PKey::from_rsa(rsa).unwrap();
+
+        let input = format!(
+            "{}.{}",
+            base64::encode_url(br#"{"alg":"RS256","typ":"JWT"}"#),
+            base64::encode_url(claims().as_bytes())
+        );
+
+        let mut signer = Signer::new(MessageDigest::sha256(), &key).unwrap();
+        signer.update(input.as_bytes()).unwrap();
+        let signature = signer.sign_to_vec().unwrap();
+        let token = format!("{}.{}", input, base64::encode_url(&signature));
+
+        let options = AuthOptions {
+            hs256_secret: None,
+            rs256_public_key: Some(key.public_key_to_pem().unwrap()),
+        };
+
+        assert!(verify(&options, &token, NOW).is_ok());
+    }
+
+    #[test]
+    fn verify_malformed() {
+        for token in &["", "a.b", "a.b.c.d", "!.b.c"] {
+            match verify(&hs256_options(), token, NOW) {
+                Err(Error::Malformed) => {}
+                res => panic!("Unexpected result for {:?}: {:?}", token, res),
+            }
+        }
+    }
+}
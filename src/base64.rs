@@ -0,0 +1,90 @@
+const STANDARD: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const URL_SAFE: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Encodes padded base64, as TURN servers expect passwords to be encoded.
+pub fn encode(input: &[u8]) -> String {
+    encode_with(STANDARD, true, input)
+}
+
+/// Encodes unpadded base64url, as JWT parts are encoded.
+pub fn encode_url(input: &[u8]) -> String {
+    encode_with(URL_SAFE, false, input)
+}
+
+/// Decodes unpadded base64url, as JWT parts are encoded.
+pub fn decode_url(input: &str) -> Option<Vec<u8>> {
+    let value = |c: u8| URL_SAFE.iter().position(|&a| a == c).map(|i| i as u32);
+
+    let input = input.as_bytes();
+    if input.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut output = Vec::with_capacity(input.len() * 3 / 4);
+    for chunk in input.chunks(4) {
+        let mut acc = 0;
+        for &c in chunk {
+            acc = (acc << 6) | value(c)?;
+        }
+        acc <<= 6 * (4 - chunk.len() as u32);
+
+        let bytes = [(acc >> 16) as u8, (acc >> 8) as u8, acc as u8];
+        output.extend_from_slice(&bytes[..chunk.len() - 1]);
+    }
+
+    Some(output)
+}
+
+fn encode_with(alphabet: &[u8], pad: bool, input: &[u8]) -> String {
+    let mut output = String::with_capacity((input.len() + 2) / 3 * 4);
+    for chunk in input.chunks(3) {
+        let mut acc = 0u32;
+        for (i, &byte) in chunk.iter().enumerate() {
+            acc |= u32::from(byte) << (16 - 8 * i);
+        }
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                output.push(alphabet[(acc >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else if pad {
+                output.push('=');
+            }
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_padding() {
+        assert_eq!(encode(b""), "");
+        assert_eq!(encode(b"f"), "Zg==");
+        assert_eq!(encode(b"fo"), "Zm8=");
+        assert_eq!(encode(b"foo"), "Zm9v");
+        assert_eq!(encode(b"foob"), "Zm9vYg==");
+        assert_eq!(encode(&[0xfb, 0xff]), "+/8=");
+    }
+
+    #[test]
+    fn encode_url_without_padding() {
+        assert_eq!(encode_url(b"f"), "Zg");
+        assert_eq!(encode_url(b"foob"), "Zm9vYg");
+        assert_eq!(encode_url(&[0xfb, 0xff]), "-_8");
+    }
+
+    #[test]
+    fn decode_url_padding() {
+        assert_eq!(decode_url("").unwrap(), b"");
+        assert_eq!(decode_url("Zg").unwrap(), b"f");
+        assert_eq!(decode_url("Zm8").unwrap(), b"fo");
+        assert_eq!(decode_url("Zm9v").unwrap(), b"foo");
+        assert_eq!(decode_url("-_8").unwrap(), [0xfb, 0xff]);
+        assert_eq!(decode_url("Zm9vY"), None);
+        assert_eq!(decode_url("Zm9v="), None);
+        assert_eq!(decode_url("+/8"), None);
+    }
+}
@@ -12,3 +12,9 @@ impl<'a> From<NomError<'a>> for ParseError {
         ParseError(kind)
     }
 }
+
+impl From<nom::ErrorKind> for ParseError {
+    fn from(kind: nom::ErrorKind) -> Self {
+        ParseError(kind)
+    }
+}
@@ -0,0 +1,114 @@
+use openssl::error::ErrorStack;
+use openssl::hash::MessageDigest;
+use openssl::memcmp;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use uuid::Uuid;
+
+use std::fmt;
+use std::str::FromStr;
+
+/// A token of an invitation to a room: the identifier of the invitation and
+/// a signature binding it to the room, `$INVITE_ID.$SIGNATURE`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token {
+    pub id: Uuid,
+    signature: String,
+}
+
+impl Token {
+    pub fn sign(secret: &[u8], id: Uuid, room_id: Uuid) -> Result<Token, ErrorStack> {
+        let signature = signature(secret, id, room_id)?;
+        Ok(Token { id, signature })
+    }
+
+    /// Checks that the token has been issued with the secret for the room.
+    pub fn verify(&self, secret: &[u8], room_id: Uuid) -> Result<bool, ErrorStack> {
+        let expected = signature(secret, self.id, room_id)?;
+        let is_valid = expected.len() == self.signature.len()
+            && memcmp::eq(expected.as_bytes(), self.signature.as_bytes());
+
+        Ok(is_valid)
+    }
+}
+
+fn signature(secret: &[u8], id: Uuid, room_id: Uuid) -> Result<String, ErrorStack> {
+    let key = PKey::hmac(secret)?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+    signer.update(id.as_bytes())?;
+    signer.update(room_id.as_bytes())?;
+
+    let signature = signer
+        .sign_to_vec()?
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+
+    Ok(signature)
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}", self.id.simple(), self.signature)
+    }
+}
+
+#[derive(Debug, Fail)]
+#[fail(display = "Invalid invite token")]
+pub struct InvalidToken;
+
+impl FromStr for Token {
+    type Err = InvalidToken;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, '.');
+        let id = parts.next().ok_or(InvalidToken)?;
+        let signature = parts.next().ok_or(InvalidToken)?;
+
+        let id = Uuid::parse_str(id).map_err(|_| InvalidToken)?;
+        let is_hex = signature.chars().all(|c| c.is_digit(16) && !c.is_uppercase());
+        if signature.is_empty() || !is_hex {
+            return Err(InvalidToken);
+        }
+
+        Ok(Token {
+            id,
+            signature: signature.to_owned(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &[u8] = b"secret";
+
+    fn ids() -> (Uuid, Uuid) {
+        let id = Uuid::parse_str("3ac4dfd4-4bb6-4e3e-9c8a-57e5c4d57c3b").unwrap();
+        let room_id = Uuid::parse_str("050b7c6f-795c-4cb4-aeea-5ee3f9083de2").unwrap();
+        (id, room_id)
+    }
+
+    #[test]
+    fn sign_and_verify() {
+        let (id, room_id) = ids();
+        let token = Token::sign(SECRET, id, room_id).unwrap();
+
+        assert!(token.verify(SECRET, room_id).unwrap());
+        assert!(!token.verify(b"another secret", room_id).unwrap());
+        assert!(!token.verify(SECRET, id).unwrap());
+    }
+
+    #[test]
+    fn parse_token() {
+        let (id, room_id) = ids();
+        let token = Token::sign(SECRET, id, room_id).unwrap();
+
+        assert_eq!(Token::from_str(&token.to_string()).unwrap(), token);
+        assert!(Token::from_str(&id.simple().to_string()).is_err());
+        assert!(Token::from_str(&format!("{}.", id.simple())).is_err());
+        assert!(Token::from_str(&format!("{}.xyz", id.simple())).is_err());
+        assert!(Token::from_str("foo.abc").is_err());
+    }
+}
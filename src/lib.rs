@@ -6,33 +6,51 @@ extern crate jsonrpc_core;
 extern crate jsonrpc_macros;
 #[macro_use]
 extern crate nom;
+extern crate openssl;
+#[cfg(test)]
+extern crate quickcheck;
 extern crate rumqtt;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde_json;
+extern crate tiny_http;
+extern crate url;
 extern crate uuid;
 #[macro_use]
 extern crate failure;
 
+use diesel::prelude::*;
 use diesel::{r2d2, PgConnection};
 use rumqtt::{Message as MqttMessage, MqttCallback, MqttClient, MqttOptions, QoS};
 use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
 
 use messages::{Envelope, EventKind, Notification};
+use rate_limit::{Limits, RateLimiter};
+use schema::{agent, room};
 use topic::{AgentTopic, AppTopic, ResourceKind, Topic};
 
 macro_rules! establish_connection {
     ($pool:expr) => {
-        &$pool.get().expect("Error establishing DB connection")
+        &$pool.get()?
     };
 }
 
+pub mod acl;
+pub mod auth;
+pub mod base64;
 pub mod error;
+pub mod invite;
 pub mod messages;
+pub mod metrics;
+pub mod rate_limit;
 pub mod rpc;
+pub mod sdp;
+pub mod signaling;
 pub mod topic;
+pub mod topology;
+pub mod turn;
 pub mod version;
 
 pub mod models;
@@ -44,15 +62,74 @@ type DbPool = r2d2::Pool<r2d2::ConnectionManager<PgConnection>>;
 pub struct Options {
     pub mqtt: MqttOptions,
     pub database_url: String,
+    /// Service names the application serves, the first one is the default one.
+    pub services: Vec<String>,
+    pub acl: Option<AclOptions>,
+    /// Subjects of envelopes are authenticated by tokens if set.
+    pub auth: Option<AuthOptions>,
+    pub rate_limit: Option<RateLimitOptions>,
+    pub metrics: Option<MetricsOptions>,
+    pub rpc: rpc::Config,
+}
+
+#[derive(Clone)]
+pub struct AclOptions {
+    pub addr: String,
+    /// The client identifier and username of the service itself.
+    pub superuser: String,
+    pub superuser_password: String,
+}
+
+/// Keys JWTs are verified with, at least one of them.
+#[derive(Clone)]
+pub struct AuthOptions {
+    pub hs256_secret: Option<Vec<u8>>,
+    /// A PEM encoded public key.
+    pub rs256_public_key: Option<Vec<u8>>,
+}
+
+/// Budgets of calls an agent and all agents of an account may make.
+#[derive(Clone)]
+pub struct RateLimitOptions {
+    pub agent: Limits,
+    pub account: Limits,
+}
+
+#[derive(Clone)]
+pub struct MetricsOptions {
+    pub addr: String,
+}
+
+/// State shared by the messages the application handles.
+struct Context {
+    services: Vec<String>,
+    config: Arc<rpc::Config>,
+    auth: Option<AuthOptions>,
+    rate_limiter: Option<Arc<Mutex<RateLimiter>>>,
+    sessions: Arc<Mutex<signaling::Sessions>>,
+    planner: Arc<Mutex<topology::Planner>>,
 }
 
 pub fn try_run(options: Options) -> Result<(), failure::Error> {
     let database_url = options.database_url.clone();
+    let rate_limiter = options.rate_limit.clone().map(|rate_limit| {
+        let rate_limiter = RateLimiter::new(rate_limit.agent, rate_limit.account);
+        Arc::new(Mutex::new(rate_limiter))
+    });
+
+    let context = Context {
+        services: options.services.clone(),
+        config: Arc::new(options.rpc.clone()),
+        auth: options.auth.clone(),
+        rate_limiter: rate_limiter.clone(),
+        sessions: Arc::new(Mutex::new(signaling::Sessions::default())),
+        planner: Arc::new(Mutex::new(topology::Planner::default())),
+    };
 
     let (tx, rx) = mpsc::channel::<MqttMessage>();
     let tx = Mutex::new(tx);
 
-    let (notification_tx, notification_rx) = mpsc::channel::<Notification>();
+    let (notification_tx, notification_rx) = mpsc::channel::<(String, Notification)>();
 
     let callbacks = MqttCallback::new().on_message(move |msg| {
         let tx = tx.lock().unwrap();
@@ -60,19 +137,44 @@ pub fn try_run(options: Options) -> Result<(), failure::Error> {
     });
 
     let mut client = MqttClient::start(options.mqtt, Some(callbacks))?;
-    subscribe(&mut client)?;
+    subscribe(&mut client, &options.services)?;
 
     let client = Arc::new(Mutex::new(client));
     let mut handles = vec![];
 
+    if let Some(acl_options) = options.acl {
+        let manager = r2d2::ConnectionManager::<PgConnection>::new(options.database_url);
+        let pool = r2d2::Pool::builder()
+            .build(manager)
+            .expect("Error creating pool.");
+
+        let store = acl::DbStore::new(pool);
+        let server = acl::Server::bind(acl_options, options.auth, store, options.services)?;
+
+        let handle = thread::spawn(move || server.run());
+        handles.push(handle);
+    }
+
+    if let Some(metrics_options) = options.metrics {
+        let rate_limiter = rate_limiter.unwrap_or_else(|| {
+            let rate_limiter = RateLimiter::new(Limits::default(), Limits::default());
+            Arc::new(Mutex::new(rate_limiter))
+        });
+        let server = metrics::Server::bind(&metrics_options.addr, rate_limiter)?;
+
+        let handle = thread::spawn(move || server.run());
+        handles.push(handle);
+    }
+
+    let manager = r2d2::ConnectionManager::<PgConnection>::new(database_url);
+    let pool = r2d2::Pool::builder()
+        .build(manager)
+        .expect("Error creating pool.");
+
     let handle = thread::spawn({
         let client = Arc::clone(&client);
+        let pool = pool.clone();
         move || {
-            let manager = r2d2::ConnectionManager::<PgConnection>::new(database_url);
-            let pool = r2d2::Pool::builder()
-                .build(manager)
-                .expect("Error creating pool.");
-
             let server = rpc::build_server();
 
             for msg in rx.iter() {
@@ -80,7 +182,9 @@ pub fn try_run(options: Options) -> Result<(), failure::Error> {
                 let pool = pool.clone();
                 let mut client = client.lock().unwrap();
 
-                if let Err(e) = handle_message(&server, &mut client, &msg, notification_tx, pool) {
+                if let Err(e) =
+                    handle_message(&server, &mut client, &msg, &context, notification_tx, pool)
+                {
                     use std::io::Write;
 
                     let stderr = &mut ::std::io::stderr();
@@ -95,41 +199,32 @@ pub fn try_run(options: Options) -> Result<(), failure::Error> {
     let handle = thread::spawn({
         let client = Arc::clone(&client);
         move || {
-            for notification in notification_rx.iter() {
-                let topic = match notification {
-                    Notification::Event(ref kind) => {
-                        let app_topic = match *kind {
-                            EventKind::AgentJoin(ref event) => AppTopic {
-                                room_id: event.room_id,
-                                resource: ResourceKind::Agents,
-                            },
-                            EventKind::AgentLeave(ref event) => AppTopic {
-                                room_id: event.room_id,
-                                resource: ResourceKind::Agents,
-                            },
-                            EventKind::TrackCreate(ref event) => AppTopic {
-                                room_id: event.room_id,
-                                resource: ResourceKind::Tracks,
-                            },
-                            EventKind::TrackDelete(ref event) => AppTopic {
-                                room_id: event.room_id,
-                                resource: ResourceKind::Tracks,
-                            },
-                            EventKind::StateUpdate(_) => unreachable!(),
-                        };
-                        Topic::App(app_topic)
+            for (service, notification) in notification_rx.iter() {
+                // Recipients may use other namespaces than the one of the request.
+                let services = match namespaces(&pool, &notification, &service) {
+                    Ok(ref services) if !services.is_empty() => services.clone(),
+                    Ok(_) => vec![service],
+                    Err(e) => {
+                        println!("Error finding namespaces: {}", e);
+                        vec![service]
                     }
-                    Notification::Method(ref m) => Topic::Agent(AgentTopic::new_in(m.agent_id)),
                 };
 
+                let topics: Vec<Topic> = services
+                    .iter()
+                    .map(|service| notification_topic(&notification, service))
+                    .collect();
+
                 let note = jsonrpc_core::Notification::from(notification);
                 let payload = serde_json::to_string(&note).unwrap();
-                println!("EVENT: {}", payload);
+                println!("EVENT: {}", sdp::redact(&payload));
 
                 let mut client = client.lock().unwrap();
-                client
-                    .publish(&topic.to_string(), QoS::Level1, payload.into_bytes())
-                    .unwrap();
+                for topic in topics {
+                    client
+                        .publish(&topic.to_string(), QoS::Level1, payload.clone().into_bytes())
+                        .unwrap();
+                }
             }
         }
     });
@@ -142,15 +237,72 @@ pub fn try_run(options: Options) -> Result<(), failure::Error> {
     Ok(())
 }
 
-fn subscribe(client: &mut MqttClient) -> Result<(), failure::Error> {
-    let topics = vec![
-        ("ping", QoS::Level0),
-        (
-            "agents/+/out/signals.netology-group.services/api/v1",
-            QoS::Level1,
-        ),
-        ("agents/+/state/api/v1", QoS::Level1),
-    ];
+fn notification_topic(notification: &Notification, service: &str) -> Topic {
+    match *notification {
+        Notification::Event(ref kind) => {
+            let app_topic = match *kind {
+                EventKind::AgentJoin(ref event) => {
+                    AppTopic::new(event.room_id, ResourceKind::Agents, service)
+                }
+                EventKind::AgentLeave(ref event) => {
+                    AppTopic::new(event.room_id, ResourceKind::Agents, service)
+                }
+                EventKind::AgentUpdate(ref event) => {
+                    AppTopic::new(event.room_id, ResourceKind::Agents, service)
+                }
+                EventKind::TrackCreate(ref event) => {
+                    AppTopic::new(event.room_id, ResourceKind::Tracks, service)
+                }
+                EventKind::TrackUpdate(ref event) => {
+                    AppTopic::new(event.room_id, ResourceKind::Tracks, service)
+                }
+                EventKind::TrackDelete(ref event) => {
+                    AppTopic::new(event.room_id, ResourceKind::Tracks, service)
+                }
+                EventKind::StateUpdate(_) => unreachable!(),
+            };
+            Topic::App(app_topic)
+        }
+        Notification::Method(ref m) => Topic::Agent(AgentTopic::new_in(m.agent_id, service)),
+    }
+}
+
+/// Namespaces the recipients of the notification use: the one the room is served in
+/// for events and the agent's own one for methods. Rooms and agents created before
+/// namespaces were recorded have none.
+fn namespaces(
+    pool: &DbPool,
+    notification: &Notification,
+    service: &str,
+) -> Result<Vec<String>, failure::Error> {
+    let conn = pool.get()?;
+
+    let services = match notification_topic(notification, service) {
+        Topic::App(ref topic) => room::table
+            .find(topic.room_id)
+            .select(room::service)
+            .load::<Option<String>>(&conn)?,
+        Topic::Agent(ref topic) => agent::table
+            .find(topic.agent_id)
+            .select(agent::service)
+            .load::<Option<String>>(&conn)?,
+        _ => vec![],
+    };
+
+    Ok(services.into_iter().filter_map(|service| service).collect())
+}
+
+fn subscribe(client: &mut MqttClient, services: &[String]) -> Result<(), failure::Error> {
+    let agent_topics: Vec<String> = services
+        .iter()
+        .map(|service| format!("agents/+/out/{}/api/v1", service))
+        .collect();
+
+    let mut topics = vec![("ping", QoS::Level0)];
+    for topic in &agent_topics {
+        topics.push((topic.as_str(), QoS::Level1));
+    }
+    topics.push(("agents/+/state/api/v1", QoS::Level1));
 
     client.subscribe(topics)?;
 
@@ -161,31 +313,74 @@ fn handle_message(
     server: &rpc::Server,
     mqtt_client: &mut MqttClient,
     mqtt_msg: &MqttMessage,
-    notification_tx: ::std::sync::mpsc::Sender<Notification>,
+    context: &Context,
+    notification_tx: ::std::sync::mpsc::Sender<(String, Notification)>,
     pool: DbPool,
 ) -> Result<(), failure::Error> {
-    println!("Received message: {:?}", mqtt_msg);
+    let services = &context.services;
 
     let topic = Topic::parse(&mqtt_msg.topic)?;
     println!("Topic: {:?}", topic);
 
+    // Replies and notifications go to the namespace of the service the request was sent to.
+    let service = match topic {
+        Topic::Agent(ref t) => {
+            ensure!(services.contains(&t.service), "Unknown service: {}", t.service);
+            t.service.clone()
+        }
+        _ => services[0].clone(),
+    };
+
     let payload = String::from_utf8(mqtt_msg.payload.to_vec())?;
-    println!("Payload: {:?}", payload);
+    // Addresses of agents in candidates and descriptions don't go to logs.
+    println!("Payload: {:?}", sdp::redact(&payload));
 
     let envelope: Envelope = serde_json::from_str(&payload)?;
+    let subject = match context.auth {
+        Some(ref options) => auth::authenticate(options, &envelope, &topic),
+        None => envelope.sub.clone().ok_or(auth::Error::MissingSubject),
+    };
+
+    let subject = match subject {
+        Ok(subject) => subject,
+        Err(e) => {
+            let err = rpc::error::Error::from(e).into();
+            if let Some(resp) = rpc::reject_request(&envelope.msg, err) {
+                if let Some(topic) = topic.get_reverse() {
+                    mqtt_client.publish(&topic.to_string(), QoS::Level1, resp.into_bytes())?;
+                }
+            }
+
+            return Ok(());
+        }
+    };
+
     let request = envelope.msg;
 
+    let (events_tx, events_rx) = mpsc::channel::<Notification>();
+
     let meta = rpc::Meta {
-        subject: envelope.sub,
-        notification_tx: Some(notification_tx),
+        subject,
+        notification_tx: Some(events_tx),
         db_pool: Some(pool),
+        service: service.clone(),
+        config: Arc::clone(&context.config),
+        rate_limiter: context.rate_limiter.clone(),
+        sessions: Arc::clone(&context.sessions),
+        planner: Arc::clone(&context.planner),
     };
 
-    if let Some(resp) = server.handle_request_sync(&request, meta) {
+    if let Some(resp) = rpc::handle_request(server, &topic, &request, meta) {
         if let Some(topic) = topic.get_reverse() {
             mqtt_client.publish(&topic.to_string(), QoS::Level1, resp.into_bytes())?;
         }
     }
 
+    // Notifications are held back until the reply is published and then
+    // sent in order the calls of the request have produced them.
+    for notification in events_rx.try_iter() {
+        notification_tx.send((service.clone(), notification)).unwrap();
+    }
+
     Ok(())
 }
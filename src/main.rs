@@ -1,13 +1,22 @@
 #[macro_use]
 extern crate failure;
+extern crate openssl;
 extern crate rumqtt;
 extern crate signals;
 
 use rumqtt::MqttOptions;
-use signals::Options;
+use signals::rate_limit::Limits;
+use signals::rpc::Config as RpcConfig;
+use signals::{AclOptions, AuthOptions, MetricsOptions, Options, RateLimitOptions};
 
+use std::fs::File;
+use std::io::Read;
+use std::time::Duration;
 use std::{env, process};
 
+/// TURN credentials live a week at most.
+const MAX_TURN_TTL: u64 = 7 * 24 * 60 * 60;
+
 macro_rules! die {
     ($err:ident) => {{
         println!("{}", $err);
@@ -37,23 +46,157 @@ fn build_options() -> Result<Options, failure::Error> {
         std_error: e,
     })?;
 
-    let mqtt_options = MqttOptions::new()
+    let mut mqtt_options = MqttOptions::new()
         .set_keep_alive(5)
         .set_reconnect(3)
-        .set_client_id(mqtt_client_id)
+        .set_client_id(mqtt_client_id.clone())
         .set_broker(&mqtt_url);
 
+    // The service authenticates with its client identifier as the username.
+    let mqtt_password = env::var("MQTT_PASSWORD").ok();
+    if let Some(ref password) = mqtt_password {
+        mqtt_options = mqtt_options
+            .set_user_name(&mqtt_client_id)
+            .set_password(password);
+    }
+
     let database_url = env::var("DATABASE_URL").map_err(|e| VarError {
         var: "DATABASE_URL",
         std_error: e,
     })?;
 
+    let services: Vec<String> = env::var("SERVICE_NAME")
+        .unwrap_or_else(|_| "signals.netology-group.services".to_owned())
+        .split(',')
+        .map(|service| service.trim().to_owned())
+        .filter(|service| !service.is_empty())
+        .collect();
+
+    if services.is_empty() {
+        bail!("SERVICE_NAME has no service names");
+    }
+
+    let auth = build_auth_options()?;
+
+    let acl = match env::var("ACL_HTTP_ADDR") {
+        Ok(addr) => {
+            if auth.is_none() {
+                bail!("ACL_HTTP_ADDR is set without JWT keys to authenticate agents");
+            }
+
+            let superuser_password = mqtt_password
+                .ok_or_else(|| format_err!("ACL_HTTP_ADDR is set without MQTT_PASSWORD"))?;
+
+            Some(AclOptions {
+                addr,
+                superuser: mqtt_client_id,
+                superuser_password,
+            })
+        }
+        Err(_) => None,
+    };
+    let rate_limit = build_rate_limit_options()?;
+    let metrics = env::var("METRICS_HTTP_ADDR")
+        .ok()
+        .map(|addr| MetricsOptions { addr });
+
+    let mut rpc = RpcConfig::default();
+
+    if let Ok(lobby_timeout) = env::var("LOBBY_TIMEOUT") {
+        let secs = lobby_timeout
+            .parse()
+            .map_err(|e| format_err!("Invalid LOBBY_TIMEOUT: {}", e))?;
+        rpc.lobby_timeout = Duration::from_secs(secs);
+    }
+
+    rpc.invite_secret = env::var("INVITE_SECRET").ok().map(String::into_bytes);
+
+    let urls = |var| -> Vec<String> {
+        env::var(var)
+            .unwrap_or_default()
+            .split(',')
+            .map(|url| url.trim().to_owned())
+            .filter(|url| !url.is_empty())
+            .collect()
+    };
+
+    rpc.stun_urls = urls("STUN_URLS");
+    rpc.turn_urls = urls("TURN_URLS");
+
+    if !rpc.turn_urls.is_empty() {
+        rpc.turn_secret = env::var("TURN_SECRET")
+            .map_err(|_| format_err!("TURN_URLS are set without TURN_SECRET"))?
+            .into_bytes();
+    }
+
+    if let Ok(turn_ttl) = env::var("TURN_TTL") {
+        let secs = turn_ttl
+            .parse()
+            .map_err(|e| format_err!("Invalid TURN_TTL: {}", e))?;
+        if secs == 0 || secs > MAX_TURN_TTL {
+            bail!("Invalid TURN_TTL: must be from 1 to {} seconds", MAX_TURN_TTL);
+        }
+        rpc.turn_ttl = Duration::from_secs(secs);
+    }
+
     Ok(Options {
         mqtt: mqtt_options,
         database_url,
+        services,
+        acl,
+        auth,
+        rate_limit,
+        metrics,
+        rpc,
     })
 }
 
+fn build_rate_limit_options() -> Result<Option<RateLimitOptions>, failure::Error> {
+    let limits = |var| match env::var(var) {
+        Ok(limits) => limits
+            .parse::<Limits>()
+            .map(Some)
+            .map_err(|e| format_err!("Invalid {}: {}", var, e)),
+        Err(_) => Ok(None),
+    };
+
+    let agent = limits("RATE_LIMIT_AGENT")?;
+    let account = limits("RATE_LIMIT_ACCOUNT")?;
+
+    if agent.is_none() && account.is_none() {
+        return Ok(None);
+    }
+
+    Ok(Some(RateLimitOptions {
+        agent: agent.unwrap_or_default(),
+        account: account.unwrap_or_default(),
+    }))
+}
+
+fn build_auth_options() -> Result<Option<AuthOptions>, failure::Error> {
+    let hs256_secret = env::var("JWT_HS256_SECRET").ok().map(String::into_bytes);
+
+    let rs256_public_key = match env::var("JWT_RS256_PUBLIC_KEY") {
+        Ok(path) => {
+            let mut pem = vec![];
+            File::open(&path)?.read_to_end(&mut pem)?;
+            openssl::pkey::PKey::public_key_from_pem(&pem)
+                .map_err(|e| format_err!("Invalid JWT_RS256_PUBLIC_KEY: {}", e))?;
+            Some(pem)
+        }
+        Err(_) => None,
+    };
+
+    if hs256_secret.is_none() && rs256_public_key.is_none() {
+        return Ok(None);
+    }
+
+    Ok(Some(AuthOptions {
+        hs256_secret,
+        rs256_public_key,
+    }))
+}
+
 #[derive(Debug, Fail)]
 #[fail(display = "{} {}", var, std_error)]
 struct VarError {
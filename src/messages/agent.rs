@@ -1,9 +1,16 @@
 use chrono::NaiveDateTime;
+use serde_json::Value;
+use url::Url;
 use uuid::Uuid;
 
 use messages::query_parameters::QueryParameters;
 use messages::{Event, EventKind};
-use models;
+use models::{self, Role};
+use rpc::error::ErrorData;
+
+const DISPLAY_NAME_MAX_LEN: usize = 256;
+const AVATAR_URL_MAX_LEN: usize = 2048;
+const USER_AGENT_MAX_LEN: usize = 512;
 
 // Create
 
@@ -43,26 +50,91 @@ impl ReadResponse {
     pub fn new(agent: &models::RoomAgent) -> ReadResponse {
         ReadResponse {
             id: agent.agent_id,
-            data: ReadResponseData {
-                label: agent.label.clone(),
-                created_at: agent.created_at,
-            },
+            data: ReadResponseData::new(agent),
         }
     }
 }
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 struct ReadResponseData {
     label: String,
+    role: Role,
     created_at: NaiveDateTime,
+    data: Value,
+    version: i32,
+}
+
+impl ReadResponseData {
+    fn new(agent: &models::RoomAgent) -> ReadResponseData {
+        ReadResponseData {
+            label: agent.label.clone(),
+            role: agent.role,
+            created_at: agent.created_at,
+            data: agent.data.clone(),
+            version: agent.version,
+        }
+    }
 }
 
 // Read
 
 // Update
 
-pub type UpdateRequest = JoinRequest;
-pub type UpdateResponse = JoinResponse;
+#[derive(Debug, Deserialize)]
+pub struct UpdateRequest {
+    pub room_id: Uuid,
+    pub id: Uuid,
+    /// Version of the membership the changes are based on. If it's given and
+    /// the membership has been changed since, the update is rejected.
+    pub version: Option<i32>,
+    pub data: UpdateRequestData,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateRequestData {
+    pub label: Option<String>,
+    pub role: Option<Role>,
+    /// JSON merge patch (RFC 7396) to apply to the membership data.
+    pub data: Option<Value>,
+}
+
+impl UpdateRequestData {
+    pub fn is_empty(&self) -> bool {
+        self.label.is_none() && self.role.is_none() && self.data.is_none()
+    }
+
+    pub fn validate(&self) -> Result<(), ErrorData> {
+        match self.data {
+            Some(ref data) if !data.is_object() => Err(ErrorData::new("room_agent", "data")),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UpdateResponse {
+    id: Uuid,
+    data: UpdateResponseData,
+}
+
+type UpdateResponseData = ReadResponseData;
+
+impl UpdateResponse {
+    pub fn new(agent: &models::RoomAgent) -> UpdateResponse {
+        UpdateResponse {
+            id: agent.agent_id,
+            data: UpdateResponseData::new(agent),
+        }
+    }
+}
+
+pub type UpdateEvent = Event<UpdateResponse>;
+
+impl From<UpdateEvent> for EventKind {
+    fn from(event: UpdateEvent) -> Self {
+        EventKind::AgentUpdate(event)
+    }
+}
 
 // Update
 
@@ -84,13 +156,7 @@ impl ListResponse {
     pub fn new(agents: &[models::RoomAgent]) -> ListResponse {
         let data: Vec<ListResponseData> = agents
             .iter()
-            .map(|agent| ListResponseData {
-                id: agent.agent_id,
-                data: ReadResponseData {
-                    label: agent.label.clone(),
-                    created_at: agent.created_at,
-                },
-            })
+            .map(|agent| ListResponseData::new(agent))
             .collect();
 
         ListResponse(data)
@@ -113,6 +179,20 @@ pub struct JoinRequest {
 #[derive(Clone, Debug, Deserialize)]
 pub struct JoinRequestData {
     pub label: String,
+    /// Speaker if not given.
+    pub role: Option<Role>,
+    pub data: Option<Value>,
+    /// A token of an invitation to the room.
+    pub invite: Option<String>,
+}
+
+impl JoinRequestData {
+    pub fn validate(&self) -> Result<(), ErrorData> {
+        match self.data {
+            Some(ref data) if !data.is_object() => Err(ErrorData::new("room_agent", "data")),
+            _ => Ok(()),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Serialize)]
@@ -123,8 +203,22 @@ pub struct JoinResponse {
 
 #[derive(Clone, Debug, Serialize)]
 struct JoinResponseData {
+    status: JoinStatus,
     label: String,
+    role: Role,
     created_at: NaiveDateTime,
+    data: Value,
+    /// Pending agents have no membership to version yet.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<i32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JoinStatus {
+    Joined,
+    /// Waiting in the lobby of the room.
+    Pending,
 }
 
 impl JoinResponse {
@@ -132,8 +226,26 @@ impl JoinResponse {
         JoinResponse {
             id: agent.agent_id,
             data: JoinResponseData {
+                status: JoinStatus::Joined,
                 label: agent.label.clone(),
+                role: agent.role,
                 created_at: agent.created_at,
+                data: agent.data.clone(),
+                version: Some(agent.version),
+            },
+        }
+    }
+
+    pub fn pending(req: &models::LobbyRequest) -> JoinResponse {
+        JoinResponse {
+            id: req.agent_id,
+            data: JoinResponseData {
+                status: JoinStatus::Pending,
+                label: req.label.clone(),
+                role: req.role,
+                created_at: req.created_at,
+                data: req.data.clone(),
+                version: None,
             },
         }
     }
@@ -143,11 +255,16 @@ impl JoinResponse {
 pub struct JoinEventPayload {
     agent_id: Uuid,
     room_id: Uuid,
+    role: Role,
 }
 
 impl JoinEventPayload {
-    pub fn new(agent_id: Uuid, room_id: Uuid) -> JoinEventPayload {
-        JoinEventPayload { agent_id, room_id }
+    pub fn new(agent: &models::RoomAgent) -> JoinEventPayload {
+        JoinEventPayload {
+            agent_id: agent.agent_id,
+            room_id: agent.room_id,
+            role: agent.role,
+        }
     }
 }
 
@@ -175,20 +292,13 @@ pub struct LeaveResponse {
     data: LeaveResponseData,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
-struct LeaveResponseData {
-    label: String,
-    created_at: NaiveDateTime,
-}
+type LeaveResponseData = ReadResponseData;
 
 impl LeaveResponse {
     pub fn new(agent: &models::RoomAgent) -> LeaveResponse {
         LeaveResponse {
             id: agent.agent_id,
-            data: LeaveResponseData {
-                label: agent.label.clone(),
-                created_at: agent.created_at,
-            },
+            data: LeaveResponseData::new(agent),
         }
     }
 }
@@ -202,3 +312,170 @@ impl From<LeaveEvent> for EventKind {
 }
 
 // Leave
+
+// Read profile
+
+#[derive(Debug, Deserialize)]
+pub struct ReadProfileRequest {
+    pub id: Uuid,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ReadProfileResponse {
+    id: Uuid,
+    data: ReadProfileResponseData,
+}
+
+impl ReadProfileResponse {
+    pub fn new(agent: &models::Agent) -> ReadProfileResponse {
+        ReadProfileResponse {
+            id: agent.id,
+            data: ReadProfileResponseData {
+                account_id: agent.account_id,
+                display_name: agent.display_name.clone(),
+                avatar_url: agent.avatar_url.clone(),
+                user_agent: agent.user_agent.clone(),
+                data: agent.data.clone(),
+                created_at: agent.created_at,
+                updated_at: agent.updated_at,
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct ReadProfileResponseData {
+    account_id: Option<Uuid>,
+    display_name: Option<String>,
+    avatar_url: Option<String>,
+    user_agent: Option<String>,
+    data: Value,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+}
+
+// Read profile
+
+// Update profile
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateProfileRequest {
+    pub id: Uuid,
+    pub data: UpdateProfileRequestData,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateProfileRequestData {
+    pub display_name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub user_agent: Option<String>,
+    /// JSON merge patch (RFC 7396) to apply to the agent's custom data.
+    pub data: Option<Value>,
+}
+
+impl UpdateProfileRequestData {
+    pub fn is_empty(&self) -> bool {
+        self.display_name.is_none() && self.avatar_url.is_none() && self.user_agent.is_none()
+            && self.data.is_none()
+    }
+
+    pub fn validate(&self) -> Result<(), ErrorData> {
+        if let Some(ref display_name) = self.display_name {
+            if display_name.chars().count() > DISPLAY_NAME_MAX_LEN {
+                return Err(ErrorData::new("agent", "display_name"));
+            }
+        }
+
+        if let Some(ref avatar_url) = self.avatar_url {
+            let is_http = Url::parse(avatar_url)
+                .map(|url| url.scheme() == "http" || url.scheme() == "https")
+                .unwrap_or(false);
+
+            if !is_http || avatar_url.len() > AVATAR_URL_MAX_LEN {
+                return Err(ErrorData::new("agent", "avatar_url"));
+            }
+        }
+
+        if let Some(ref user_agent) = self.user_agent {
+            if user_agent.chars().count() > USER_AGENT_MAX_LEN {
+                return Err(ErrorData::new("agent", "user_agent"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub type UpdateProfileResponse = ReadProfileResponse;
+
+// Update profile
+
+// List profiles
+
+pub type ListProfilesRequest = QueryParameters;
+
+#[derive(Debug, Serialize)]
+pub struct ListProfilesResponse(Vec<ListProfilesResponseData>);
+
+impl ListProfilesResponse {
+    pub fn new(agents: &[models::Agent]) -> ListProfilesResponse {
+        let data: Vec<ListProfilesResponseData> = agents
+            .iter()
+            .map(|agent| ListProfilesResponseData::new(agent))
+            .collect();
+
+        ListProfilesResponse(data)
+    }
+}
+
+type ListProfilesResponseData = ReadProfileResponse;
+
+// List profiles
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn validate_update_profile_request_data() {
+        let data: UpdateProfileRequestData = serde_json::from_str(
+            r#"{ "display_name": "Alice", "avatar_url": "https://example.org/alice.png" }"#,
+        ).unwrap();
+        assert!(!data.is_empty());
+        assert!(data.validate().is_ok());
+
+        let data: UpdateProfileRequestData =
+            serde_json::from_str(r#"{ "avatar_url": "javascript:alert(1)" }"#).unwrap();
+        assert_eq!(data.validate(), Err(ErrorData::new("agent", "avatar_url")));
+
+        let data: UpdateProfileRequestData = serde_json::from_str(r#"{}"#).unwrap();
+        assert!(data.is_empty());
+    }
+
+    #[test]
+    fn validate_update_request_data() {
+        let data: UpdateRequestData =
+            serde_json::from_str(r#"{ "data": { "hand_raised": true } }"#).unwrap();
+        assert!(!data.is_empty());
+        assert!(data.validate().is_ok());
+
+        let data: UpdateRequestData = serde_json::from_str(r#"{ "data": [true] }"#).unwrap();
+        assert_eq!(data.validate(), Err(ErrorData::new("room_agent", "data")));
+    }
+
+    #[test]
+    fn validate_join_request_data() {
+        let data: JoinRequestData =
+            serde_json::from_str(r#"{ "label": "alice", "data": { "hand_raised": false } }"#)
+                .unwrap();
+        assert!(data.validate().is_ok());
+
+        let data: JoinRequestData = serde_json::from_str(r#"{ "label": "alice" }"#).unwrap();
+        assert!(data.validate().is_ok());
+
+        let data: JoinRequestData =
+            serde_json::from_str(r#"{ "label": "alice", "data": "hand_raised" }"#).unwrap();
+        assert_eq!(data.validate(), Err(ErrorData::new("room_agent", "data")));
+    }
+}
@@ -0,0 +1,152 @@
+use chrono::NaiveDateTime;
+use uuid::Uuid;
+
+use invite::Token;
+use messages::room::ReadRequest;
+use models::{self, Role};
+use rpc::error::ErrorData;
+
+const LABEL_MAX_LEN: usize = 256;
+
+/// A day.
+const DEFAULT_EXPIRES_IN: u32 = 86_400;
+
+/// Thirty days.
+const MAX_EXPIRES_IN: u32 = 2_592_000;
+
+// Create
+
+#[derive(Debug, Deserialize)]
+pub struct CreateRequest {
+    pub room_id: Uuid,
+    #[serde(default)]
+    pub data: CreateRequestData,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct CreateRequestData {
+    /// Agents join with their own labels if not given.
+    pub label: Option<String>,
+    /// Speaker if not given.
+    pub role: Option<Role>,
+    #[serde(default)]
+    pub single_use: bool,
+    /// Lifetime of the invitation in seconds.
+    pub expires_in: Option<u32>,
+}
+
+impl CreateRequestData {
+    pub fn validate(&self) -> Result<(), ErrorData> {
+        if let Some(ref label) = self.label {
+            if label.chars().count() > LABEL_MAX_LEN {
+                return Err(ErrorData::new("invite", "label"));
+            }
+        }
+
+        let expires_in = self.expires_in();
+        if expires_in == 0 || expires_in > MAX_EXPIRES_IN {
+            return Err(ErrorData::new("invite", "expires_in"));
+        }
+
+        Ok(())
+    }
+
+    pub fn expires_in(&self) -> u32 {
+        self.expires_in.unwrap_or(DEFAULT_EXPIRES_IN)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreateResponse {
+    id: Uuid,
+    data: CreateResponseData,
+}
+
+#[derive(Debug, Serialize)]
+struct CreateResponseData {
+    room_id: Uuid,
+    token: String,
+    label: Option<String>,
+    role: Role,
+    single_use: bool,
+    uses: i32,
+    expires_at: NaiveDateTime,
+    created_by: Option<Uuid>,
+    created_at: NaiveDateTime,
+}
+
+impl CreateResponse {
+    pub fn new(invite: &models::Invite, token: &Token) -> CreateResponse {
+        CreateResponse {
+            id: invite.id,
+            data: CreateResponseData {
+                room_id: invite.room_id,
+                token: token.to_string(),
+                label: invite.label.clone(),
+                role: invite.role,
+                single_use: invite.single_use,
+                uses: invite.uses,
+                expires_at: invite.expires_at,
+                created_by: invite.created_by,
+                created_at: invite.created_at,
+            },
+        }
+    }
+}
+
+// Create
+
+// List
+
+pub type ListRequest = ReadRequest;
+
+#[derive(Debug, Serialize)]
+pub struct ListResponse(Vec<ListResponseData>);
+
+impl ListResponse {
+    pub fn new(invites: Vec<ListResponseData>) -> ListResponse {
+        ListResponse(invites)
+    }
+}
+
+pub type ListResponseData = CreateResponse;
+
+// List
+
+// Revoke
+
+#[derive(Debug, Deserialize)]
+pub struct RevokeRequest {
+    pub room_id: Uuid,
+    pub id: Uuid,
+}
+
+pub type RevokeResponse = CreateResponse;
+
+// Revoke
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn validate_create_request_data() {
+        let data: CreateRequestData = serde_json::from_str("{}").unwrap();
+        assert!(data.validate().is_ok());
+        assert_eq!(data.expires_in(), DEFAULT_EXPIRES_IN);
+
+        let data: CreateRequestData = serde_json::from_str(r#"{"expires_in": 0}"#).unwrap();
+        assert_eq!(data.validate(), Err(ErrorData::new("invite", "expires_in")));
+
+        let data: CreateRequestData =
+            serde_json::from_str(r#"{"expires_in": 2592001}"#).unwrap();
+        assert_eq!(data.validate(), Err(ErrorData::new("invite", "expires_in")));
+
+        let data = CreateRequestData {
+            label: Some("x".repeat(LABEL_MAX_LEN + 1)),
+            ..Default::default()
+        };
+        assert_eq!(data.validate(), Err(ErrorData::new("invite", "label")));
+    }
+}
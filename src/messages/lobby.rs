@@ -0,0 +1,140 @@
+use chrono::NaiveDateTime;
+use serde_json::Value;
+use uuid::Uuid;
+
+use messages::agent::JoinResponse;
+use messages::room::ReadRequest;
+use messages::Method;
+use models::{self, Role};
+
+/// A pending request to join the room.
+#[derive(Debug, Serialize)]
+pub struct Entry {
+    room_id: Uuid,
+    data: EntryData,
+}
+
+#[derive(Debug, Serialize)]
+struct EntryData {
+    agent_id: Uuid,
+    label: String,
+    role: Role,
+    data: Value,
+    created_at: NaiveDateTime,
+}
+
+impl Entry {
+    pub fn new(req: &models::LobbyRequest) -> Entry {
+        Entry {
+            room_id: req.room_id,
+            data: EntryData {
+                agent_id: req.agent_id,
+                label: req.label.clone(),
+                role: req.role,
+                data: req.data.clone(),
+                created_at: req.created_at,
+            },
+        }
+    }
+
+    /// `lobby.request` to a moderator of the room.
+    pub fn to_method(&self, moderator_id: Uuid) -> Method {
+        Method::new(moderator_id, "lobby.request", self)
+    }
+
+    /// `lobby.expire` to the agent whose request has waited too long.
+    pub fn to_expire_method(&self) -> Method {
+        Method::new(self.data.agent_id, "lobby.expire", self)
+    }
+}
+
+/// Tells the agent who has decided on its request and why.
+#[derive(Debug, Serialize)]
+struct DecisionNotification {
+    room_id: Uuid,
+    data: DecisionNotificationData,
+}
+
+#[derive(Debug, Serialize)]
+struct DecisionNotificationData {
+    by: Uuid,
+    reason: Option<String>,
+}
+
+fn decision_method(
+    method: &str,
+    room_id: Uuid,
+    agent_id: Uuid,
+    by: Uuid,
+    reason: Option<String>,
+) -> Method {
+    let params = DecisionNotification {
+        room_id,
+        data: DecisionNotificationData { by, reason },
+    };
+
+    Method::new(agent_id, method, params)
+}
+
+// Admit
+
+#[derive(Debug, Deserialize)]
+pub struct AdmitRequest {
+    pub room_id: Uuid,
+    pub data: AdmitRequestData,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AdmitRequestData {
+    pub agent_id: Uuid,
+}
+
+pub type AdmitResponse = JoinResponse;
+
+impl AdmitRequest {
+    pub fn to_method(&self, by: Uuid) -> Method {
+        decision_method("lobby.admit", self.room_id, self.data.agent_id, by, None)
+    }
+}
+
+// Admit
+
+// Deny
+
+#[derive(Debug, Deserialize)]
+pub struct DenyRequest {
+    pub room_id: Uuid,
+    pub data: DenyRequestData,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DenyRequestData {
+    pub agent_id: Uuid,
+    pub reason: Option<String>,
+}
+
+pub type DenyResponse = Entry;
+
+impl DenyRequest {
+    pub fn to_method(&self, by: Uuid) -> Method {
+        let reason = self.data.reason.clone();
+        decision_method("lobby.deny", self.room_id, self.data.agent_id, by, reason)
+    }
+}
+
+// Deny
+
+// List
+
+pub type ListRequest = ReadRequest;
+
+#[derive(Debug, Serialize)]
+pub struct ListResponse(Vec<Entry>);
+
+impl ListResponse {
+    pub fn new(reqs: &[models::LobbyRequest]) -> ListResponse {
+        ListResponse(reqs.iter().map(Entry::new).collect())
+    }
+}
+
+// List
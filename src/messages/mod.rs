@@ -1,10 +1,12 @@
 use jsonrpc_core::{self, Params, Version};
 use serde::ser::Serialize;
-use serde_json;
+use serde_json::{self, Map, Value};
 use std::ops::Deref;
 use uuid::Uuid;
 
 pub mod agent;
+pub mod invite;
+pub mod lobby;
 pub mod query_parameters;
 pub mod room;
 pub mod state;
@@ -14,7 +16,12 @@ pub mod webrtc;
 
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Envelope {
-    pub sub: EnvelopeSubject,
+    /// Trusted as is unless the subject is authenticated by `token`.
+    #[serde(default)]
+    pub sub: Option<EnvelopeSubject>,
+    /// A JWT the subject is derived from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
     pub msg: EnvelopeMessage,
 }
 
@@ -57,8 +64,12 @@ pub enum EventKind {
     AgentJoin(agent::JoinEvent),
     #[serde(rename = "agent.leave_room")]
     AgentLeave(agent::LeaveEvent),
+    #[serde(rename = "agent.update")]
+    AgentUpdate(agent::UpdateEvent),
     #[serde(rename = "track.create")]
     TrackCreate(track::CreateEvent),
+    #[serde(rename = "track.update")]
+    TrackUpdate(track::UpdateEvent),
     #[serde(rename = "track.delete")]
     TrackDelete(track::DeleteEvent),
     #[serde(rename = "state.update")]
@@ -98,12 +109,52 @@ impl<T: Serialize> Event<T> {
     }
 }
 
+/// Applies JSON merge patch (RFC 7396) to the target.
+pub fn merge_patch(target: &mut Value, patch: &Value) {
+    match *patch {
+        Value::Object(ref patch) => {
+            if !target.is_object() {
+                *target = Value::Object(Map::new());
+            }
+
+            if let Value::Object(ref mut target) = *target {
+                for (key, value) in patch {
+                    if value.is_null() {
+                        target.remove(key);
+                    } else {
+                        merge_patch(target.entry(key.as_str()).or_insert(Value::Null), value);
+                    }
+                }
+            }
+        }
+        _ => *target = patch.clone(),
+    }
+}
+
 #[derive(Debug)]
 pub struct Method {
     pub agent_id: Uuid,
     pub body: jsonrpc_core::Notification,
 }
 
+impl Method {
+    /// A notification calling the method of the agent.
+    pub fn new<T: Serialize>(agent_id: Uuid, method: &str, params: T) -> Method {
+        let params = serde_json::to_value(params)
+            .ok()
+            .map(|value| Params::Array(vec![value]));
+
+        Method {
+            agent_id,
+            body: jsonrpc_core::Notification {
+                jsonrpc: Some(Version::V2),
+                method: method.to_owned(),
+                params,
+            },
+        }
+    }
+}
+
 impl From<Method> for Notification {
     fn from(method: Method) -> Self {
         Notification::Method(method)
@@ -128,13 +179,71 @@ mod tests {
 
         let envelope: Envelope = serde_json::from_str(json).unwrap();
         let expected = Envelope {
-            sub: EnvelopeSubject {
+            sub: Some(EnvelopeSubject {
                 account_id: Uuid::parse_str("31517b3d-5a14-4a14-a6c5-2ba63f7375d3").unwrap(),
                 agent_id: Uuid::parse_str("85c36f15-5021-4ab8-91a8-0f972cdb6d3a").unwrap(),
-            },
+            }),
+            token: None,
+            msg: EnvelopeMessage("ping".to_string()),
+        };
+
+        assert_eq!(envelope, expected);
+    }
+
+    #[test]
+    fn deserialize_envelope_with_token() {
+        use serde_json;
+
+        let json = r#"{
+            "token": "eyJhbGciOiJIUzI1NiJ9.e30.c2lnbmF0dXJl",
+            "msg": "ping"
+        }"#;
+
+        let envelope: Envelope = serde_json::from_str(json).unwrap();
+        let expected = Envelope {
+            sub: None,
+            token: Some("eyJhbGciOiJIUzI1NiJ9.e30.c2lnbmF0dXJl".to_string()),
             msg: EnvelopeMessage("ping".to_string()),
         };
 
         assert_eq!(envelope, expected);
     }
+
+    #[test]
+    fn method_notification() {
+        let agent_id = Uuid::parse_str("85c36f15-5021-4ab8-91a8-0f972cdb6d3a").unwrap();
+        let method = Method::new(agent_id, "room.kick", vec!["reason"]);
+
+        assert_eq!(method.agent_id, agent_id);
+        assert_eq!(
+            serde_json::to_string(&method.body).unwrap(),
+            r#"{"jsonrpc":"2.0","method":"room.kick","params":[["reason"]]}"#
+        );
+    }
+
+    #[test]
+    fn merge_patch_value() {
+        let mut target: Value = serde_json::from_str(
+            r#"{ "a": "b", "c": { "d": "e", "f": "g" }, "h": [1, 2] }"#,
+        ).unwrap();
+        let patch: Value = serde_json::from_str(
+            r#"{ "a": "z", "c": { "f": null }, "h": [3], "i": { "j": true } }"#,
+        ).unwrap();
+        let expected: Value = serde_json::from_str(
+            r#"{ "a": "z", "c": { "d": "e" }, "h": [3], "i": { "j": true } }"#,
+        ).unwrap();
+
+        merge_patch(&mut target, &patch);
+        assert_eq!(target, expected);
+    }
+
+    #[test]
+    fn merge_patch_non_object() {
+        let mut target: Value = serde_json::from_str(r#"["a"]"#).unwrap();
+        let patch: Value = serde_json::from_str(r#"{ "a": { "b": null } }"#).unwrap();
+        let expected: Value = serde_json::from_str(r#"{ "a": {} }"#).unwrap();
+
+        merge_patch(&mut target, &patch);
+        assert_eq!(target, expected);
+    }
 }
@@ -1,4 +1,4 @@
-use nom::multispace;
+use nom::{alpha, multispace};
 use nom::types::CompleteStr;
 use serde::{Deserialize, Deserializer};
 use uuid::Uuid;
@@ -7,6 +7,7 @@ use std::fmt;
 use std::str::FromStr;
 
 use error;
+use models::TrackKind;
 
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
@@ -68,6 +69,9 @@ pub enum Oper {
 pub enum Filter {
     RoomId(Uuid),
     OwnerId(Uuid),
+    AgentId(Uuid),
+    Kind(TrackKind),
+    AccountId(Uuid),
 }
 
 named!(room_filter<CompleteStr, Filter>, preceded!(
@@ -86,8 +90,32 @@ named!(owner_filter<CompleteStr, Filter>, preceded!(
     )
 ));
 
+named!(agent_filter<CompleteStr, Filter>, preceded!(
+    tag_s!("agent_id:"),
+    map!(
+        map_res!(take_s!(36), |s: CompleteStr| FromStr::from_str(s.0)),
+        Filter::AgentId
+    )
+));
+
+named!(kind_filter<CompleteStr, Filter>, preceded!(
+    tag_s!("kind:"),
+    map!(
+        map_res!(alpha, |s: CompleteStr| FromStr::from_str(s.0)),
+        Filter::Kind
+    )
+));
+
+named!(account_filter<CompleteStr, Filter>, preceded!(
+    tag_s!("account_id:"),
+    map!(
+        map_res!(take_s!(36), |s: CompleteStr| FromStr::from_str(s.0)),
+        Filter::AccountId
+    )
+));
+
 named!(filter<CompleteStr, Filter>, alt!(
-    room_filter | owner_filter
+    room_filter | owner_filter | agent_filter | kind_filter | account_filter
 ));
 
 named!(parenthesis<CompleteStr, Expr>, delimited!(
@@ -204,4 +232,27 @@ mod tests {
         let input = format!("room:{}", room_id);
         assert!(Expr::from_str(&input).is_err())
     }
+
+    #[test]
+    fn agent_expr_from_str() {
+        let agent_id = Uuid::parse_str("55e813bc-0c9b-4270-9f7f-81e5ffcfc9ff").unwrap();
+        let input = format!("agent_id:{}", agent_id);
+        let ex = Expr::from_str(&input);
+
+        assert!(ex.is_ok());
+        assert_eq!(ex.unwrap(), Expr::Value(Filter::AgentId(agent_id)));
+    }
+
+    #[test]
+    fn kind_expr_from_str() {
+        let owner_id = Uuid::parse_str("55e813bc-0c9b-4270-9f7f-81e5ffcfc9ff").unwrap();
+        let input = format!("owner_id:{} AND kind:screen", owner_id);
+        let expected = Expr::And(
+            Box::new(Expr::Value(Filter::OwnerId(owner_id))),
+            Box::new(Expr::Value(Filter::Kind(TrackKind::Screen))),
+        );
+
+        assert_eq!(Expr::from_str(&input).unwrap(), expected);
+        assert!(Expr::from_str("kind:hologram").is_err());
+    }
 }
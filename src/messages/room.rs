@@ -1,10 +1,74 @@
 use chrono::NaiveDateTime;
+use serde_json::Value;
 use uuid::Uuid;
 
+use messages::agent::LeaveResponse;
+use messages::Method;
 use models;
+use rpc::error::ErrorData;
+use sdp::Policy;
+use topology;
+
+const MEDIA_KINDS: &[&str] = &["audio", "video", "application"];
 
 // Create
 
+#[derive(Debug, Default, Deserialize)]
+pub struct CreateRequest {
+    #[serde(default)]
+    pub data: CreateRequestData,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct CreateRequestData {
+    /// Whether agents have to be admitted by a moderator to join.
+    #[serde(default)]
+    pub lobby: bool,
+    /// Rewrites of descriptions relayed in the room.
+    #[serde(default)]
+    pub sdp_policy: Policy,
+    /// Whom agents of the room are told to connect to, if anyone.
+    #[serde(default)]
+    pub topology: Option<topology::Policy>,
+}
+
+impl CreateRequestData {
+    pub fn validate(&self) -> Result<(), ErrorData> {
+        let policy = &self.sdp_policy;
+        let err = || ErrorData::new("room", "sdp_policy");
+
+        let allowed_codecs = match policy.allowed_codecs {
+            Some(ref codecs) if codecs.is_empty() => return Err(err()),
+            Some(ref codecs) => codecs.as_slice(),
+            None => &[],
+        };
+
+        let mut codecs = policy.codec_order.iter().chain(allowed_codecs);
+        if codecs.any(|codec| codec.trim().is_empty()) {
+            return Err(err());
+        }
+
+        if policy.candidate_types.as_ref().map_or(false, Vec::is_empty) {
+            return Err(err());
+        }
+
+        for (kind, &kbps) in &policy.bandwidth {
+            if !MEDIA_KINDS.contains(&kind.as_str()) || kbps == 0 {
+                return Err(err());
+            }
+        }
+
+        // A mesh connects two members at least.
+        if let Some(ref topology) = self.topology {
+            if topology.mesh_limit < 2 {
+                return Err(ErrorData::new("room", "topology"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(Debug, Serialize)]
 pub struct CreateResponse {
     id: Uuid,
@@ -17,6 +81,10 @@ impl CreateResponse {
             id: room.id,
             data: CreateResponseData {
                 created_at: room.created_at,
+                lobby: room.lobby,
+                sdp_policy: room.sdp_policy.clone(),
+                topology: room.topology.clone(),
+                owner_account_id: room.owner_account_id,
             },
         }
     }
@@ -25,6 +93,10 @@ impl CreateResponse {
 #[derive(Debug, Serialize)]
 struct CreateResponseData {
     created_at: NaiveDateTime,
+    lobby: bool,
+    sdp_policy: Value,
+    topology: Option<Value>,
+    owner_account_id: Option<Uuid>,
 }
 
 // Create
@@ -37,7 +109,6 @@ pub struct ReadRequest {
 }
 
 pub type ReadResponse = CreateResponse;
-type ReadResponseData = CreateResponseData;
 
 // Read
 
@@ -55,20 +126,170 @@ pub struct ListResponse(Vec<ListResponseData>);
 
 impl ListResponse {
     pub fn new(rooms: &[models::Room]) -> ListResponse {
-        let data: Vec<ListResponseData> = rooms
-            .iter()
-            .map(|room| ListResponseData {
-                id: room.id,
-                data: ReadResponseData {
-                    created_at: room.created_at,
-                },
-            })
-            .collect();
-
-        ListResponse(data)
+        ListResponse(rooms.iter().map(ReadResponse::new).collect())
     }
 }
 
 type ListResponseData = ReadResponse;
 
 // List
+
+// Kick
+
+#[derive(Debug, Deserialize)]
+pub struct KickRequest {
+    pub room_id: Uuid,
+    pub data: KickRequestData,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct KickRequestData {
+    pub agent_id: Uuid,
+    pub reason: Option<String>,
+}
+
+pub type KickResponse = LeaveResponse;
+
+/// Tells the removed agent who has removed it from the room and why.
+#[derive(Debug, Serialize)]
+struct KickNotification {
+    room_id: Uuid,
+    data: KickNotificationData,
+}
+
+#[derive(Debug, Serialize)]
+struct KickNotificationData {
+    by: Uuid,
+    reason: Option<String>,
+}
+
+impl KickRequest {
+    pub fn to_method(&self, method: &str, by: Uuid) -> Method {
+        self.to_method_of(self.data.agent_id, method, by)
+    }
+
+    /// The notification to another agent removed along with the requested one.
+    pub fn to_method_of(&self, agent_id: Uuid, method: &str, by: Uuid) -> Method {
+        let params = KickNotification {
+            room_id: self.room_id,
+            data: KickNotificationData {
+                by,
+                reason: self.data.reason.clone(),
+            },
+        };
+
+        Method::new(agent_id, method, params)
+    }
+}
+
+// Kick
+
+// Ban
+
+pub type BanRequest = KickRequest;
+
+#[derive(Debug, Serialize)]
+pub struct BanResponse {
+    room_id: Uuid,
+    data: BanResponseData,
+}
+
+#[derive(Debug, Serialize)]
+struct BanResponseData {
+    agent_id: Uuid,
+    account_id: Option<Uuid>,
+    reason: Option<String>,
+    created_at: NaiveDateTime,
+}
+
+impl BanResponse {
+    pub fn new(ban: &models::RoomBan) -> BanResponse {
+        BanResponse {
+            room_id: ban.room_id,
+            data: BanResponseData {
+                agent_id: ban.agent_id,
+                account_id: ban.account_id,
+                reason: ban.reason.clone(),
+                created_at: ban.created_at,
+            },
+        }
+    }
+}
+
+// Ban
+
+// Unban
+
+#[derive(Debug, Deserialize)]
+pub struct UnbanRequest {
+    pub room_id: Uuid,
+    pub data: UnbanRequestData,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UnbanRequestData {
+    pub agent_id: Uuid,
+}
+
+pub type UnbanResponse = BanResponse;
+
+// Unban
+
+// List bans
+
+pub type ListBansRequest = ReadRequest;
+
+#[derive(Debug, Serialize)]
+pub struct ListBansResponse(Vec<BanResponse>);
+
+impl ListBansResponse {
+    pub fn new(bans: &[models::RoomBan]) -> ListBansResponse {
+        ListBansResponse(bans.iter().map(BanResponse::new).collect())
+    }
+}
+
+// List bans
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn validate_create_request_data() {
+        let data: CreateRequestData = serde_json::from_str("{}").unwrap();
+        assert!(data.validate().is_ok());
+
+        let data: CreateRequestData = serde_json::from_str(
+            r#"{
+                "sdp_policy": {
+                    "codec_order": ["VP8", "opus"],
+                    "allowed_codecs": ["VP8", "H264", "opus"],
+                    "bandwidth": {"video": 1500},
+                    "listener_recvonly": true,
+                    "candidate_types": ["mdns", "relay"]
+                },
+                "topology": {"mesh_limit": 4}
+            }"#,
+        ).unwrap();
+        assert!(data.validate().is_ok());
+
+        let invalid = [
+            r#"{"sdp_policy": {"allowed_codecs": []}}"#,
+            r#"{"sdp_policy": {"codec_order": [" "]}}"#,
+            r#"{"sdp_policy": {"bandwidth": {"video": 0}}}"#,
+            r#"{"sdp_policy": {"bandwidth": {"screen": 1500}}}"#,
+            r#"{"sdp_policy": {"candidate_types": []}}"#,
+        ];
+
+        for data in &invalid {
+            let data: CreateRequestData = serde_json::from_str(data).unwrap();
+            assert_eq!(data.validate(), Err(ErrorData::new("room", "sdp_policy")));
+        }
+
+        for data in &[r#"{"topology": {"mesh_limit": 0}}"#, r#"{"topology": {"mesh_limit": 1}}"#] {
+            let data: CreateRequestData = serde_json::from_str(data).unwrap();
+            assert_eq!(data.validate(), Err(ErrorData::new("room", "topology")));
+        }
+    }
+}
@@ -1,5 +1,8 @@
+use chrono::NaiveDateTime;
 use uuid::Uuid;
 
+use messages::query_parameters::QueryParameters;
+use models;
 use topic::{AppTopic, ResourceKind};
 
 // Create
@@ -18,20 +21,61 @@ pub struct CreateRequestData {
 
 #[derive(Debug, Serialize)]
 pub struct CreateResponse {
+    room_id: Uuid,
+    agent_id: Uuid,
     data: CreateResponseData,
 }
 
 impl CreateResponse {
-    pub fn new(topic: AppTopic) -> CreateResponse {
+    pub fn new(subscription: &models::Subscription, service: &str) -> CreateResponse {
+        let topic = AppTopic::new(subscription.room_id, subscription.resource, service);
+
         CreateResponse {
-            data: CreateResponseData { topic },
+            room_id: subscription.room_id,
+            agent_id: subscription.agent_id,
+            data: CreateResponseData {
+                resource: subscription.resource,
+                topic,
+                created_at: subscription.created_at,
+            },
         }
     }
 }
 
 #[derive(Debug, Serialize)]
 struct CreateResponseData {
+    resource: ResourceKind,
     topic: AppTopic,
+    created_at: NaiveDateTime,
 }
 
 // Create
+
+// Delete
+
+pub type DeleteRequest = CreateRequest;
+pub type DeleteResponse = CreateResponse;
+
+// Delete
+
+// List
+
+pub type ListRequest = QueryParameters;
+
+#[derive(Debug, Serialize)]
+pub struct ListResponse(Vec<ListResponseData>);
+
+impl ListResponse {
+    pub fn new(subscriptions: &[models::Subscription], service: &str) -> ListResponse {
+        let data: Vec<ListResponseData> = subscriptions
+            .iter()
+            .map(|subscription| ListResponseData::new(subscription, service))
+            .collect();
+
+        ListResponse(data)
+    }
+}
+
+type ListResponseData = CreateResponse;
+
+// List
@@ -1,9 +1,15 @@
+use chrono::NaiveDateTime;
 use serde_json::Value;
 use uuid::Uuid;
 
 use messages::query_parameters::QueryParameters;
-use messages::{Event, EventKind};
-use models;
+use messages::{merge_patch, Event, EventKind, Method};
+use models::{self, TrackKind};
+use rpc::error::ErrorData;
+
+/// Maximum length of a stream id, same as of `msid-id` in RFC 8830.
+const STREAM_ID_MAX_LEN: usize = 64;
+const LABEL_MAX_LEN: usize = 256;
 
 // Create
 
@@ -12,10 +18,26 @@ pub struct CreateRequest {
     pub data: CreateRequestData,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Debug, Deserialize)]
 pub struct CreateRequestData {
     pub owner_id: Uuid,
+    pub room_id: Option<Uuid>,
+    pub kind: TrackKind,
+    #[serde(default)]
+    pub label: String,
+    pub stream_id: String,
+    #[serde(default = "empty_object")]
     pub metadata: Value,
+    pub enabled: Option<bool>,
+    pub muted: Option<bool>,
+}
+
+impl CreateRequestData {
+    pub fn validate(&self) -> Result<(), ErrorData> {
+        validate_stream_id(&self.stream_id)?;
+        validate_label(&self.label)?;
+        validate_metadata(&self.metadata)
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -33,13 +55,33 @@ impl CreateResponse {
     }
 }
 
-type CreateResponseData = CreateRequestData;
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct CreateResponseData {
+    owner_id: Uuid,
+    room_id: Option<Uuid>,
+    kind: TrackKind,
+    label: String,
+    stream_id: String,
+    metadata: Value,
+    enabled: bool,
+    muted: bool,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+}
 
 impl CreateResponseData {
     fn new(track: &models::Track) -> CreateResponseData {
         CreateResponseData {
             owner_id: track.owner_id,
+            room_id: track.room_id,
+            kind: track.kind,
+            label: track.label.clone(),
+            stream_id: track.stream_id.clone(),
             metadata: track.metadata.clone(),
+            enabled: track.enabled,
+            muted: track.muted,
+            created_at: track.created_at,
+            updated_at: track.updated_at,
         }
     }
 }
@@ -54,6 +96,123 @@ impl From<CreateEvent> for EventKind {
 
 // Create
 
+// Update
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateRequest {
+    pub id: Uuid,
+    pub data: UpdateRequestData,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateRequestData {
+    pub label: Option<String>,
+    /// JSON merge patch (RFC 7396) to apply to the track's metadata.
+    pub metadata: Option<Value>,
+    pub enabled: Option<bool>,
+    pub muted: Option<bool>,
+}
+
+impl UpdateRequestData {
+    pub fn is_empty(&self) -> bool {
+        self.label.is_none() && self.metadata.is_none() && self.enabled.is_none()
+            && self.muted.is_none()
+    }
+
+    pub fn validate(&self) -> Result<(), ErrorData> {
+        if let Some(ref label) = self.label {
+            validate_label(label)?;
+        }
+
+        Ok(())
+    }
+
+    /// Applies the metadata patch, if any, to the track's metadata, which must
+    /// stay an object as on create.
+    pub fn patch_metadata(&self, mut metadata: Value) -> Result<Option<Value>, ErrorData> {
+        match self.metadata {
+            Some(ref patch) => {
+                merge_patch(&mut metadata, patch);
+                validate_metadata(&metadata)?;
+                Ok(Some(metadata))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UpdateResponse {
+    id: Uuid,
+    data: UpdateResponseData,
+}
+
+type UpdateResponseData = CreateResponseData;
+
+impl UpdateResponse {
+    pub fn new(track: &models::Track) -> UpdateResponse {
+        UpdateResponse {
+            id: track.id,
+            data: UpdateResponseData::new(track),
+        }
+    }
+}
+
+pub type UpdateEvent = Event<UpdateResponse>;
+
+impl From<UpdateEvent> for EventKind {
+    fn from(event: UpdateEvent) -> Self {
+        EventKind::TrackUpdate(event)
+    }
+}
+
+// Update
+
+// Force mute
+
+#[derive(Debug, Deserialize)]
+pub struct ForceMuteRequest {
+    pub id: Uuid,
+    #[serde(default)]
+    pub data: ForceMuteRequestData,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ForceMuteRequestData {
+    pub reason: Option<String>,
+}
+
+pub type ForceMuteResponse = UpdateResponse;
+
+/// Tells the owner of the track who has muted it and why.
+#[derive(Debug, Serialize)]
+struct ForceMuteNotification {
+    id: Uuid,
+    data: ForceMuteNotificationData,
+}
+
+#[derive(Debug, Serialize)]
+struct ForceMuteNotificationData {
+    by: Uuid,
+    reason: Option<String>,
+}
+
+impl ForceMuteRequest {
+    pub fn to_method(&self, owner_id: Uuid, by: Uuid) -> Method {
+        let params = ForceMuteNotification {
+            id: self.id,
+            data: ForceMuteNotificationData {
+                by,
+                reason: self.data.reason.clone(),
+            },
+        };
+
+        Method::new(owner_id, "track.force_mute", params)
+    }
+}
+
+// Force mute
+
 // Delete
 
 #[derive(Debug, Deserialize)]
@@ -109,3 +268,104 @@ impl ListResponse {
 type ListResponseData = CreateResponse;
 
 // List
+
+fn empty_object() -> Value {
+    Value::Object(Default::default())
+}
+
+fn validate_stream_id(stream_id: &str) -> Result<(), ErrorData> {
+    // `token-char` of RFC 4566, so the id can be put into SDP as is.
+    let is_token_char = |c: char| match c {
+        '\x21' | '\x23'...'\x27' | '\x2A' | '\x2B' | '\x2D' | '\x2E' => true,
+        '0'...'9' | 'A'...'Z' | '\x5E'...'\x7E' => true,
+        _ => false,
+    };
+
+    if stream_id.is_empty() || stream_id.len() > STREAM_ID_MAX_LEN
+        || !stream_id.chars().all(is_token_char)
+    {
+        return Err(ErrorData::new("track", "stream_id"));
+    }
+
+    Ok(())
+}
+
+fn validate_label(label: &str) -> Result<(), ErrorData> {
+    if label.chars().count() > LABEL_MAX_LEN {
+        return Err(ErrorData::new("track", "label"));
+    }
+
+    Ok(())
+}
+
+fn validate_metadata(metadata: &Value) -> Result<(), ErrorData> {
+    if !metadata.is_object() {
+        return Err(ErrorData::new("track", "metadata"));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    fn create_request_data(stream_id: &str) -> CreateRequestData {
+        let json = format!(
+            r#"{{ "owner_id": "1154b35c-e5b0-4a42-8ab2-d4967ce38c9e", "kind": "screen", "stream_id": "{}" }}"#,
+            stream_id
+        );
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn deserialize_create_request_data() {
+        let data = create_request_data("a0ff-{stream}");
+
+        assert_eq!(data.kind, TrackKind::Screen);
+        assert_eq!(data.label, "");
+        assert!(data.metadata.is_object());
+        assert!(data.validate().is_ok());
+    }
+
+    #[test]
+    fn validate_create_request_data() {
+        assert_eq!(
+            create_request_data("").validate(),
+            Err(ErrorData::new("track", "stream_id"))
+        );
+        assert_eq!(
+            create_request_data("a b").validate(),
+            Err(ErrorData::new("track", "stream_id"))
+        );
+
+        let mut data = create_request_data("stream");
+        data.label = "x".repeat(LABEL_MAX_LEN + 1);
+        assert_eq!(data.validate(), Err(ErrorData::new("track", "label")));
+
+        let mut data = create_request_data("stream");
+        data.metadata = Value::Null;
+        assert_eq!(data.validate(), Err(ErrorData::new("track", "metadata")));
+    }
+
+    #[test]
+    fn patch_metadata() {
+        let metadata: Value = serde_json::from_str(r#"{"a": 1, "b": 2}"#).unwrap();
+
+        let data: UpdateRequestData = serde_json::from_str(r#"{"label": "x"}"#).unwrap();
+        assert_eq!(data.patch_metadata(metadata.clone()), Ok(None));
+
+        let data: UpdateRequestData = serde_json::from_str(r#"{"metadata": {"a": null}}"#).unwrap();
+        let expected: Value = serde_json::from_str(r#"{"b": 2}"#).unwrap();
+        assert_eq!(data.patch_metadata(metadata.clone()), Ok(Some(expected)));
+
+        for patch in &[r#"{"metadata": "a"}"#, r#"{"metadata": [1]}"#] {
+            let data: UpdateRequestData = serde_json::from_str(patch).unwrap();
+            assert_eq!(
+                data.patch_metadata(metadata.clone()),
+                Err(ErrorData::new("track", "metadata"))
+            );
+        }
+    }
+}
@@ -1,8 +1,13 @@
+use chrono::{DateTime, Utc};
 use jsonrpc_core::{Notification, Params, Version};
 use serde_json;
 use uuid::Uuid;
 
 use messages;
+use messages::room::ReadRequest;
+use sdp::{self, Policy, SessionDescription};
+use signaling::{self, State};
+use turn::Credentials;
 
 #[derive(Debug, Deserialize)]
 pub struct Request<T> {
@@ -10,12 +15,67 @@ pub struct Request<T> {
     data: T,
 }
 
+impl<T: Route> Request<T> {
+    pub fn room_id(&self) -> Uuid {
+        self.room_id
+    }
+
+    /// The sender and the recipient of the message.
+    pub fn route(&self) -> (Uuid, Uuid) {
+        (self.data.from(), self.data.to())
+    }
+}
+
+/// Signaling messages are relayed from one agent to another.
+pub trait Route {
+    fn from(&self) -> Uuid;
+    fn to(&self) -> Uuid;
+}
+
+macro_rules! impl_route {
+    ($data:ty) => {
+        impl Route for $data {
+            fn from(&self) -> Uuid {
+                self.from
+            }
+
+            fn to(&self) -> Uuid {
+                self.to
+            }
+        }
+    };
+}
+
+impl_route!(OfferRequestData);
+impl_route!(AnswerRequestData);
+impl_route!(CandidateRequestData);
+impl_route!(RenegotiateRequestData);
+impl_route!(IceRestartRequestData);
+impl_route!(HangupRequestData);
+impl_route!(EndOfCandidatesRequestData);
+
 #[derive(Debug, Serialize)]
 pub struct NotificationParams<T> {
     room_id: Uuid,
     data: T,
 }
 
+/// Rewrites the SDP of the JSEP the description has been parsed from.
+fn apply_policy(
+    jsep: &mut serde_json::Value,
+    desc: &SessionDescription,
+    policy: &Policy,
+    is_listener: bool,
+) -> Result<(), sdp::Error> {
+    let text = match jsep.get("sdp").and_then(|sdp| sdp.as_str()) {
+        Some(text) => policy.apply(text, desc, is_listener)?,
+        None => return Err(sdp::Error::InvalidJsep),
+    };
+
+    jsep["sdp"] = serde_json::Value::from(text);
+    Ok(())
+}
+
 // Offer
 
 pub type OfferRequest = Request<OfferRequestData>;
@@ -33,6 +93,24 @@ struct Track {
     id: Uuid,
 }
 
+impl OfferRequest {
+    /// Parses the description, the tracks of the offer must be announced in it.
+    pub fn description(&self) -> Result<SessionDescription, sdp::Error> {
+        let desc = sdp::parse_jsep(&self.data.jsep, "offer")?;
+        desc.check_tracks(self.data.tracks.iter().map(|track| track.id.to_string()))?;
+        Ok(desc)
+    }
+
+    pub fn apply_policy(
+        &mut self,
+        desc: &SessionDescription,
+        policy: &Policy,
+        is_listener: bool,
+    ) -> Result<(), sdp::Error> {
+        apply_policy(&mut self.data.jsep, desc, policy, is_listener)
+    }
+}
+
 type OfferNotification = NotificationParams<OfferNotificationData>;
 
 #[derive(Debug, Serialize)]
@@ -65,6 +143,21 @@ pub struct AnswerRequestData {
     to: Uuid,
 }
 
+impl AnswerRequest {
+    pub fn description(&self) -> Result<SessionDescription, sdp::Error> {
+        sdp::parse_jsep(&self.data.jsep, "answer")
+    }
+
+    pub fn apply_policy(
+        &mut self,
+        desc: &SessionDescription,
+        policy: &Policy,
+        is_listener: bool,
+    ) -> Result<(), sdp::Error> {
+        apply_policy(&mut self.data.jsep, desc, policy, is_listener)
+    }
+}
+
 type AnswerNotification = NotificationParams<AnswerNotificationData>;
 
 #[derive(Debug, Serialize)]
@@ -95,6 +188,46 @@ pub struct CandidateRequestData {
     to: Uuid,
 }
 
+impl CandidateRequest {
+    pub fn new(room_id: Uuid, from: Uuid, to: Uuid, candidate: serde_json::Value) -> Self {
+        Request {
+            room_id,
+            data: CandidateRequestData {
+                candidate,
+                from,
+                to,
+            },
+        }
+    }
+
+    /// Filters the candidate by the policy, returns whether it passes. The end
+    /// of candidates always does.
+    pub fn apply_policy(&mut self, policy: &Policy) -> bool {
+        let candidate = &mut self.data.candidate;
+        if candidate.is_null() {
+            return true;
+        }
+
+        let filtered = match candidate.get("candidate").and_then(|c| c.as_str()) {
+            Some("") => return true,
+            Some(text) => policy.filter_candidate(text),
+            None => None,
+        };
+
+        match filtered {
+            Some(text) => {
+                candidate["candidate"] = serde_json::Value::from(text);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn into_candidate(self) -> serde_json::Value {
+        self.data.candidate
+    }
+}
+
 type CandidateNotification = NotificationParams<CandidateNotificationData>;
 
 #[derive(Debug, Serialize)]
@@ -114,44 +247,321 @@ impl From<CandidateRequest> for CandidateNotificationData {
 
 // Candidate
 
+// Renegotiate
+
+/// Asks the other agent to make a new offer.
+pub type RenegotiateRequest = Request<RenegotiateRequestData>;
+
+#[derive(Debug, Deserialize)]
+pub struct RenegotiateRequestData {
+    from: Uuid,
+    to: Uuid,
+}
+
+type RenegotiateNotification = NotificationParams<RenegotiateNotificationData>;
+
 #[derive(Debug, Serialize)]
-#[serde(untagged)]
-enum WebrtcMethod {
-    Offer(OfferNotification),
-    Answer(AnswerNotification),
-    Candidate(CandidateNotification),
+struct RenegotiateNotificationData {
+    from: Uuid,
 }
 
-impl From<OfferRequest> for WebrtcMethod {
-    fn from(req: OfferRequest) -> Self {
-        let offer = OfferNotification {
-            room_id: req.room_id,
-            data: req.into(),
-        };
-        WebrtcMethod::Offer(offer)
+impl From<RenegotiateRequest> for RenegotiateNotificationData {
+    fn from(req: RenegotiateRequest) -> Self {
+        RenegotiateNotificationData {
+            from: req.data.from,
+        }
     }
 }
 
-impl From<AnswerRequest> for WebrtcMethod {
-    fn from(req: AnswerRequest) -> Self {
-        let answer = AnswerNotification {
-            room_id: req.room_id,
-            data: req.into(),
-        };
-        WebrtcMethod::Answer(answer)
+// Renegotiate
+
+// Ice restart
+
+/// Announces an ICE restart, either agent may make the offer with new ICE credentials.
+pub type IceRestartRequest = Request<IceRestartRequestData>;
+
+#[derive(Debug, Deserialize)]
+pub struct IceRestartRequestData {
+    from: Uuid,
+    to: Uuid,
+}
+
+type IceRestartNotification = NotificationParams<IceRestartNotificationData>;
+
+#[derive(Debug, Serialize)]
+struct IceRestartNotificationData {
+    from: Uuid,
+}
+
+impl From<IceRestartRequest> for IceRestartNotificationData {
+    fn from(req: IceRestartRequest) -> Self {
+        IceRestartNotificationData {
+            from: req.data.from,
+        }
     }
 }
 
-impl From<CandidateRequest> for WebrtcMethod {
-    fn from(req: CandidateRequest) -> Self {
-        let candidate = CandidateNotification {
-            room_id: req.room_id,
-            data: req.into(),
-        };
-        WebrtcMethod::Candidate(candidate)
+// Ice restart
+
+// Hangup
+
+pub type HangupRequest = Request<HangupRequestData>;
+
+#[derive(Debug, Deserialize)]
+pub struct HangupRequestData {
+    from: Uuid,
+    to: Uuid,
+    reason: Option<String>,
+}
+
+type HangupNotification = NotificationParams<HangupNotificationData>;
+
+#[derive(Debug, Serialize)]
+struct HangupNotificationData {
+    from: Uuid,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+}
+
+impl From<HangupRequest> for HangupNotificationData {
+    fn from(req: HangupRequest) -> Self {
+        HangupNotificationData {
+            from: req.data.from,
+            reason: req.data.reason,
+        }
+    }
+}
+
+// Hangup
+
+// End of candidates
+
+pub type EndOfCandidatesRequest = Request<EndOfCandidatesRequestData>;
+
+#[derive(Debug, Deserialize)]
+pub struct EndOfCandidatesRequestData {
+    from: Uuid,
+    to: Uuid,
+}
+
+impl EndOfCandidatesRequest {
+    pub fn new(room_id: Uuid, from: Uuid, to: Uuid) -> Self {
+        Request {
+            room_id,
+            data: EndOfCandidatesRequestData { from, to },
+        }
     }
 }
 
+type EndOfCandidatesNotification = NotificationParams<EndOfCandidatesNotificationData>;
+
+#[derive(Debug, Serialize)]
+struct EndOfCandidatesNotificationData {
+    from: Uuid,
+}
+
+impl From<EndOfCandidatesRequest> for EndOfCandidatesNotificationData {
+    fn from(req: EndOfCandidatesRequest) -> Self {
+        EndOfCandidatesNotificationData {
+            from: req.data.from,
+        }
+    }
+}
+
+// End of candidates
+
+// Connect
+
+/// Tells the agent to connect to the peer. Of the two, the agent with the lesser
+/// identifier makes the offer, as it wins glare anyway.
+#[derive(Debug, Serialize)]
+pub struct ConnectNotification {
+    room_id: Uuid,
+    data: ConnectNotificationData,
+}
+
+#[derive(Debug, Serialize)]
+struct ConnectNotificationData {
+    agent_id: Uuid,
+    offerer: bool,
+}
+
+impl ConnectNotification {
+    pub fn new(room_id: Uuid, agent_id: Uuid, peer_id: Uuid) -> ConnectNotification {
+        ConnectNotification {
+            room_id,
+            data: ConnectNotificationData {
+                agent_id: peer_id,
+                offerer: agent_id < peer_id,
+            },
+        }
+    }
+
+    pub fn into_method(self, agent_id: Uuid) -> messages::Method {
+        messages::Method::new(agent_id, "webrtc.connect", self)
+    }
+}
+
+// Connect
+
+// Disconnect
+
+/// Tells the agent to close its peer connection with the peer.
+#[derive(Debug, Serialize)]
+pub struct DisconnectNotification {
+    room_id: Uuid,
+    data: DisconnectNotificationData,
+}
+
+#[derive(Debug, Serialize)]
+struct DisconnectNotificationData {
+    agent_id: Uuid,
+}
+
+impl DisconnectNotification {
+    pub fn new(room_id: Uuid, peer_id: Uuid) -> DisconnectNotification {
+        DisconnectNotification {
+            room_id,
+            data: DisconnectNotificationData { agent_id: peer_id },
+        }
+    }
+
+    pub fn into_method(self, agent_id: Uuid) -> messages::Method {
+        messages::Method::new(agent_id, "webrtc.disconnect", self)
+    }
+}
+
+// Disconnect
+
+// Sessions
+
+pub type SessionsRequest = ReadRequest;
+
+#[derive(Debug, Serialize)]
+pub struct SessionsResponse(Vec<Session>);
+
+impl SessionsResponse {
+    pub fn new(sessions: &[&signaling::Session]) -> SessionsResponse {
+        SessionsResponse(sessions.iter().map(|session| Session::new(session)).collect())
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Session {
+    room_id: Uuid,
+    data: SessionData,
+}
+
+#[derive(Debug, Serialize)]
+struct SessionData {
+    agents: [Uuid; 2],
+    state: State,
+    offerer: Option<Uuid>,
+    polite: Uuid,
+    glares: u32,
+    /// Candidates of each agent waiting for its description.
+    queued: [usize; 2],
+    updated_at: DateTime<Utc>,
+}
+
+impl Session {
+    fn new(session: &signaling::Session) -> Session {
+        Session {
+            room_id: session.room_id,
+            data: SessionData {
+                agents: [session.agents.0, session.agents.1],
+                state: session.state(),
+                offerer: session.offerer,
+                polite: session.polite(),
+                glares: session.glares,
+                queued: [
+                    session.queued(session.agents.0),
+                    session.queued(session.agents.1),
+                ],
+                updated_at: session.updated_at,
+            },
+        }
+    }
+}
+
+// Sessions
+
+// Ice servers
+
+pub type IceServersRequest = ReadRequest;
+
+#[derive(Debug, Serialize)]
+pub struct IceServersResponse {
+    room_id: Uuid,
+    data: IceServersResponseData,
+}
+
+#[derive(Debug, Serialize)]
+struct IceServersResponseData {
+    ice_servers: Vec<IceServer>,
+    /// When TURN credentials expire, if any have been issued.
+    expires_at: Option<DateTime<Utc>>,
+}
+
+/// An `RTCIceServer` as peer connections are configured with.
+#[derive(Debug, Serialize)]
+struct IceServer {
+    urls: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    credential: Option<String>,
+}
+
+impl IceServersResponse {
+    pub fn new(
+        room_id: Uuid,
+        stun_urls: &[String],
+        turn: Option<(&[String], Credentials, DateTime<Utc>)>,
+    ) -> IceServersResponse {
+        let mut ice_servers = vec![];
+        if !stun_urls.is_empty() {
+            ice_servers.push(IceServer {
+                urls: stun_urls.to_vec(),
+                username: None,
+                credential: None,
+            });
+        }
+
+        let mut expires_at = None;
+        if let Some((turn_urls, credentials, at)) = turn {
+            ice_servers.push(IceServer {
+                urls: turn_urls.to_vec(),
+                username: Some(credentials.username),
+                credential: Some(credentials.credential),
+            });
+            expires_at = Some(at);
+        }
+
+        IceServersResponse {
+            room_id,
+            data: IceServersResponseData {
+                ice_servers,
+                expires_at,
+            },
+        }
+    }
+}
+
+// Ice servers
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum WebrtcMethod {
+    Offer(OfferNotification),
+    Answer(AnswerNotification),
+    Candidate(CandidateNotification),
+    Renegotiate(RenegotiateNotification),
+    IceRestart(IceRestartNotification),
+    Hangup(HangupNotification),
+    EndOfCandidates(EndOfCandidatesNotification),
+}
+
 impl From<WebrtcMethod> for Option<Params> {
     fn from(method: WebrtcMethod) -> Self {
         serde_json::to_value(method)
@@ -166,6 +576,10 @@ impl From<WebrtcMethod> for Notification {
             WebrtcMethod::Offer(_) => "webrtc.offer",
             WebrtcMethod::Answer(_) => "webrtc.answer",
             WebrtcMethod::Candidate(_) => "webrtc.candidate",
+            WebrtcMethod::Renegotiate(_) => "webrtc.renegotiate",
+            WebrtcMethod::IceRestart(_) => "webrtc.ice_restart",
+            WebrtcMethod::Hangup(_) => "webrtc.hangup",
+            WebrtcMethod::EndOfCandidates(_) => "webrtc.end_of_candidates",
         };
 
         Notification {
@@ -176,50 +590,41 @@ impl From<WebrtcMethod> for Notification {
     }
 }
 
-impl From<OfferRequest> for Notification {
-    fn from(req: OfferRequest) -> Self {
-        let method = WebrtcMethod::from(req);
-        Notification::from(method)
-    }
-}
-
-impl From<AnswerRequest> for Notification {
-    fn from(req: AnswerRequest) -> Self {
-        let method = WebrtcMethod::from(req);
-        Notification::from(method)
-    }
-}
-
-impl From<CandidateRequest> for Notification {
-    fn from(req: CandidateRequest) -> Self {
-        let method = WebrtcMethod::from(req);
-        Notification::from(method)
-    }
-}
-
-impl From<OfferRequest> for messages::Method {
-    fn from(req: OfferRequest) -> Self {
-        messages::Method {
-            agent_id: req.data.to,
-            body: req.into(),
+/// A request is relayed to its recipient as a notification of the same method.
+macro_rules! impl_method {
+    ($req:ty, $notification:ident, $variant:ident) => {
+        impl From<$req> for WebrtcMethod {
+            fn from(req: $req) -> Self {
+                let notification = $notification {
+                    room_id: req.room_id,
+                    data: req.into(),
+                };
+                WebrtcMethod::$variant(notification)
+            }
         }
-    }
-}
 
-impl From<AnswerRequest> for messages::Method {
-    fn from(req: AnswerRequest) -> Self {
-        messages::Method {
-            agent_id: req.data.to,
-            body: req.into(),
+        impl From<$req> for Notification {
+            fn from(req: $req) -> Self {
+                let method = WebrtcMethod::from(req);
+                Notification::from(method)
+            }
         }
-    }
-}
 
-impl From<CandidateRequest> for messages::Method {
-    fn from(req: CandidateRequest) -> Self {
-        messages::Method {
-            agent_id: req.data.to,
-            body: req.into(),
+        impl From<$req> for messages::Method {
+            fn from(req: $req) -> Self {
+                messages::Method {
+                    agent_id: req.data.to,
+                    body: req.into(),
+                }
+            }
         }
-    }
+    };
 }
+
+impl_method!(OfferRequest, OfferNotification, Offer);
+impl_method!(AnswerRequest, AnswerNotification, Answer);
+impl_method!(CandidateRequest, CandidateNotification, Candidate);
+impl_method!(RenegotiateRequest, RenegotiateNotification, Renegotiate);
+impl_method!(IceRestartRequest, IceRestartNotification, IceRestart);
+impl_method!(HangupRequest, HangupNotification, Hangup);
+impl_method!(EndOfCandidatesRequest, EndOfCandidatesNotification, EndOfCandidates);
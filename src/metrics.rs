@@ -0,0 +1,114 @@
+use failure;
+use tiny_http::{self, Header, Method, Response};
+
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use rate_limit::RateLimiter;
+
+/// HTTP endpoint exposing the state of the rate limiter at `GET /metrics`
+/// in the Prometheus text format.
+pub struct Server {
+    http: tiny_http::Server,
+    rate_limiter: Arc<Mutex<RateLimiter>>,
+}
+
+impl Server {
+    pub fn bind(
+        addr: &str,
+        rate_limiter: Arc<Mutex<RateLimiter>>,
+    ) -> Result<Server, failure::Error> {
+        let http = tiny_http::Server::http(addr).map_err(|e| format_err!("{}", e))?;
+
+        Ok(Server { http, rate_limiter })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.http.server_addr()
+    }
+
+    pub fn run(&self) {
+        for request in self.http.incoming_requests() {
+            let resp = match (request.method(), request.url()) {
+                (&Method::Get, "/metrics") => {
+                    let body = render(&self.rate_limiter.lock().unwrap());
+                    let content_type = "Content-Type: text/plain; version=0.0.4";
+                    let header = content_type.parse::<Header>().unwrap();
+                    Response::from_string(body).with_header(header)
+                }
+                (&Method::Get, _) => Response::from_string("").with_status_code(404),
+                _ => Response::from_string("").with_status_code(405),
+            };
+
+            if let Err(e) = request.respond(resp) {
+                println!("Metrics error: {}", e);
+            }
+        }
+    }
+}
+
+pub fn render(rate_limiter: &RateLimiter) -> String {
+    let mut out = String::new();
+
+    out.push_str("# HELP signals_rate_limit_calls_total Calls checked by the rate limiter.\n");
+    out.push_str("# TYPE signals_rate_limit_calls_total counter\n");
+    for (method, stats) in rate_limiter.stats() {
+        let method = escape(method);
+        for &(result, value) in &[("allowed", stats.allowed), ("rejected", stats.rejected)] {
+            writeln!(
+                out,
+                "signals_rate_limit_calls_total{{method=\"{}\",result=\"{}\"}} {}",
+                method, result, value
+            ).unwrap();
+        }
+    }
+
+    out.push_str("# HELP signals_rate_limit_buckets Token buckets of agents and accounts.\n");
+    out.push_str("# TYPE signals_rate_limit_buckets gauge\n");
+    writeln!(out, "signals_rate_limit_buckets {}", rate_limiter.bucket_count()).unwrap();
+
+    out
+}
+
+/// Escapes a label value.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use messages::EnvelopeSubject;
+    use rate_limit::Limits;
+
+    #[test]
+    fn render_rate_limiter() {
+        let limits = "room.create=1:1".parse::<Limits>().unwrap();
+        let mut rate_limiter = RateLimiter::new(limits, Limits::default());
+        let subject = EnvelopeSubject {
+            account_id: "31517b3d-5a14-4a14-a6c5-2ba63f7375d3".parse().unwrap(),
+            agent_id: "1154b35c-e5b0-4a42-8ab2-d4967ce38c9e".parse().unwrap(),
+        };
+
+        assert!(rate_limiter.check(&subject, "room.create"));
+        assert!(!rate_limiter.check(&subject, "room.create"));
+
+        let metrics = render(&rate_limiter);
+        assert!(metrics.contains(
+            "signals_rate_limit_calls_total{method=\"room.create\",result=\"allowed\"} 1\n"
+        ));
+        assert!(metrics.contains(
+            "signals_rate_limit_calls_total{method=\"room.create\",result=\"rejected\"} 1\n"
+        ));
+        assert!(metrics.contains("signals_rate_limit_buckets 1\n"));
+    }
+
+    #[test]
+    fn escape_label_value() {
+        assert_eq!(escape("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+}
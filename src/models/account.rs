@@ -0,0 +1,17 @@
+use chrono::NaiveDateTime;
+use uuid::Uuid;
+
+use schema::account;
+
+#[derive(Identifiable, Queryable, Debug)]
+#[table_name = "account"]
+pub struct Account {
+    pub id: Uuid,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "account"]
+pub struct NewAccount {
+    pub id: Uuid,
+}
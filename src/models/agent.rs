@@ -1,15 +1,40 @@
+use chrono::NaiveDateTime;
+use serde_json::Value;
 use uuid::Uuid;
 
+use models::Account;
 use schema::agent;
 
 #[derive(Associations, Identifiable, Queryable, Debug)]
 #[table_name = "agent"]
+#[belongs_to(Account)]
 pub struct Agent {
     pub id: Uuid,
+    /// Agents created before accounts were introduced have none.
+    pub account_id: Option<Uuid>,
+    pub display_name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub user_agent: Option<String>,
+    pub data: Value,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    /// The namespace of the service the agent uses, see `Options::services`.
+    pub service: Option<String>,
 }
 
 #[derive(Insertable, Debug, PartialEq, Serialize, Deserialize)]
 #[table_name = "agent"]
 pub struct NewAgent {
     pub id: Uuid,
+    pub account_id: Option<Uuid>,
+    pub service: Option<String>,
+}
+
+#[derive(AsChangeset, Debug)]
+#[table_name = "agent"]
+pub struct AgentChangeset {
+    pub display_name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub user_agent: Option<String>,
+    pub data: Option<Value>,
 }
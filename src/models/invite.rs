@@ -0,0 +1,24 @@
+use chrono::NaiveDateTime;
+use uuid::Uuid;
+
+use models::{Agent, Role, Room};
+use schema::invite;
+
+/// An invitation to join the room with the preset label and role.
+#[derive(Associations, Identifiable, Queryable, Debug)]
+#[table_name = "invite"]
+#[belongs_to(Agent, foreign_key = "created_by")]
+#[belongs_to(Room)]
+pub struct Invite {
+    pub id: Uuid,
+    pub room_id: Uuid,
+    /// Overrides the label the agent joins with.
+    pub label: Option<String>,
+    pub role: Role,
+    pub single_use: bool,
+    /// How many agents have joined with the invitation.
+    pub uses: i32,
+    pub expires_at: NaiveDateTime,
+    pub created_by: Option<Uuid>,
+    pub created_at: NaiveDateTime,
+}
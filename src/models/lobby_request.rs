@@ -0,0 +1,33 @@
+use chrono::NaiveDateTime;
+use serde_json::Value;
+use uuid::Uuid;
+
+use models::{Agent, Role, Room};
+use schema::lobby_request;
+
+/// A request of the agent to join the room, pending until a moderator
+/// admits or denies it.
+#[derive(Associations, Identifiable, Queryable, Debug)]
+#[table_name = "lobby_request"]
+#[primary_key(room_id, agent_id)]
+#[belongs_to(Agent)]
+#[belongs_to(Room)]
+pub struct LobbyRequest {
+    pub room_id: Uuid,
+    pub agent_id: Uuid,
+    pub label: String,
+    /// The role the agent gets once admitted.
+    pub role: Role,
+    pub data: Value,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "lobby_request"]
+pub struct NewLobbyRequest {
+    pub room_id: Uuid,
+    pub agent_id: Uuid,
+    pub label: String,
+    pub role: Role,
+    pub data: Option<Value>,
+}
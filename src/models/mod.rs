@@ -1,9 +1,19 @@
+mod account;
 mod agent;
+mod invite;
+mod lobby_request;
 mod room;
 mod room_agent;
+mod room_ban;
+mod subscription;
 mod track;
 
-pub use models::agent::{Agent, NewAgent};
-pub use models::room::Room;
-pub use models::room_agent::{NewRoomAgent, RoomAgent};
-pub use models::track::{NewTrack, Track};
+pub use models::account::{Account, NewAccount};
+pub use models::agent::{Agent, AgentChangeset, NewAgent};
+pub use models::invite::Invite;
+pub use models::lobby_request::{LobbyRequest, NewLobbyRequest};
+pub use models::room::{NewRoom, Room};
+pub use models::room_agent::{NewRoomAgent, Role, RoomAgent, RoomAgentChangeset};
+pub use models::room_ban::{NewRoomBan, RoomBan};
+pub use models::subscription::{NewSubscription, Subscription};
+pub use models::track::{NewTrack, Track, TrackChangeset, TrackKind};
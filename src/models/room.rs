@@ -1,8 +1,39 @@
 use chrono::NaiveDateTime;
+use serde_json::Value;
 use uuid::Uuid;
 
+use schema::room;
+
 #[derive(Queryable, Debug)]
 pub struct Room {
     pub id: Uuid,
     pub created_at: NaiveDateTime,
+    /// Agents wait in the lobby until a moderator admits them.
+    pub lobby: bool,
+    /// Rewrites of descriptions relayed in the room, see `sdp::Policy`.
+    pub sdp_policy: Value,
+    /// How agents of the room are told to connect, see `topology::Policy`.
+    pub topology: Option<Value>,
+    /// Agents of the account own the room.
+    pub owner_account_id: Option<Uuid>,
+    /// The namespace the room is served in.
+    pub service: Option<String>,
+}
+
+impl Room {
+    /// Rooms are served only in the namespace they're created in, those created
+    /// before namespaces were recorded in any.
+    pub fn is_served_in(&self, service: &str) -> bool {
+        self.service.as_ref().map_or(true, |s| s == service)
+    }
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "room"]
+pub struct NewRoom {
+    pub lobby: bool,
+    pub sdp_policy: Value,
+    pub topology: Option<Value>,
+    pub owner_account_id: Option<Uuid>,
+    pub service: Option<String>,
 }
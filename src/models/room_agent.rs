@@ -1,6 +1,17 @@
 use chrono::NaiveDateTime;
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Text;
+use nom::ErrorKind;
+use serde_json::Value;
 use uuid::Uuid;
 
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+
+use error;
 use models::{Agent, Room};
 use schema::room_agent;
 
@@ -14,6 +25,10 @@ pub struct RoomAgent {
     pub room_id: Uuid,
     pub label: String,
     pub created_at: NaiveDateTime,
+    pub data: Value,
+    /// Incremented on each update, used to detect concurrent changes.
+    pub version: i32,
+    pub role: Role,
 }
 
 #[derive(Insertable, Debug)]
@@ -22,4 +37,62 @@ pub struct NewRoomAgent {
     pub agent_id: Uuid,
     pub room_id: Uuid,
     pub label: String,
+    pub data: Option<Value>,
+    pub role: Role,
+}
+
+#[derive(AsChangeset, Debug)]
+#[table_name = "room_agent"]
+pub struct RoomAgentChangeset {
+    pub label: Option<String>,
+    pub data: Option<Value>,
+    pub version: i32,
+    pub role: Option<Role>,
+}
+
+/// Role of an agent in a room, see `rpc::permission` for what each role is allowed to do.
+#[derive(AsExpression, FromSqlRow, Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+#[sql_type = "Text"]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Owner,
+    Moderator,
+    Speaker,
+    Listener,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let value = format!("{:?}", self).to_lowercase();
+        f.write_str(&value)
+    }
+}
+
+impl FromStr for Role {
+    type Err = error::ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "owner" => Ok(Role::Owner),
+            "moderator" => Ok(Role::Moderator),
+            "speaker" => Ok(Role::Speaker),
+            "listener" => Ok(Role::Listener),
+            _ => Err(error::ParseError::from(ErrorKind::Tag)),
+        }
+    }
+}
+
+impl ToSql<Text, Pg> for Role {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        ToSql::<Text, Pg>::to_sql(&self.to_string(), out)
+    }
+}
+
+impl FromSql<Text, Pg> for Role {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Pg>>::from_sql(bytes)?;
+        value
+            .parse()
+            .map_err(|_| format!("Unknown role: {}", value).into())
+    }
 }
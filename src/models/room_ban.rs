@@ -0,0 +1,28 @@
+use chrono::NaiveDateTime;
+use uuid::Uuid;
+
+use models::Room;
+use schema::room_ban;
+
+/// A ban of the agent in the room. If the agent has an account,
+/// other agents of the account are banned too.
+#[derive(Associations, Identifiable, Queryable, Debug)]
+#[table_name = "room_ban"]
+#[primary_key(room_id, agent_id)]
+#[belongs_to(Room)]
+pub struct RoomBan {
+    pub room_id: Uuid,
+    pub agent_id: Uuid,
+    pub account_id: Option<Uuid>,
+    pub reason: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "room_ban"]
+pub struct NewRoomBan {
+    pub room_id: Uuid,
+    pub agent_id: Uuid,
+    pub account_id: Option<Uuid>,
+    pub reason: Option<String>,
+}
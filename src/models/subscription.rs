@@ -0,0 +1,26 @@
+use chrono::NaiveDateTime;
+use uuid::Uuid;
+
+use models::{Agent, Room};
+use schema::subscription;
+use topic::ResourceKind;
+
+#[derive(Associations, Identifiable, Queryable, Debug)]
+#[table_name = "subscription"]
+#[primary_key(agent_id, room_id, resource)]
+#[belongs_to(Agent)]
+#[belongs_to(Room)]
+pub struct Subscription {
+    pub agent_id: Uuid,
+    pub room_id: Uuid,
+    pub resource: ResourceKind,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "subscription"]
+pub struct NewSubscription {
+    pub agent_id: Uuid,
+    pub room_id: Uuid,
+    pub resource: ResourceKind,
+}
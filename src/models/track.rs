@@ -1,6 +1,17 @@
+use chrono::NaiveDateTime;
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Text;
+use nom::ErrorKind;
 use serde_json::Value;
 use uuid::Uuid;
 
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+
+use error;
 use messages::track::CreateRequest;
 use models::Agent;
 use schema::track;
@@ -12,13 +23,28 @@ pub struct Track {
     pub id: Uuid,
     pub owner_id: Uuid,
     pub metadata: Value,
+    pub enabled: bool,
+    pub muted: bool,
+    pub kind: TrackKind,
+    pub label: String,
+    pub stream_id: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    /// Tracks without a room are shared with every room of the owner.
+    pub room_id: Option<Uuid>,
 }
 
-#[derive(AsChangeset, Insertable, Debug, Deserialize)]
+#[derive(AsChangeset, Insertable, Debug)]
 #[table_name = "track"]
 pub struct NewTrack {
     pub owner_id: Uuid,
     pub metadata: Value,
+    pub enabled: Option<bool>,
+    pub muted: Option<bool>,
+    pub kind: TrackKind,
+    pub label: String,
+    pub stream_id: String,
+    pub room_id: Option<Uuid>,
 }
 
 impl From<CreateRequest> for NewTrack {
@@ -28,6 +54,67 @@ impl From<CreateRequest> for NewTrack {
         NewTrack {
             owner_id: data.owner_id,
             metadata: data.metadata,
+            enabled: data.enabled,
+            muted: data.muted,
+            kind: data.kind,
+            label: data.label,
+            stream_id: data.stream_id,
+            room_id: data.room_id,
+        }
+    }
+}
+
+#[derive(AsChangeset, Debug)]
+#[table_name = "track"]
+pub struct TrackChangeset {
+    pub metadata: Option<Value>,
+    pub enabled: Option<bool>,
+    pub muted: Option<bool>,
+    pub label: Option<String>,
+}
+
+#[derive(AsExpression, FromSqlRow, Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+#[sql_type = "Text"]
+#[serde(rename_all = "lowercase")]
+pub enum TrackKind {
+    Audio,
+    Video,
+    Screen,
+    Data,
+}
+
+impl fmt::Display for TrackKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let value = format!("{:?}", self).to_lowercase();
+        f.write_str(&value)
+    }
+}
+
+impl FromStr for TrackKind {
+    type Err = error::ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "audio" => Ok(TrackKind::Audio),
+            "video" => Ok(TrackKind::Video),
+            "screen" => Ok(TrackKind::Screen),
+            "data" => Ok(TrackKind::Data),
+            _ => Err(error::ParseError::from(ErrorKind::Tag)),
         }
     }
 }
+
+impl ToSql<Text, Pg> for TrackKind {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        ToSql::<Text, Pg>::to_sql(&self.to_string(), out)
+    }
+}
+
+impl FromSql<Text, Pg> for TrackKind {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Pg>>::from_sql(bytes)?;
+        value
+            .parse()
+            .map_err(|_| format!("Unknown track kind: {}", value).into())
+    }
+}
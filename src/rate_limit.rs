@@ -0,0 +1,358 @@
+use uuid::Uuid;
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Instant;
+
+use messages::EnvelopeSubject;
+
+/// Buckets left untouched are dropped once in this many checks.
+const PURGE_INTERVAL: u64 = 1024;
+
+/// A budget of a token bucket: `rate` calls per second on average
+/// with bursts of up to `burst` calls.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Budget {
+    pub rate: f64,
+    pub burst: f64,
+}
+
+#[derive(Debug, Fail)]
+#[fail(display = "Invalid rate limit: {}", _0)]
+pub struct ParseError(String);
+
+impl FromStr for Budget {
+    type Err = ParseError;
+
+    /// `rate:burst`, or just `rate` for bursts of the same size.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseError(s.to_owned());
+
+        let mut parts = s.splitn(2, ':');
+        let rate: f64 = parts
+            .next()
+            .and_then(|rate| rate.trim().parse().ok())
+            .ok_or_else(err)?;
+        let burst: f64 = match parts.next() {
+            Some(burst) => burst.trim().parse().map_err(|_| err())?,
+            None => rate.max(1.0),
+        };
+
+        if rate.is_nan() || rate <= 0.0 || burst.is_nan() || burst < 1.0 {
+            return Err(err());
+        }
+
+        Ok(Budget { rate, burst })
+    }
+}
+
+/// The name the default budget goes by.
+const ANY_METHOD: &str = "*";
+
+/// Budgets of methods. Methods without a budget aren't limited unless
+/// there's a default one, which they share.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Limits {
+    methods: HashMap<String, Budget>,
+    default: Option<Budget>,
+}
+
+impl Limits {
+    /// The budget of the method and the name of the budget.
+    pub fn get<'a>(&self, method: &'a str) -> Option<(&'a str, Budget)> {
+        match self.methods.get(method) {
+            Some(budget) => Some((method, *budget)),
+            None => self.default.map(|budget| (ANY_METHOD, budget)),
+        }
+    }
+}
+
+impl FromStr for Limits {
+    type Err = ParseError;
+
+    /// Comma separated `method=budget` pairs, `*` stands for any other method,
+    /// e.g. `webrtc.candidate=20:50,room.create=0.1:3,*=10:20`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut limits = Limits::default();
+
+        for pair in s.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+            let mut parts = pair.splitn(2, '=');
+            let method = parts.next().map(str::trim).unwrap_or("");
+            let budget = parts.next().ok_or_else(|| ParseError(pair.to_owned()))?;
+            let budget = Budget::from_str(budget)?;
+
+            match method {
+                "" => return Err(ParseError(pair.to_owned())),
+                ANY_METHOD => limits.default = Some(budget),
+                _ => {
+                    limits.methods.insert(method.to_owned(), budget);
+                }
+            }
+        }
+
+        Ok(limits)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum Key {
+    Agent(Uuid),
+    Account(Uuid),
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, budget: Budget, now: Instant) {
+        let elapsed = now.duration_since(self.updated_at);
+        let elapsed = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) * 1e-9;
+
+        self.tokens = (self.tokens + elapsed * budget.rate).min(budget.burst);
+        self.updated_at = now;
+    }
+}
+
+/// Calls of a method the limiter has checked.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Stats {
+    pub allowed: u64,
+    pub rejected: u64,
+}
+
+/// Token buckets of agents and accounts per method. A call is allowed
+/// if both the agent and its account have a token for the method.
+#[derive(Debug)]
+pub struct RateLimiter {
+    agent: Limits,
+    account: Limits,
+    buckets: HashMap<(Key, String), Bucket>,
+    stats: HashMap<String, Stats>,
+    checks: u64,
+}
+
+impl RateLimiter {
+    pub fn new(agent: Limits, account: Limits) -> RateLimiter {
+        RateLimiter {
+            agent,
+            account,
+            buckets: HashMap::new(),
+            stats: HashMap::new(),
+            checks: 0,
+        }
+    }
+
+    pub fn check(&mut self, subject: &EnvelopeSubject, method: &str) -> bool {
+        self.check_at(subject, method, Instant::now())
+    }
+
+    fn check_at(&mut self, subject: &EnvelopeSubject, method: &str, now: Instant) -> bool {
+        let budgets = [
+            (Key::Agent(subject.agent_id), self.agent.get(method)),
+            (Key::Account(subject.account_id), self.account.get(method)),
+        ];
+
+        // Stats of methods without a budget of their own are counted under
+        // the default one, so that arbitrary method names don't pile up.
+        let name = budgets
+            .iter()
+            .filter_map(|&(_, budget)| budget.map(|(name, _)| name))
+            .max_by_key(|&name| name != ANY_METHOD);
+
+        let name = match name {
+            Some(name) => name,
+            None => return true,
+        };
+
+        self.checks += 1;
+        if self.checks % PURGE_INTERVAL == 0 {
+            self.purge(now);
+        }
+
+        let mut is_allowed = true;
+        for &(key, budget) in &budgets {
+            if let Some((name, budget)) = budget {
+                let bucket = self.buckets
+                    .entry((key, name.to_owned()))
+                    .or_insert_with(|| Bucket {
+                        tokens: budget.burst,
+                        updated_at: now,
+                    });
+
+                bucket.refill(budget, now);
+                is_allowed = is_allowed && bucket.tokens >= 1.0;
+            }
+        }
+
+        // Tokens are taken only from both buckets at once.
+        if is_allowed {
+            for &(key, budget) in &budgets {
+                if let Some((name, _)) = budget {
+                    if let Some(bucket) = self.buckets.get_mut(&(key, name.to_owned())) {
+                        bucket.tokens -= 1.0;
+                    }
+                }
+            }
+        }
+
+        let stats = self.stats.entry(name.to_owned()).or_insert_with(Stats::default);
+        if is_allowed {
+            stats.allowed += 1;
+        } else {
+            stats.rejected += 1;
+        }
+
+        is_allowed
+    }
+
+    /// Drops buckets which have refilled completely, they are the same as new ones.
+    fn purge(&mut self, now: Instant) {
+        let (agent, account) = (&self.agent, &self.account);
+
+        self.buckets.retain(|&(key, ref name), bucket| {
+            let budget = match key {
+                Key::Agent(_) => agent.get(name),
+                Key::Account(_) => account.get(name),
+            };
+
+            match budget {
+                Some((_, budget)) => {
+                    bucket.refill(budget, now);
+                    bucket.tokens < budget.burst
+                }
+                None => false,
+            }
+        });
+    }
+
+    /// Calls checked per method, sorted by method.
+    pub fn stats(&self) -> Vec<(&str, Stats)> {
+        let mut stats: Vec<(&str, Stats)> = self.stats
+            .iter()
+            .map(|(method, stats)| (method.as_str(), *stats))
+            .collect();
+        stats.sort_by(|a, b| a.0.cmp(b.0));
+        stats
+    }
+
+    pub fn bucket_count(&self) -> usize {
+        self.buckets.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn subject(agent_id: &str) -> EnvelopeSubject {
+        EnvelopeSubject {
+            account_id: Uuid::parse_str("31517b3d-5a14-4a14-a6c5-2ba63f7375d3").unwrap(),
+            agent_id: Uuid::parse_str(agent_id).unwrap(),
+        }
+    }
+
+    #[test]
+    fn parse_limits() {
+        let limits = Limits::from_str("webrtc.candidate=20:50, room.create=0.5, *=10:20").unwrap();
+
+        assert_eq!(
+            limits.get("webrtc.candidate"),
+            Some((
+                "webrtc.candidate",
+                Budget {
+                    rate: 20.0,
+                    burst: 50.0,
+                },
+            ))
+        );
+        assert_eq!(
+            limits.get("room.create"),
+            Some((
+                "room.create",
+                Budget {
+                    rate: 0.5,
+                    burst: 1.0,
+                },
+            ))
+        );
+        assert_eq!(
+            limits.get("ping"),
+            Some((
+                "*",
+                Budget {
+                    rate: 10.0,
+                    burst: 20.0,
+                },
+            ))
+        );
+
+        assert_eq!(Limits::from_str("").unwrap(), Limits::default());
+        assert!(Limits::from_str("room.create").is_err());
+        assert!(Limits::from_str("room.create=0").is_err());
+        assert!(Limits::from_str("room.create=1:0.5").is_err());
+        assert!(Limits::from_str("=1").is_err());
+    }
+
+    #[test]
+    fn limit_agent() {
+        let limits = Limits::from_str("room.create=1:2").unwrap();
+        let mut limiter = RateLimiter::new(limits, Limits::default());
+        let agent = subject("1154b35c-e5b0-4a42-8ab2-d4967ce38c9e");
+        let now = Instant::now();
+
+        assert!(limiter.check_at(&agent, "room.create", now));
+        assert!(limiter.check_at(&agent, "room.create", now));
+        assert!(!limiter.check_at(&agent, "room.create", now));
+        assert!(limiter.check_at(&agent, "ping", now));
+
+        let later = now + Duration::from_secs(1);
+        assert!(limiter.check_at(&agent, "room.create", later));
+        assert!(!limiter.check_at(&agent, "room.create", later));
+
+        assert_eq!(
+            limiter.stats(),
+            vec![(
+                "room.create",
+                Stats {
+                    allowed: 3,
+                    rejected: 2,
+                },
+            )]
+        );
+    }
+
+    #[test]
+    fn limit_account() {
+        let agent_limits = Limits::from_str("room.create=1:2").unwrap();
+        let account_limits = Limits::from_str("room.create=1:3").unwrap();
+        let mut limiter = RateLimiter::new(agent_limits, account_limits);
+        let first = subject("1154b35c-e5b0-4a42-8ab2-d4967ce38c9e");
+        let second = subject("9923576c-1ee5-4987-a797-6cb4982d45de");
+        let now = Instant::now();
+
+        assert!(limiter.check_at(&first, "room.create", now));
+        assert!(limiter.check_at(&first, "room.create", now));
+        assert!(limiter.check_at(&second, "room.create", now));
+        assert!(!limiter.check_at(&second, "room.create", now));
+        assert_eq!(limiter.bucket_count(), 3);
+    }
+
+    #[test]
+    fn purge_full_buckets() {
+        let limits = Limits::from_str("*=1:1").unwrap();
+        let mut limiter = RateLimiter::new(limits, Limits::default());
+        let agent = subject("1154b35c-e5b0-4a42-8ab2-d4967ce38c9e");
+        let now = Instant::now();
+
+        assert!(limiter.check_at(&agent, "ping", now));
+        limiter.purge(now);
+        assert_eq!(limiter.bucket_count(), 1);
+
+        limiter.purge(now + Duration::from_secs(1));
+        assert_eq!(limiter.bucket_count(), 0);
+    }
+}
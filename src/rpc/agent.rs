@@ -1,19 +1,32 @@
 use diesel;
 use diesel::prelude::*;
+use diesel::PgConnection;
 
 use std::str::FromStr;
+use std::sync::mpsc::Sender;
+use std::sync::Mutex;
+use std::time::Duration;
+use uuid::Uuid;
 
 use messages::agent::{CreateRequest, CreateResponse, DeleteRequest, DeleteResponse, JoinEvent,
                       JoinEventPayload, JoinRequest, JoinResponse, LeaveEvent, LeaveRequest,
-                      LeaveResponse, ListRequest, ListResponse, ReadRequest, ReadResponse,
+                      LeaveResponse, ListProfilesRequest, ListProfilesResponse, ListRequest,
+                      ListResponse, ReadProfileRequest, ReadProfileResponse, ReadRequest,
+                      ReadResponse, UpdateEvent, UpdateProfileRequest, UpdateProfileResponse,
                       UpdateRequest, UpdateResponse};
+use messages::lobby::Entry as LobbyEntry;
 use messages::query_parameters;
 use messages::track::{DeleteEvent as TrackDeleteEvent, DeleteResponse as TrackDeleteResponse};
-use messages::EventKind;
+use messages::{merge_patch, EventKind, Notification};
 use models;
 use rpc;
-use rpc::error::{Error, Result};
-use schema::{agent, room, room_agent, track};
+use rpc::error::{Error, ErrorData, Result};
+use rpc::lobby;
+use rpc::permission::{self, Action};
+use rpc::topology;
+use schema::{account, agent, lobby_request, room, room_agent, room_ban, track};
+use signaling::Sessions;
+use topology::Planner;
 
 build_rpc_trait! {
     pub trait Rpc {
@@ -39,6 +52,15 @@ build_rpc_trait! {
 
         #[rpc(meta, name = "agent.leave_room")]
         fn leave_room(&self, Self::Metadata, LeaveRequest) -> Result<LeaveResponse>;
+
+        #[rpc(meta, name = "agent.read_profile")]
+        fn read_profile(&self, Self::Metadata, ReadProfileRequest) -> Result<ReadProfileResponse>;
+
+        #[rpc(meta, name = "agent.update_profile")]
+        fn update_profile(&self, Self::Metadata, UpdateProfileRequest) -> Result<UpdateProfileResponse>;
+
+        #[rpc(meta, name = "agent.list_profiles")]
+        fn list_profiles(&self, Self::Metadata, ListProfilesRequest) -> Result<ListProfilesResponse>;
     }
 }
 
@@ -50,10 +72,27 @@ impl Rpc for RpcImpl {
     fn create(&self, meta: rpc::Meta, req: CreateRequest) -> Result<CreateResponse> {
         let conn = establish_connection!(meta.db_pool.unwrap());
 
-        let changeset = models::NewAgent { id: req.id };
-        let agent: models::Agent = diesel::insert_into(agent::table)
-            .values(&changeset)
-            .get_result(conn)?;
+        // The agent belongs to the account of the caller and uses its namespace.
+        let account_id = meta.subject.account_id;
+        let service = meta.service.clone();
+
+        let agent = conn.transaction::<_, Error, _>(|| {
+            diesel::insert_into(account::table)
+                .values(&models::NewAccount { id: account_id })
+                .on_conflict_do_nothing()
+                .execute(conn)?;
+
+            let changeset = models::NewAgent {
+                id: req.id,
+                account_id: Some(account_id),
+                service: Some(service),
+            };
+
+            diesel::insert_into(agent::table)
+                .values(&changeset)
+                .get_result::<models::Agent>(conn)
+                .map_err(Error::from)
+        })?;
 
         let resp = CreateResponse::new(&agent);
 
@@ -71,30 +110,91 @@ impl Rpc for RpcImpl {
     }
 
     fn update(&self, meta: rpc::Meta, req: UpdateRequest) -> Result<UpdateResponse> {
+        let (id, room_id, version) = (req.id, req.room_id, req.version);
+        let data = req.data;
+        if data.is_empty() {
+            Err(Error::BadRequest)?
+        }
+        data.validate().map_err(Error::InvalidValue)?;
+
         let conn = establish_connection!(meta.db_pool.unwrap());
+        let subject_id = meta.subject.agent_id;
+
+        let agent = conn.transaction::<_, Error, _>(|| {
+            let target = room_agent::table.find((id, room_id));
+            let agent: models::RoomAgent = target.for_update().get_result(conn)?;
+
+            let subject_role = if agent.agent_id == subject_id {
+                agent.role
+            } else {
+                let role = permission::member_role(conn, subject_id, room_id)?;
+                let role = role.ok_or(Error::Forbidden)?;
+                permission::authorize(role, Action::UpdateMember)?;
+                role
+            };
+
+            // Both the current and the new role have to be within the subject's reach.
+            if let Some(new_role) = data.role {
+                permission::authorize(subject_role, Action::SetRole(agent.role))?;
+                permission::authorize(subject_role, Action::SetRole(new_role))?;
+            }
 
-        let agent = room_agent::table.find((req.id, req.room_id));
+            if version.map_or(false, |version| version != agent.version) {
+                return Err(Error::PreconditionFailed(ErrorData::new(
+                    "room_agent",
+                    "version",
+                )));
+            }
 
-        let agent = diesel::update(agent)
-            .set(room_agent::label.eq(req.data.label))
-            .get_result::<models::RoomAgent>(conn)?;
+            let next_version = agent.version + 1;
+            let membership_data = data.data.map(|patch| {
+                let mut membership_data = agent.data;
+                merge_patch(&mut membership_data, &patch);
+                membership_data
+            });
+
+            let changeset = models::RoomAgentChangeset {
+                label: data.label,
+                data: membership_data,
+                version: next_version,
+                role: data.role,
+            };
+
+            diesel::update(target)
+                .set(&changeset)
+                .get_result(conn)
+                .map_err(Error::from)
+        })?;
+
+        let resp = UpdateResponse::new(&agent);
+
+        let event = UpdateEvent::new(agent.room_id, resp.clone());
+        let notification_tx = meta.notification_tx.unwrap();
+        let event_kind = EventKind::from(event);
+        notification_tx.send(event_kind.into()).unwrap();
 
-        Ok(UpdateResponse::new(&agent))
+        Ok(resp)
     }
 
     fn delete(&self, meta: rpc::Meta, req: DeleteRequest) -> Result<DeleteResponse> {
+        // Agents delete themselves only, as they go offline.
+        if req.id != meta.subject.agent_id {
+            return Err(Error::Forbidden);
+        }
+
         let conn = establish_connection!(meta.db_pool.unwrap());
 
         let agent = agent::table.find(req.id).first::<models::Agent>(conn)?;
 
         let (room_agents, tracks) = conn.transaction::<_, Error, _>(|| {
+            // Tracks go first, otherwise the ones bound to rooms get removed by cascade.
+            let tracks = diesel::delete(track::table.filter(track::owner_id.eq(agent.id)))
+                .get_results::<models::Track>(conn)?;
+
             let room_agents = diesel::delete(
                 room_agent::table.filter(room_agent::agent_id.eq(agent.id)),
             ).get_results::<models::RoomAgent>(conn)?;
 
-            let tracks = diesel::delete(track::table.filter(track::owner_id.eq(agent.id)))
-                .get_results::<models::Track>(conn)?;
-
             diesel::delete(&agent).execute(conn)?;
 
             Ok((room_agents, tracks))
@@ -104,8 +204,13 @@ impl Rpc for RpcImpl {
 
         for room_agent in &room_agents {
             let room_id = room_agent.room_id;
+            meta.sessions.lock().unwrap().remove_agent(room_id, agent.id);
 
-            for track in &tracks {
+            let room_tracks = tracks
+                .iter()
+                .filter(|track| track.room_id.map_or(true, |id| id == room_id));
+
+            for track in room_tracks {
                 let payload = TrackDeleteResponse::new(track);
                 let event = TrackDeleteEvent::new(room_id, payload);
                 let event_kind = EventKind::from(event);
@@ -116,6 +221,8 @@ impl Rpc for RpcImpl {
             let event = LeaveEvent::new(room_id, payload);
             let event_kind = EventKind::from(event);
             notification_tx.send(event_kind.into()).unwrap();
+
+            topology::update(conn, &notification_tx, &meta.planner, room_id);
         }
 
         let resp = DeleteResponse::new(&agent);
@@ -147,27 +254,87 @@ impl Rpc for RpcImpl {
     }
 
     fn join_room(&self, meta: rpc::Meta, req: JoinRequest) -> Result<JoinResponse> {
+        // Agents join on their own, others are let in through the lobby or invitations.
+        if req.id != meta.subject.agent_id {
+            return Err(Error::Forbidden);
+        }
+        req.data.validate().map_err(Error::InvalidValue)?;
+
         let conn = establish_connection!(meta.db_pool.unwrap());
 
         let room: models::Room = room::table.find(req.room_id).first(conn)?;
 
+        // Tenants are isolated, so are their rooms.
+        if !room.is_served_in(&meta.service) {
+            return Err(Error::Forbidden);
+        }
+
+        let account_id = agent::table
+            .find(req.id)
+            .select(agent::account_id)
+            .first::<Option<Uuid>>(conn)?;
+
+        // A ban of an agent applies to all agents of its account.
+        let is_banned = diesel::select(diesel::dsl::exists(
+            room_ban::table
+                .filter(room_ban::room_id.eq(room.id))
+                .filter(
+                    room_ban::agent_id
+                        .eq(req.id)
+                        .or(room_ban::account_id.eq(account_id)),
+                ),
+        )).get_result::<bool>(conn)?;
+
+        if is_banned {
+            return Err(Error::Forbidden);
+        }
+
+        // An invitation lets the agent in with its label and role, bypassing the lobby.
+        if let Some(ref token) = req.data.invite {
+            let secret = rpc::invite::secret(&meta.config)?;
+            let agent_id = meta.subject.agent_id;
+            let agent = join_with_invite(conn, secret, &room, agent_id, &req, token)?;
+            let notification_tx = meta.notification_tx.unwrap();
+            let resp = notify_join(&notification_tx, &agent);
+            topology::update(conn, &notification_tx, &meta.planner, room.id);
+            return Ok(resp);
+        }
+
+        let role = req.data.role.unwrap_or(models::Role::Speaker);
+        // Only agents of the owner account join as owners on their own.
+        let owner_account_id = room.owner_account_id;
+        let is_owner = owner_account_id == Some(meta.subject.account_id)
+            && account_id == owner_account_id;
+
+        // Everyone but agents of the owner account waits in the lobby, even while
+        // the room is empty. The requested role is checked on admission.
+        if room.lobby && !is_owner {
+            let notification_tx = meta.notification_tx.unwrap();
+            let timeout = meta.config.lobby_timeout;
+            return knock(conn, &notification_tx, &room, req, role, timeout);
+        }
+
+        // Agents of the account which has created the room may take any role, owners
+        // and moderators of other accounts are appointed by those already in charge.
+        if !is_owner && (role == models::Role::Owner || role == models::Role::Moderator) {
+            return Err(Error::Forbidden);
+        }
+
         let changeset = models::NewRoomAgent {
             room_id: room.id,
             agent_id: req.id,
             label: req.data.label.clone(),
+            data: req.data.data.clone(),
+            role,
         };
 
         let agent: models::RoomAgent = diesel::insert_into(room_agent::table)
             .values(&changeset)
             .get_result(conn)?;
 
-        let resp = JoinResponse::new(&agent);
-
-        let payload = JoinEventPayload::new(agent.agent_id, agent.room_id);
-        let event = JoinEvent::new(room.id, payload);
         let notification_tx = meta.notification_tx.unwrap();
-        let event_kind = EventKind::from(event);
-        notification_tx.send(event_kind.into()).unwrap();
+        let resp = notify_join(&notification_tx, &agent);
+        topology::update(conn, &notification_tx, &meta.planner, room.id);
 
         Ok(resp)
     }
@@ -179,15 +346,343 @@ impl Rpc for RpcImpl {
             .find((req.id, req.room_id))
             .first::<models::RoomAgent>(conn)?;
 
-        diesel::delete(&room_agent).execute(conn)?;
+        if room_agent.agent_id != meta.subject.agent_id {
+            let action = Action::RemoveMember(room_agent.role);
+            permission::authorize_member(conn, meta.subject.agent_id, req.room_id, action)?;
+        }
 
         let notification_tx = meta.notification_tx.unwrap();
-        let resp = LeaveResponse::new(&room_agent);
 
-        let event = LeaveEvent::new(req.room_id, resp.clone());
+        remove_member(conn, &notification_tx, &meta.sessions, &meta.planner, &room_agent)
+    }
+
+    fn read_profile(&self, meta: rpc::Meta, req: ReadProfileRequest) -> Result<ReadProfileResponse> {
+        let conn = establish_connection!(meta.db_pool.unwrap());
+
+        let agent = agent::table.find(req.id).first::<models::Agent>(conn)?;
+
+        Ok(ReadProfileResponse::new(&agent))
+    }
+
+    fn update_profile(
+        &self,
+        meta: rpc::Meta,
+        req: UpdateProfileRequest,
+    ) -> Result<UpdateProfileResponse> {
+        let id = req.id;
+        let data = req.data;
+        if data.is_empty() {
+            Err(Error::BadRequest)?
+        }
+        data.validate().map_err(Error::InvalidValue)?;
+
+        let conn = establish_connection!(meta.db_pool.unwrap());
+        let subject = &meta.subject;
+
+        let agent = conn.transaction::<_, Error, _>(|| {
+            let target = agent::table.find(id);
+            let agent: models::Agent = target.for_update().get_result(conn)?;
+
+            // A profile is changed by the agent itself or another agent of the same account.
+            if agent.id != subject.agent_id && agent.account_id != Some(subject.account_id) {
+                return Err(Error::Forbidden);
+            }
+
+            let custom_data = data.data.map(|patch| {
+                let mut custom_data = agent.data;
+                merge_patch(&mut custom_data, &patch);
+                custom_data
+            });
+
+            let changeset = models::AgentChangeset {
+                display_name: data.display_name,
+                avatar_url: data.avatar_url,
+                user_agent: data.user_agent,
+                data: custom_data,
+            };
+
+            diesel::update(target)
+                .set(&changeset)
+                .get_result(conn)
+                .map_err(Error::from)
+        })?;
+
+        Ok(UpdateProfileResponse::new(&agent))
+    }
+
+    fn list_profiles(
+        &self,
+        meta: rpc::Meta,
+        req: ListProfilesRequest,
+    ) -> Result<ListProfilesResponse> {
+        let conn = establish_connection!(meta.db_pool.unwrap());
+
+        // Profiles are listed by account, of the caller or of someone it shares a room with.
+        let fq = req.fq.ok_or(Error::BadRequest)?;
+        let account_id = match query_parameters::Expr::from_str(&fq)? {
+            query_parameters::Expr::Value(query_parameters::Filter::AccountId(id)) => id,
+            _ => Err(Error::BadRequest)?,
+        };
+
+        if account_id != meta.subject.account_id {
+            let rooms = room_agent::table
+                .filter(room_agent::agent_id.eq(meta.subject.agent_id))
+                .select(room_agent::room_id)
+                .load::<Uuid>(conn)?;
+
+            let is_co_member = diesel::select(diesel::dsl::exists(
+                room_agent::table
+                    .inner_join(agent::table)
+                    .filter(agent::account_id.eq(account_id))
+                    .filter(room_agent::room_id.eq_any(rooms)),
+            )).get_result::<bool>(conn)?;
+
+            if !is_co_member {
+                return Err(Error::Forbidden);
+            }
+        }
+
+        let agents = agent::table
+            .filter(agent::account_id.eq(account_id))
+            .load::<models::Agent>(conn)?;
+
+        Ok(ListProfilesResponse::new(&agents))
+    }
+}
+
+/// Tells the room about the new member.
+fn notify_join(
+    notification_tx: &Sender<Notification>,
+    agent: &models::RoomAgent,
+) -> JoinResponse {
+    let payload = JoinEventPayload::new(agent);
+    let event = JoinEvent::new(agent.room_id, payload);
+    let event_kind = EventKind::from(event);
+    notification_tx.send(event_kind.into()).unwrap();
+
+    JoinResponse::new(agent)
+}
+
+/// Lets the agent into the room with the invitation. Only the caller redeems
+/// invitations, for itself.
+fn join_with_invite(
+    conn: &PgConnection,
+    secret: &[u8],
+    room: &models::Room,
+    agent_id: Uuid,
+    req: &JoinRequest,
+    token: &str,
+) -> Result<models::RoomAgent> {
+    conn.transaction::<_, Error, _>(|| {
+        let invite = rpc::invite::redeem(conn, secret, token, room.id)?;
+
+        let changeset = models::NewRoomAgent {
+            room_id: room.id,
+            agent_id,
+            label: invite.label.unwrap_or_else(|| req.data.label.clone()),
+            data: req.data.data.clone(),
+            role: invite.role,
+        };
+
+        diesel::insert_into(room_agent::table)
+            .values(&changeset)
+            .get_result(conn)
+            .map_err(Error::from)
+    })
+}
+
+/// Puts the agent in the lobby of the room and asks its moderators to decide on it.
+fn knock(
+    conn: &PgConnection,
+    notification_tx: &Sender<Notification>,
+    room: &models::Room,
+    req: JoinRequest,
+    role: models::Role,
+    timeout: Duration,
+) -> Result<JoinResponse> {
+    lobby::purge_expired(conn, notification_tx, room.id, timeout)?;
+
+    let changeset = models::NewLobbyRequest {
+        room_id: room.id,
+        agent_id: req.id,
+        label: req.data.label,
+        role,
+        data: req.data.data,
+    };
+
+    let request: models::LobbyRequest = diesel::insert_into(lobby_request::table)
+        .values(&changeset)
+        .get_result(conn)?;
+
+    let entry = LobbyEntry::new(&request);
+    for moderator_id in lobby::moderator_ids(conn, room.id)? {
+        let method = entry.to_method(moderator_id);
+        notification_tx.send(method.into()).unwrap();
+    }
+
+    Ok(JoinResponse::pending(&request))
+}
+
+/// Removes the agent from the room along with its tracks and signaling sessions
+/// in the room, notifies the room about that and replans its peer connections.
+pub fn remove_member(
+    conn: &PgConnection,
+    notification_tx: &Sender<Notification>,
+    sessions: &Mutex<Sessions>,
+    planner: &Mutex<Planner>,
+    room_agent: &models::RoomAgent,
+) -> Result<LeaveResponse> {
+    let tracks = conn.transaction::<_, Error, _>(|| delete_member(conn, room_agent))?;
+    let resp = notify_leave(notification_tx, sessions, room_agent, &tracks);
+    topology::update(conn, notification_tx, planner, room_agent.room_id);
+
+    Ok(resp)
+}
+
+/// Deletes the membership along with the tracks of the agent bound to the room.
+pub fn delete_member(
+    conn: &PgConnection,
+    room_agent: &models::RoomAgent,
+) -> Result<Vec<models::Track>> {
+    let tracks = diesel::delete(
+        track::table
+            .filter(track::owner_id.eq(room_agent.agent_id))
+            .filter(track::room_id.eq(room_agent.room_id)),
+    ).get_results::<models::Track>(conn)?;
+
+    diesel::delete(room_agent).execute(conn)?;
+
+    Ok(tracks)
+}
+
+/// Tells the room that the agent and its tracks are gone, once they're deleted.
+pub fn notify_leave(
+    notification_tx: &Sender<Notification>,
+    sessions: &Mutex<Sessions>,
+    room_agent: &models::RoomAgent,
+    tracks: &[models::Track],
+) -> LeaveResponse {
+    sessions.lock().unwrap().remove_agent(room_agent.room_id, room_agent.agent_id);
+
+    for track in tracks {
+        let payload = TrackDeleteResponse::new(track);
+        let event = TrackDeleteEvent::new(room_agent.room_id, payload);
         let event_kind = EventKind::from(event);
         notification_tx.send(event_kind.into()).unwrap();
+    }
 
-        Ok(resp)
+    let resp = LeaveResponse::new(room_agent);
+
+    let event = LeaveEvent::new(room_agent.room_id, resp.clone());
+    let event_kind = EventKind::from(event);
+    notification_tx.send(event_kind.into()).unwrap();
+
+    resp
+}
+
+#[cfg(test)]
+mod tests {
+    use rpc::tests::{build_pool, call, execute, subject};
+
+    const ROOM_ID: &str = "050b7c6f-795c-4cb4-aeea-5ee3f9083de2";
+    const AGENT_ID: &str = "1154b35c-e5b0-4a42-8ab2-d4967ce38c9e";
+    const ACCOUNT_ID: &str = "31517b3d-5a14-4a14-a6c5-2ba63f7375d3";
+    const PEER_ID: &str = "9923576c-1ee5-4987-a797-6cb4982d45de";
+    const PEER_ACCOUNT_ID: &str = "5f9e4b5c-2f8e-4d55-9b5b-1b0e8e2a7a3c";
+    const STRANGER_ID: &str = "e19c94cf-53eb-4048-9c94-7ae74ff6d912";
+    const STRANGER_ACCOUNT_ID: &str = "058df470-73ea-43a4-b36c-e4615cad468e";
+
+    #[test]
+    #[ignore]
+    fn list_profiles_of_co_members() {
+        let pool = build_pool();
+        let setup = format!(
+            "insert into account (id) values ('{account}'), ('{peer_account}'),
+                 ('{stranger_account}');
+             insert into agent (id, account_id) values ('{agent}', '{account}'),
+                 ('{peer}', '{peer_account}'), ('{stranger}', '{stranger_account}');
+             insert into room (id) values ('{room}');
+             insert into room_agent (agent_id, room_id, label)
+                 values ('{agent}', '{room}', 'a'), ('{peer}', '{room}', 'b');",
+            agent = AGENT_ID,
+            account = ACCOUNT_ID,
+            peer = PEER_ID,
+            peer_account = PEER_ACCOUNT_ID,
+            stranger = STRANGER_ID,
+            stranger_account = STRANGER_ACCOUNT_ID,
+            room = ROOM_ID,
+        );
+        execute(&pool, &setup);
+
+        let list = |account_id: &str| {
+            let params = format!(r#"{{"fq":"account_id:{}"}}"#, account_id);
+            call(&pool, subject(AGENT_ID, ACCOUNT_ID), "agent.list_profiles", &params).0
+        };
+
+        assert_eq!(list(ACCOUNT_ID)["result"][0]["id"], AGENT_ID);
+        assert_eq!(list(PEER_ACCOUNT_ID)["result"][0]["id"], PEER_ID);
+        assert_eq!(list(STRANGER_ACCOUNT_ID)["error"]["code"], 403);
+
+        let agent = subject(AGENT_ID, ACCOUNT_ID);
+        let (resp, _) = call(&pool, agent, "agent.list_profiles", "{}");
+        assert_eq!(resp["error"]["code"], 400);
+    }
+
+    #[test]
+    #[ignore]
+    fn join_on_own_behalf() {
+        let pool = build_pool();
+        let setup = format!(
+            "insert into account (id) values ('{account}'), ('{peer_account}');
+             insert into agent (id, account_id)
+                 values ('{agent}', '{account}'), ('{peer}', '{peer_account}');
+             insert into room (id, owner_account_id) values ('{room}', '{account}');
+             insert into room_agent (agent_id, room_id, label, role)
+                 values ('{agent}', '{room}', 'a', 'owner');",
+            agent = AGENT_ID,
+            account = ACCOUNT_ID,
+            peer = PEER_ID,
+            peer_account = PEER_ACCOUNT_ID,
+            room = ROOM_ID,
+        );
+        execute(&pool, &setup);
+
+        let join = |agent_id: &str| {
+            format!(
+                r#"{{"id":"{}","room_id":"{}","data":{{"label":"b","role":"moderator"}}}}"#,
+                agent_id, ROOM_ID
+            )
+        };
+
+        let owner = subject(AGENT_ID, ACCOUNT_ID);
+        let (resp, _) = call(&pool, owner, "agent.join_room", &join(PEER_ID));
+        assert_eq!(resp["error"]["code"], 403);
+
+        let peer = subject(PEER_ID, PEER_ACCOUNT_ID);
+        let (resp, _) = call(&pool, peer, "agent.join_room", &join(PEER_ID));
+        assert_eq!(resp["error"]["code"], 403);
+    }
+
+    #[test]
+    #[ignore]
+    fn join_room_of_own_namespace_only() {
+        let pool = build_pool();
+        let setup = format!(
+            "insert into account (id) values ('{account}');
+             insert into agent (id, account_id) values ('{agent}', '{account}');
+             insert into room (id, owner_account_id, service)
+                 values ('{room}', '{account}', 'signals.example.org');",
+            agent = AGENT_ID,
+            account = ACCOUNT_ID,
+            room = ROOM_ID,
+        );
+        execute(&pool, &setup);
+
+        let req = format!(
+            r#"{{"id":"{}","room_id":"{}","data":{{"label":"a","role":"owner"}}}}"#,
+            AGENT_ID, ROOM_ID
+        );
+        let (resp, _) = call(&pool, subject(AGENT_ID, ACCOUNT_ID), "agent.join_room", &req);
+        assert_eq!(resp["error"]["code"], 403);
     }
 }
@@ -1,7 +1,16 @@
 use diesel;
+use diesel::r2d2::PoolError;
+use diesel::result::{DatabaseErrorInformation, DatabaseErrorKind};
 use jsonrpc_core as jsonrpc;
+use openssl::error::ErrorStack;
+use serde_json;
 
+use std::fmt;
+
+use auth;
 use error;
+use sdp;
+use signaling;
 
 pub type Result<T> = ::std::result::Result<T, Error>;
 
@@ -10,16 +19,138 @@ pub enum Error {
     #[fail(display = "Bad request")]
     BadRequest,
 
+    #[fail(display = "Conflict: {} already exists", _0)]
+    Conflict(ErrorData),
+
+    #[fail(display = "{} are disabled", _0)]
+    Disabled(&'static str),
+
+    #[fail(display = "{}", _0)]
+    Crypto(#[cause] ErrorStack),
+
     #[fail(display = "{}", _0)]
     Db(#[cause] diesel::result::Error),
 
+    #[fail(display = "Forbidden")]
+    Forbidden,
+
     #[fail(display = "Invalid parameters")]
     InvalidParameters(#[cause] error::ParseError),
+
+    #[fail(display = "Invalid value: {}", _0)]
+    InvalidValue(ErrorData),
+
+    #[fail(display = "Invalid session description: {}", _0)]
+    InvalidDescription(#[cause] sdp::Error),
+
+    #[fail(display = "Not found: {}", _0)]
+    NotFound(ErrorData),
+
+    #[fail(display = "Precondition failed: {} has changed", _0)]
+    PreconditionFailed(ErrorData),
+
+    #[fail(display = "Too many requests")]
+    TooManyRequests,
+
+    #[fail(display = "Unauthorized: {}", _0)]
+    Unauthorized(#[cause] auth::Error),
+
+    #[fail(display = "Service unavailable")]
+    Unavailable(#[cause] PoolError),
+}
+
+impl Error {
+    fn data(&self) -> Option<&ErrorData> {
+        match *self {
+            Error::Conflict(ref data)
+            | Error::InvalidValue(ref data)
+            | Error::NotFound(ref data)
+            | Error::PreconditionFailed(ref data) => Some(data),
+            _ => None,
+        }
+    }
+}
+
+/// Machine-readable details of an error: the entity and its field the error is about.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ErrorData {
+    pub entity: String,
+    pub field: String,
+}
+
+impl ErrorData {
+    pub fn new(entity: &str, field: &str) -> ErrorData {
+        ErrorData {
+            entity: entity.to_owned(),
+            field: field.to_owned(),
+        }
+    }
+
+    fn from_info(info: &DatabaseErrorInformation) -> Option<ErrorData> {
+        if let Some(data) = info.constraint_name().and_then(constraint_data) {
+            return Some(data);
+        }
+
+        match (info.table_name(), info.column_name()) {
+            (Some(table), Some(column)) => Some(ErrorData::new(table, column)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ErrorData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({})", self.entity, self.field)
+    }
+}
+
+/// Maps constraints created by the migrations to the entity and the field they refer to.
+/// For foreign keys that's the referenced entity.
+fn constraint_data(constraint: &str) -> Option<ErrorData> {
+    let (entity, field) = match constraint {
+        "account_pkey" => ("account", "id"),
+        "agent_pkey" => ("agent", "id"),
+        "agent_account_id_fkey" => ("account", "account_id"),
+        "invite_pkey" => ("invite", "id"),
+        "invite_room_id_fkey" => ("room", "room_id"),
+        "invite_created_by_fkey" => ("agent", "created_by"),
+        "lobby_request_pkey" => ("lobby_request", "agent_id"),
+        "lobby_request_room_id_fkey" => ("room", "room_id"),
+        "lobby_request_agent_id_fkey" => ("agent", "agent_id"),
+        "room_pkey" => ("room", "id"),
+        "track_pkey" => ("track", "id"),
+        "track_owner_id_fkey" => ("agent", "owner_id"),
+        "track_owner_id_room_id_fkey" => ("room_agent", "room_id"),
+        "room_agent_pkey" => ("room_agent", "room_id"),
+        "room_agent_agent_id_fkey" => ("agent", "agent_id"),
+        "room_agent_room_id_fkey" => ("room", "room_id"),
+        "room_ban_pkey" => ("room_ban", "agent_id"),
+        "room_ban_room_id_fkey" => ("room", "room_id"),
+        "subscription_pkey" => ("subscription", "resource"),
+        _ => return None,
+    };
+
+    Some(ErrorData::new(entity, field))
 }
 
 impl From<diesel::result::Error> for Error {
     fn from(e: diesel::result::Error) -> Self {
-        Error::Db(e)
+        let data = match e {
+            diesel::result::Error::DatabaseError(_, ref info) => ErrorData::from_info(&**info),
+            _ => None,
+        };
+
+        match (e, data) {
+            (
+                diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _),
+                Some(data),
+            ) => Error::Conflict(data),
+            (
+                diesel::result::Error::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _),
+                Some(data),
+            ) => Error::NotFound(data),
+            (e, _) => Error::Db(e),
+        }
     }
 }
 
@@ -29,20 +160,158 @@ impl From<error::ParseError> for Error {
     }
 }
 
+impl From<auth::Error> for Error {
+    fn from(e: auth::Error) -> Self {
+        Error::Unauthorized(e)
+    }
+}
+
+impl From<signaling::Error> for Error {
+    fn from(e: signaling::Error) -> Self {
+        let data = ErrorData::new("webrtc_session", "offer");
+
+        match e {
+            signaling::Error::Glare => Error::Conflict(data),
+            signaling::Error::NoPendingOffer => Error::NotFound(data),
+            signaling::Error::CandidateQueueFull => Error::TooManyRequests,
+        }
+    }
+}
+
+impl From<sdp::Error> for Error {
+    fn from(e: sdp::Error) -> Self {
+        Error::InvalidDescription(e)
+    }
+}
+
+impl From<ErrorStack> for Error {
+    fn from(e: ErrorStack) -> Self {
+        Error::Crypto(e)
+    }
+}
+
+impl From<PoolError> for Error {
+    fn from(e: PoolError) -> Self {
+        Error::Unavailable(e)
+    }
+}
+
 impl From<Error> for jsonrpc::Error {
     fn from(err: Error) -> Self {
         let code = match err {
             Error::Db(ref e) => match *e {
                 diesel::result::Error::NotFound => 404,
+                diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => 409,
                 _ => 422,
             },
-            _ => 500,
+            Error::BadRequest | Error::InvalidParameters(_) => 400,
+            Error::Unauthorized(_) => 401,
+            Error::Crypto(_) => 500,
+            Error::Disabled(_) => 501,
+            Error::Forbidden => 403,
+            Error::NotFound(_) => 404,
+            Error::Conflict(_) => 409,
+            Error::PreconditionFailed(_) => 412,
+            Error::InvalidValue(_) | Error::InvalidDescription(_) => 422,
+            Error::TooManyRequests => 429,
+            Error::Unavailable(_) => 503,
         };
 
+        let data = err.data().and_then(|data| serde_json::to_value(data).ok());
+
         jsonrpc::Error {
             code: jsonrpc::ErrorCode::ServerError(code),
             message: err.to_string(),
-            data: None,
+            data,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constraint_data_of_foreign_key() {
+        assert_eq!(
+            constraint_data("room_agent_room_id_fkey"),
+            Some(ErrorData::new("room", "room_id"))
+        );
+        assert_eq!(constraint_data("foo_fkey"), None);
+    }
+
+    #[test]
+    fn conflict_into_jsonrpc_error() {
+        let err = Error::Conflict(ErrorData::new("room_agent", "room_id"));
+        let err = jsonrpc::Error::from(err);
+
+        assert_eq!(err.code, jsonrpc::ErrorCode::ServerError(409));
+        assert_eq!(err.message, "Conflict: room_agent (room_id) already exists");
+        assert_eq!(
+            err.data,
+            serde_json::from_str(r#"{ "entity": "room_agent", "field": "room_id" }"#).ok()
+        );
+    }
+
+    #[test]
+    fn not_found_into_jsonrpc_error() {
+        let err = Error::NotFound(ErrorData::new("room", "room_id"));
+        let err = jsonrpc::Error::from(err);
+
+        assert_eq!(err.code, jsonrpc::ErrorCode::ServerError(404));
+        assert_eq!(
+            err.data,
+            serde_json::from_str(r#"{ "entity": "room", "field": "room_id" }"#).ok()
+        );
+    }
+
+    #[test]
+    fn precondition_failed_into_jsonrpc_error() {
+        let err = Error::PreconditionFailed(ErrorData::new("room_agent", "version"));
+        let err = jsonrpc::Error::from(err);
+
+        assert_eq!(err.code, jsonrpc::ErrorCode::ServerError(412));
+        assert_eq!(err.message, "Precondition failed: room_agent (version) has changed");
+    }
+
+    #[test]
+    fn unauthorized_into_jsonrpc_error() {
+        let err = jsonrpc::Error::from(Error::Unauthorized(auth::Error::Expired));
+
+        assert_eq!(err.code, jsonrpc::ErrorCode::ServerError(401));
+        assert_eq!(err.message, "Unauthorized: Token has expired");
+    }
+
+    #[test]
+    fn invalid_description_into_jsonrpc_error() {
+        let err = Error::from(sdp::Error::MissingMid(1));
+        let err = jsonrpc::Error::from(err);
+
+        assert_eq!(err.code, jsonrpc::ErrorCode::ServerError(422));
+        assert_eq!(err.message, "Invalid session description: media section 1 has no mid");
+    }
+
+    #[test]
+    fn glare_into_jsonrpc_error() {
+        let err = jsonrpc::Error::from(Error::from(signaling::Error::Glare));
+
+        assert_eq!(err.code, jsonrpc::ErrorCode::ServerError(409));
+        assert_eq!(err.message, "Conflict: webrtc_session (offer) already exists");
+    }
+
+    #[test]
+    fn too_many_requests_into_jsonrpc_error() {
+        let err = jsonrpc::Error::from(Error::TooManyRequests);
+
+        assert_eq!(err.code, jsonrpc::ErrorCode::ServerError(429));
+        assert_eq!(err.message, "Too many requests");
+    }
+
+    #[test]
+    fn forbidden_into_jsonrpc_error() {
+        let err = jsonrpc::Error::from(Error::Forbidden);
+
+        assert_eq!(err.code, jsonrpc::ErrorCode::ServerError(403));
+        assert_eq!(err.data, None);
+    }
+}
@@ -0,0 +1,145 @@
+use diesel;
+use diesel::dsl::{now, IntervalDsl};
+use diesel::prelude::*;
+use diesel::PgConnection;
+use uuid::Uuid;
+
+use std::str::FromStr;
+
+use invite::Token;
+use messages::invite::{CreateRequest, CreateResponse, ListRequest, ListResponse, RevokeRequest,
+                       RevokeResponse};
+use models::{self, Role};
+use rpc;
+use rpc::error::{Error, Result};
+use rpc::permission::{self, Action};
+use schema::invite;
+
+build_rpc_trait! {
+    pub trait Rpc {
+        type Metadata;
+
+        #[rpc(meta, name = "room.invite.create")]
+        fn create(&self, Self::Metadata, CreateRequest) -> Result<CreateResponse>;
+
+        #[rpc(meta, name = "room.invite.list")]
+        fn list(&self, Self::Metadata, ListRequest) -> Result<ListResponse>;
+
+        #[rpc(meta, name = "room.invite.revoke")]
+        fn revoke(&self, Self::Metadata, RevokeRequest) -> Result<RevokeResponse>;
+    }
+}
+
+pub struct RpcImpl;
+
+impl Rpc for RpcImpl {
+    type Metadata = rpc::Meta;
+
+    fn create(&self, meta: rpc::Meta, req: CreateRequest) -> Result<CreateResponse> {
+        req.data.validate().map_err(Error::InvalidValue)?;
+        let secret = secret(&meta.config)?;
+
+        let conn = establish_connection!(meta.db_pool.unwrap());
+        let subject_id = meta.subject.agent_id;
+        let role = req.data.role.unwrap_or(Role::Speaker);
+
+        let subject_role = permission::member_role(conn, subject_id, req.room_id)?;
+        let subject_role = subject_role.ok_or(Error::Forbidden)?;
+        permission::authorize(subject_role, Action::ManageInvites)?;
+
+        // Agents joining with the invitation get its role without further checks.
+        permission::authorize(subject_role, Action::SetRole(role))?;
+
+        let expires_in = i64::from(req.data.expires_in());
+
+        let invite: models::Invite = diesel::insert_into(invite::table)
+            .values((
+                invite::room_id.eq(req.room_id),
+                invite::label.eq(req.data.label.clone()),
+                invite::role.eq(role),
+                invite::single_use.eq(req.data.single_use),
+                invite::expires_at.eq(now + expires_in.seconds()),
+                invite::created_by.eq(subject_id),
+            ))
+            .get_result(conn)?;
+
+        let token = Token::sign(secret, invite.id, invite.room_id)?;
+
+        Ok(CreateResponse::new(&invite, &token))
+    }
+
+    fn list(&self, meta: rpc::Meta, req: ListRequest) -> Result<ListResponse> {
+        let secret = secret(&meta.config)?;
+        let conn = establish_connection!(meta.db_pool.unwrap());
+
+        let action = Action::ManageInvites;
+        permission::authorize_member(conn, meta.subject.agent_id, req.room_id, action)?;
+
+        let invites = invite::table
+            .filter(invite::room_id.eq(req.room_id))
+            .order(invite::created_at)
+            .load::<models::Invite>(conn)?;
+
+        let invites = invites
+            .iter()
+            .map(|invite| {
+                let token = Token::sign(secret, invite.id, invite.room_id)?;
+                Ok(CreateResponse::new(invite, &token))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(ListResponse::new(invites))
+    }
+
+    fn revoke(&self, meta: rpc::Meta, req: RevokeRequest) -> Result<RevokeResponse> {
+        let secret = secret(&meta.config)?;
+        let conn = establish_connection!(meta.db_pool.unwrap());
+
+        let action = Action::ManageInvites;
+        permission::authorize_member(conn, meta.subject.agent_id, req.room_id, action)?;
+
+        let target = invite::table
+            .filter(invite::id.eq(req.id))
+            .filter(invite::room_id.eq(req.room_id));
+        let invite: models::Invite = diesel::delete(target).get_result(conn)?;
+
+        let token = Token::sign(secret, invite.id, invite.room_id)?;
+
+        Ok(RevokeResponse::new(&invite, &token))
+    }
+}
+
+/// The key invitation tokens are signed with, if invitations are enabled.
+pub fn secret(config: &rpc::Config) -> Result<&[u8]> {
+    config
+        .invite_secret
+        .as_ref()
+        .map(Vec::as_slice)
+        .ok_or(Error::Disabled("Invitations"))
+}
+
+/// Checks the token of an invitation to the room and counts one more use of it.
+/// Forged, expired, revoked and used up invitations are forbidden alike.
+pub fn redeem(
+    conn: &PgConnection,
+    secret: &[u8],
+    token: &str,
+    room_id: Uuid,
+) -> Result<models::Invite> {
+    let token = Token::from_str(token).map_err(|_| Error::Forbidden)?;
+    if !token.verify(secret, room_id)? {
+        return Err(Error::Forbidden);
+    }
+
+    let target = invite::table
+        .filter(invite::id.eq(token.id))
+        .filter(invite::room_id.eq(room_id))
+        .filter(invite::expires_at.gt(now))
+        .filter(invite::single_use.eq(false).or(invite::uses.eq(0)));
+
+    diesel::update(target)
+        .set(invite::uses.eq(invite::uses + 1))
+        .get_result::<models::Invite>(conn)
+        .optional()?
+        .ok_or(Error::Forbidden)
+}
@@ -0,0 +1,211 @@
+use diesel;
+use diesel::dsl::{now, IntervalDsl};
+use diesel::prelude::*;
+use diesel::PgConnection;
+use uuid::Uuid;
+
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+
+use messages::agent::{JoinEvent, JoinEventPayload};
+use messages::lobby::{AdmitRequest, AdmitResponse, DenyRequest, DenyResponse, Entry,
+                      ListRequest, ListResponse};
+use messages::{EventKind, Notification};
+use models::{self, Role};
+use rpc;
+use rpc::error::{Error, Result};
+use rpc::permission::{self, Action};
+use rpc::topology;
+use schema::{lobby_request, room_agent};
+
+build_rpc_trait! {
+    pub trait Rpc {
+        type Metadata;
+
+        #[rpc(meta, name = "lobby.admit")]
+        fn admit(&self, Self::Metadata, AdmitRequest) -> Result<AdmitResponse>;
+
+        #[rpc(meta, name = "lobby.deny")]
+        fn deny(&self, Self::Metadata, DenyRequest) -> Result<DenyResponse>;
+
+        #[rpc(meta, name = "lobby.list")]
+        fn list(&self, Self::Metadata, ListRequest) -> Result<ListResponse>;
+    }
+}
+
+pub struct RpcImpl;
+
+impl Rpc for RpcImpl {
+    type Metadata = rpc::Meta;
+
+    fn admit(&self, meta: rpc::Meta, req: AdmitRequest) -> Result<AdmitResponse> {
+        let conn = establish_connection!(meta.db_pool.unwrap());
+        let subject_id = meta.subject.agent_id;
+        let (room_id, agent_id) = (req.room_id, req.data.agent_id);
+        let timeout = meta.config.lobby_timeout;
+
+        let role = permission::member_role(conn, subject_id, room_id)?;
+        let role = role.ok_or(Error::Forbidden)?;
+        permission::authorize(role, Action::ManageLobby)?;
+
+        let notification_tx = meta.notification_tx.unwrap();
+        purge_expired(conn, &notification_tx, room_id, timeout)?;
+
+        let agent = conn.transaction::<_, Error, _>(|| {
+            let target = lobby_request::table.find((room_id, agent_id));
+            let request: models::LobbyRequest = diesel::delete(target).get_result(conn)?;
+
+            // The requested role has to be within the admitting member's reach.
+            permission::authorize(role, Action::SetRole(request.role))?;
+
+            let changeset = models::NewRoomAgent {
+                room_id,
+                agent_id,
+                label: request.label,
+                data: Some(request.data),
+                role: request.role,
+            };
+
+            diesel::insert_into(room_agent::table)
+                .values(&changeset)
+                .get_result::<models::RoomAgent>(conn)
+                .map_err(Error::from)
+        })?;
+
+        let resp = AdmitResponse::new(&agent);
+
+        let payload = JoinEventPayload::new(&agent);
+        let event = JoinEvent::new(room_id, payload);
+        let event_kind = EventKind::from(event);
+        notification_tx.send(event_kind.into()).unwrap();
+
+        let method = req.to_method(subject_id);
+        notification_tx.send(method.into()).unwrap();
+
+        topology::update(conn, &notification_tx, &meta.planner, room_id);
+
+        Ok(resp)
+    }
+
+    fn deny(&self, meta: rpc::Meta, req: DenyRequest) -> Result<DenyResponse> {
+        let conn = establish_connection!(meta.db_pool.unwrap());
+        let subject_id = meta.subject.agent_id;
+
+        permission::authorize_member(conn, subject_id, req.room_id, Action::ManageLobby)?;
+
+        let notification_tx = meta.notification_tx.unwrap();
+        purge_expired(conn, &notification_tx, req.room_id, meta.config.lobby_timeout)?;
+
+        let target = lobby_request::table.find((req.room_id, req.data.agent_id));
+        let request: models::LobbyRequest = diesel::delete(target).get_result(conn)?;
+
+        let resp = DenyResponse::new(&request);
+
+        let method = req.to_method(subject_id);
+        notification_tx.send(method.into()).unwrap();
+
+        Ok(resp)
+    }
+
+    fn list(&self, meta: rpc::Meta, req: ListRequest) -> Result<ListResponse> {
+        let conn = establish_connection!(meta.db_pool.unwrap());
+
+        let action = Action::ManageLobby;
+        permission::authorize_member(conn, meta.subject.agent_id, req.room_id, action)?;
+
+        let notification_tx = meta.notification_tx.unwrap();
+        purge_expired(conn, &notification_tx, req.room_id, meta.config.lobby_timeout)?;
+
+        let requests = lobby_request::table
+            .filter(lobby_request::room_id.eq(req.room_id))
+            .order(lobby_request::created_at)
+            .load::<models::LobbyRequest>(conn)?;
+
+        Ok(ListResponse::new(&requests))
+    }
+}
+
+/// Drops requests of the room which have waited longer than the timeout and tells
+/// their agents with `lobby.expire`. Expiry is lazy: it happens whenever the lobby
+/// of the room is used, so an expired request lingers until then.
+pub fn purge_expired(
+    conn: &PgConnection,
+    notification_tx: &Sender<Notification>,
+    room_id: Uuid,
+    timeout: Duration,
+) -> Result<()> {
+    let expires_at = now - (timeout.as_secs() as i64).seconds();
+
+    let requests = diesel::delete(
+        lobby_request::table
+            .filter(lobby_request::room_id.eq(room_id))
+            .filter(lobby_request::created_at.lt(expires_at)),
+    ).get_results::<models::LobbyRequest>(conn)?;
+
+    for request in &requests {
+        let method = Entry::new(request).to_expire_method();
+        notification_tx.send(method.into()).unwrap();
+    }
+
+    Ok(())
+}
+
+/// Members of the room who decide on lobby requests.
+pub fn moderator_ids(conn: &PgConnection, room_id: Uuid) -> Result<Vec<Uuid>> {
+    room_agent::table
+        .select(room_agent::agent_id)
+        .filter(room_agent::room_id.eq(room_id))
+        .filter(room_agent::role.eq_any(vec![Role::Owner, Role::Moderator]))
+        .get_results(conn)
+        .map_err(Error::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use messages::Notification;
+    use rpc::tests::{build_pool, call, execute, subject};
+
+    const ROOM_ID: &str = "050b7c6f-795c-4cb4-aeea-5ee3f9083de2";
+    const OWNER_ID: &str = "1154b35c-e5b0-4a42-8ab2-d4967ce38c9e";
+    const OWNER_ACCOUNT_ID: &str = "31517b3d-5a14-4a14-a6c5-2ba63f7375d3";
+    const AGENT_ID: &str = "9923576c-1ee5-4987-a797-6cb4982d45de";
+    const ACCOUNT_ID: &str = "5f9e4b5c-2f8e-4d55-9b5b-1b0e8e2a7a3c";
+
+    #[test]
+    #[ignore]
+    fn expire_requests() {
+        let pool = build_pool();
+        let setup = format!(
+            "insert into account (id) values ('{owner_account}'), ('{account}');
+             insert into agent (id, account_id)
+                 values ('{owner}', '{owner_account}'), ('{agent}', '{account}');
+             insert into room (id, owner_account_id, lobby)
+                 values ('{room}', '{owner_account}', true);
+             insert into room_agent (agent_id, room_id, label, role)
+                 values ('{owner}', '{room}', 'a', 'owner');
+             insert into lobby_request (room_id, agent_id, label, role, created_at)
+                 values ('{room}', '{agent}', 'b', 'speaker', now() - interval '1 day');",
+            owner = OWNER_ID,
+            owner_account = OWNER_ACCOUNT_ID,
+            agent = AGENT_ID,
+            account = ACCOUNT_ID,
+            room = ROOM_ID,
+        );
+        execute(&pool, &setup);
+
+        let req = format!(r#"{{"room_id":"{}"}}"#, ROOM_ID);
+        let owner = subject(OWNER_ID, OWNER_ACCOUNT_ID);
+        let (resp, rx) = call(&pool, owner, "lobby.list", &req);
+        assert!(resp["result"].as_array().unwrap().is_empty());
+
+        let expired: Vec<_> = rx.try_iter()
+            .filter_map(|notification| match notification {
+                Notification::Method(m) => Some(m),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].agent_id.to_string(), AGENT_ID);
+        assert_eq!(expired[0].body.method, "lobby.expire");
+    }
+}
@@ -1,22 +1,37 @@
-use jsonrpc_core::{MetaIoHandler, Metadata};
+use jsonrpc_core::futures::Future;
+use jsonrpc_core::{self as jsonrpc, Call, MetaIoHandler, Metadata, Output, Request, Response,
+                   Version};
+use serde_json;
 
 use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use messages::{EnvelopeSubject, Notification};
+use rate_limit::RateLimiter;
 use rpc::agent::Rpc as AgentRpc;
+use rpc::invite::Rpc as InviteRpc;
+use rpc::lobby::Rpc as LobbyRpc;
 use rpc::ping::Rpc as PingRpc;
 use rpc::room::Rpc as RoomRpc;
 use rpc::subscription::Rpc as SubscriptionRpc;
 use rpc::track::Rpc as TrackRpc;
 use rpc::webrtc::Rpc as WebrtcRpc;
+use signaling::Sessions;
+use topic::Topic;
+use topology::Planner;
 use DbPool;
 
 mod agent;
-mod error;
+pub mod error;
 mod event;
+mod invite;
+mod lobby;
+mod permission;
 mod ping;
 mod room;
 mod subscription;
+mod topology;
 mod track;
 mod webrtc;
 
@@ -26,10 +41,45 @@ pub struct Meta {
     pub subject: EnvelopeSubject,
     pub notification_tx: Option<Sender<Notification>>,
     pub db_pool: Option<DbPool>,
+    pub service: String,
+    pub config: Arc<Config>,
+    pub rate_limiter: Option<Arc<Mutex<RateLimiter>>>,
+    pub sessions: Arc<Mutex<Sessions>>,
+    pub planner: Arc<Mutex<Planner>>,
 }
 
 impl Metadata for Meta {}
 
+/// Settings of the RPC methods.
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// How long a lobby request waits to be admitted before it expires.
+    pub lobby_timeout: Duration,
+    /// The key invitation tokens are signed with, invitations are disabled without it.
+    pub invite_secret: Option<Vec<u8>>,
+    /// URLs of STUN servers agents are given.
+    pub stun_urls: Vec<String>,
+    /// URLs of TURN servers agents are given credentials of.
+    pub turn_urls: Vec<String>,
+    /// The key shared with TURN servers credentials are signed with.
+    pub turn_secret: Vec<u8>,
+    /// How long TURN credentials are valid, a week at most.
+    pub turn_ttl: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            lobby_timeout: Duration::from_secs(300),
+            invite_secret: None,
+            stun_urls: vec![],
+            turn_urls: vec![],
+            turn_secret: vec![],
+            turn_ttl: Duration::from_secs(3600),
+        }
+    }
+}
+
 pub type Server = MetaIoHandler<Meta>;
 
 pub fn build_server() -> Server {
@@ -50,6 +100,12 @@ pub fn build_server() -> Server {
     let rpc = track::RpcImpl {};
     io.extend_with(rpc.to_delegate());
 
+    let rpc = lobby::RpcImpl {};
+    io.extend_with(rpc.to_delegate());
+
+    let rpc = invite::RpcImpl {};
+    io.extend_with(rpc.to_delegate());
+
     let rpc = webrtc::RpcImpl {};
     io.extend_with(rpc.to_delegate());
 
@@ -57,3 +113,332 @@ pub fn build_server() -> Server {
 
     io
 }
+
+pub fn handle_request(server: &Server, topic: &Topic, request: &str, meta: Meta) -> Option<String> {
+    let request = match serde_json::from_str::<Request>(request) {
+        Ok(request) => request,
+        Err(_) => {
+            let resp = Response::from(jsonrpc::Error::parse_error(), Some(Version::V2));
+            return serde_json::to_string(&resp).ok();
+        }
+    };
+
+    let resp = match request {
+        Request::Single(call) => handle_call(server, topic, call, meta).map(Response::Single),
+        Request::Batch(ref calls) if calls.is_empty() => Some(empty_batch_response()),
+        Request::Batch(calls) => {
+            // Calls are handled one after another in order they appear in the batch,
+            // so are the notifications they produce.
+            let outputs: Vec<Output> = calls
+                .into_iter()
+                .filter_map(|call| handle_call(server, topic, call, meta.clone()))
+                .collect();
+
+            if outputs.is_empty() {
+                None
+            } else {
+                Some(Response::Batch(outputs))
+            }
+        }
+    };
+
+    resp.and_then(|resp| serde_json::to_string(&resp).ok())
+}
+
+/// Replies to every call of the request with the error without handling them.
+pub fn reject_request(request: &str, err: jsonrpc::Error) -> Option<String> {
+    let request = match serde_json::from_str::<Request>(request) {
+        Ok(request) => request,
+        Err(_) => {
+            let resp = Response::from(jsonrpc::Error::parse_error(), Some(Version::V2));
+            return serde_json::to_string(&resp).ok();
+        }
+    };
+
+    let reject = |call: Call| match call {
+        Call::MethodCall(method) => Some(Output::from(Err(err.clone()), method.id, method.jsonrpc)),
+        _ => None,
+    };
+
+    let resp = match request {
+        Request::Single(call) => reject(call).map(Response::Single),
+        Request::Batch(ref calls) if calls.is_empty() => Some(empty_batch_response()),
+        Request::Batch(calls) => {
+            let outputs: Vec<Output> = calls.into_iter().filter_map(reject).collect();
+
+            if outputs.is_empty() {
+                None
+            } else {
+                Some(Response::Batch(outputs))
+            }
+        }
+    };
+
+    resp.and_then(|resp| serde_json::to_string(&resp).ok())
+}
+
+/// An empty batch is an invalid request, which gets a single reply.
+fn empty_batch_response() -> Response {
+    Response::from(jsonrpc::Error::invalid_request(), Some(Version::V2))
+}
+
+fn handle_call(server: &Server, topic: &Topic, call: Call, meta: Meta) -> Option<Output> {
+    if let Err(e) = authorize(topic, &call).and_then(|_| limit_rate(&meta, &call)) {
+        return match call {
+            Call::MethodCall(method) => Some(Output::from(Err(e.into()), method.id, method.jsonrpc)),
+            _ => None,
+        };
+    }
+
+    server
+        .handle_call(call, meta)
+        .wait()
+        .expect("Handler calls can never fail.")
+}
+
+fn call_method(call: &Call) -> Option<&str> {
+    match *call {
+        Call::MethodCall(ref m) => Some(&m.method),
+        Call::Notification(ref n) => Some(&n.method),
+        Call::Invalid(_) => None,
+    }
+}
+
+fn authorize(topic: &Topic, call: &Call) -> error::Result<()> {
+    let method = match call_method(call) {
+        Some(method) => method,
+        None => return Ok(()),
+    };
+
+    let is_allowed = match *topic {
+        Topic::Ping(_) => method == "ping",
+        Topic::Agent(_) => method != "event",
+        Topic::State(_) => method == "event",
+        Topic::App(_) => false,
+    };
+
+    if is_allowed {
+        Ok(())
+    } else {
+        Err(error::Error::Forbidden)
+    }
+}
+
+fn limit_rate(meta: &Meta, call: &Call) -> error::Result<()> {
+    let (rate_limiter, method) = match (meta.rate_limiter.as_ref(), call_method(call)) {
+        (Some(rate_limiter), Some(method)) => (rate_limiter, method),
+        _ => return Ok(()),
+    };
+
+    if rate_limiter.lock().unwrap().check(&meta.subject, method) {
+        Ok(())
+    } else {
+        Err(error::Error::TooManyRequests)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::r2d2::{self, CustomizeConnection};
+    use diesel::{Connection, PgConnection};
+    use serde_json::Value;
+    use uuid::Uuid;
+
+    use std::env;
+    use std::sync::mpsc::{self, Receiver};
+
+    use topic::AgentTopic;
+
+    fn agent_topic() -> Topic {
+        let agent_id = "e19c94cf-53eb-4048-9c94-7ae74ff6d912".parse().unwrap();
+        Topic::Agent(AgentTopic::new_in(agent_id, "signals.netology-group.services"))
+    }
+
+    /// Nothing a test does is committed, so the database is left as it was.
+    #[derive(Debug)]
+    struct TestTransaction;
+
+    impl CustomizeConnection<PgConnection, r2d2::Error> for TestTransaction {
+        fn on_acquire(&self, conn: &mut PgConnection) -> ::std::result::Result<(), r2d2::Error> {
+            conn.begin_test_transaction()
+                .map_err(r2d2::Error::QueryError)
+        }
+    }
+
+    /// A pool of the only connection to the migrated database at `DATABASE_URL`.
+    /// Tests using it are ignored by default, run them with `cargo test -- --ignored`.
+    pub fn build_pool() -> DbPool {
+        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+        let manager = r2d2::ConnectionManager::<PgConnection>::new(database_url);
+
+        r2d2::Pool::builder()
+            .max_size(1)
+            .connection_customizer(Box::new(TestTransaction))
+            .build(manager)
+            .unwrap()
+    }
+
+    /// Runs the statements to set up a test. Unlike `sql_query`, which prepares
+    /// its query, it takes several statements at once.
+    pub fn execute(pool: &DbPool, sql: &str) {
+        pool.get().unwrap().execute(sql).unwrap();
+    }
+
+    /// Calls the method on behalf of the agent, returns the response and notifications.
+    pub fn call(
+        pool: &DbPool,
+        subject: EnvelopeSubject,
+        method: &str,
+        params: &str,
+    ) -> (Value, Receiver<Notification>) {
+        let (notification_tx, notification_rx) = mpsc::channel();
+        let topic = Topic::Agent(AgentTopic::new_in(
+            subject.agent_id,
+            "signals.netology-group.services",
+        ));
+        let meta = Meta {
+            subject,
+            notification_tx: Some(notification_tx),
+            db_pool: Some(pool.clone()),
+            ..Default::default()
+        };
+
+        let req = format!(
+            r#"{{"jsonrpc":"2.0","method":"{}","params":[{}],"id":1}}"#,
+            method, params
+        );
+        let resp = handle_request(&build_server(), &topic, &req, meta).unwrap();
+
+        (serde_json::from_str(&resp).unwrap(), notification_rx)
+    }
+
+    pub fn subject(agent_id: &str, account_id: &str) -> EnvelopeSubject {
+        EnvelopeSubject {
+            agent_id: Uuid::parse_str(agent_id).unwrap(),
+            account_id: Uuid::parse_str(account_id).unwrap(),
+        }
+    }
+
+    #[test]
+    fn handle_single_request() {
+        let server = build_server();
+        let req = r#"{"jsonrpc":"2.0","method":"ping","params":[],"id":1}"#;
+
+        let resp = handle_request(&server, &agent_topic(), req, Meta::default()).unwrap();
+        assert_eq!(resp, r#"{"jsonrpc":"2.0","result":"pong","id":1}"#);
+    }
+
+    #[test]
+    fn handle_batch_request() {
+        let server = build_server();
+        let req = r#"[
+            {"jsonrpc":"2.0","method":"ping","params":[],"id":1},
+            {"jsonrpc":"2.0","method":"foo","params":[],"id":2},
+            {"jsonrpc":"2.0","method":"ping","params":[]},
+            {"jsonrpc":"2.0","method":"ping","params":[],"id":3}
+        ]"#;
+
+        let resp = handle_request(&server, &agent_topic(), req, Meta::default()).unwrap();
+        let resp: Value = serde_json::from_str(&resp).unwrap();
+        let resp = resp.as_array().unwrap();
+
+        assert_eq!(resp.len(), 3);
+        assert_eq!(resp[0]["id"], 1);
+        assert_eq!(resp[0]["result"], "pong");
+        assert_eq!(resp[1]["id"], 2);
+        assert_eq!(resp[1]["error"]["code"], -32601);
+        assert_eq!(resp[2]["id"], 3);
+        assert_eq!(resp[2]["result"], "pong");
+    }
+
+    #[test]
+    fn authorize_batch_entries() {
+        use topic::StateTopic;
+
+        let server = build_server();
+        let agent_id = "e19c94cf-53eb-4048-9c94-7ae74ff6d912".parse().unwrap();
+        let topic = Topic::State(StateTopic::new(agent_id));
+        let req = r#"[
+            {"jsonrpc":"2.0","method":"ping","params":[],"id":1},
+            {"jsonrpc":"2.0","method":"event","params":[]}
+        ]"#;
+
+        let resp = handle_request(&server, &topic, req, Meta::default()).unwrap();
+        let resp: Value = serde_json::from_str(&resp).unwrap();
+        let resp = resp.as_array().unwrap();
+
+        assert_eq!(resp.len(), 1);
+        assert_eq!(resp[0]["id"], 1);
+        assert_eq!(resp[0]["error"]["code"], 403);
+    }
+
+    #[test]
+    fn limit_rate_of_batch_entries() {
+        use rate_limit::Limits;
+
+        let server = build_server();
+        let limits = "ping=1:2".parse::<Limits>().unwrap();
+        let rate_limiter = RateLimiter::new(limits, Limits::default());
+        let meta = Meta {
+            rate_limiter: Some(Arc::new(Mutex::new(rate_limiter))),
+            ..Meta::default()
+        };
+        let req = r#"[
+            {"jsonrpc":"2.0","method":"ping","params":[],"id":1},
+            {"jsonrpc":"2.0","method":"ping","params":[],"id":2},
+            {"jsonrpc":"2.0","method":"ping","params":[],"id":3}
+        ]"#;
+
+        let resp = handle_request(&server, &agent_topic(), req, meta).unwrap();
+        let resp: Value = serde_json::from_str(&resp).unwrap();
+        let resp = resp.as_array().unwrap();
+
+        assert_eq!(resp.len(), 3);
+        assert_eq!(resp[0]["result"], "pong");
+        assert_eq!(resp[1]["result"], "pong");
+        assert_eq!(resp[2]["id"], 3);
+        assert_eq!(resp[2]["error"]["code"], 429);
+    }
+
+    #[test]
+    fn reject_batch_request() {
+        let req = r#"[
+            {"jsonrpc":"2.0","method":"ping","params":[],"id":1},
+            {"jsonrpc":"2.0","method":"ping","params":[]}
+        ]"#;
+        let err = error::Error::Unauthorized(::auth::Error::MissingToken);
+
+        let resp = reject_request(req, err.into()).unwrap();
+        let resp: Value = serde_json::from_str(&resp).unwrap();
+        let resp = resp.as_array().unwrap();
+
+        assert_eq!(resp.len(), 1);
+        assert_eq!(resp[0]["id"], 1);
+        assert_eq!(resp[0]["error"]["code"], 401);
+    }
+
+    #[test]
+    fn handle_invalid_request() {
+        let server = build_server();
+
+        let resp = handle_request(&server, &agent_topic(), "ping", Meta::default()).unwrap();
+        let resp: Value = serde_json::from_str(&resp).unwrap();
+        assert_eq!(resp["error"]["code"], -32700);
+    }
+
+    #[test]
+    fn handle_empty_batch() {
+        let server = build_server();
+
+        let resp = handle_request(&server, &agent_topic(), "[]", Meta::default()).unwrap();
+        let resp: Value = serde_json::from_str(&resp).unwrap();
+        assert_eq!(resp["error"]["code"], -32600);
+        assert_eq!(resp["id"], Value::Null);
+
+        let err = error::Error::Unauthorized(::auth::Error::MissingToken);
+        let resp = reject_request("[]", err.into()).unwrap();
+        let resp: Value = serde_json::from_str(&resp).unwrap();
+        assert_eq!(resp["error"]["code"], -32600);
+    }
+}
@@ -0,0 +1,155 @@
+use diesel::prelude::*;
+use diesel::PgConnection;
+use uuid::Uuid;
+
+use models::{Role, TrackKind};
+use rpc::error::{Error, Result};
+use schema::room_agent;
+
+/// An action of a member of a room which depends on the member's role.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    /// Create a track of the kind in the room.
+    CreateTrack(TrackKind),
+    /// Update or delete a track of another member.
+    ManageTrack,
+    /// Change the label or the data of another member.
+    UpdateMember,
+    /// Remove another member having the role from the room.
+    RemoveMember(Role),
+    /// Give the role to a member, or take it away.
+    SetRole(Role),
+    /// Ban agents or lift their bans.
+    ManageBans,
+    /// Admit agents waiting in the lobby or deny them.
+    ManageLobby,
+    /// Issue, list and revoke invitations to the room.
+    ManageInvites,
+    /// Delete the room altogether.
+    DeleteRoom,
+}
+
+fn rank(role: Role) -> u8 {
+    match role {
+        Role::Owner => 3,
+        Role::Moderator => 2,
+        Role::Speaker => 1,
+        Role::Listener => 0,
+    }
+}
+
+/// The permission matrix.
+pub fn is_allowed(role: Role, action: Action) -> bool {
+    let is_moderator = rank(role) >= rank(Role::Moderator);
+
+    match action {
+        Action::CreateTrack(TrackKind::Data) => true,
+        Action::CreateTrack(_) => rank(role) >= rank(Role::Speaker),
+        Action::ManageTrack
+        | Action::UpdateMember
+        | Action::ManageBans
+        | Action::ManageLobby
+        | Action::ManageInvites => is_moderator,
+        Action::RemoveMember(target) => {
+            is_moderator && (role == Role::Owner || rank(role) > rank(target))
+        }
+        Action::SetRole(target) => is_moderator && rank(role) >= rank(target),
+        Action::DeleteRoom => role == Role::Owner,
+    }
+}
+
+pub fn authorize(role: Role, action: Action) -> Result<()> {
+    if is_allowed(role, action) {
+        Ok(())
+    } else {
+        Err(Error::Forbidden)
+    }
+}
+
+/// Role of the agent in the room, `None` if the agent isn't in the room.
+pub fn member_role(conn: &PgConnection, agent_id: Uuid, room_id: Uuid) -> Result<Option<Role>> {
+    room_agent::table
+        .find((agent_id, room_id))
+        .select(room_agent::role)
+        .first(conn)
+        .optional()
+        .map_err(Error::from)
+}
+
+/// Checks that the agent is in the room and its role allows the action.
+pub fn authorize_member(
+    conn: &PgConnection,
+    agent_id: Uuid,
+    room_id: Uuid,
+    action: Action,
+) -> Result<()> {
+    match member_role(conn, agent_id, room_id)? {
+        Some(role) => authorize(role, action),
+        None => Err(Error::Forbidden),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn create_track() {
+        assert!(is_allowed(Role::Speaker, Action::CreateTrack(TrackKind::Video)));
+        assert!(is_allowed(Role::Moderator, Action::CreateTrack(TrackKind::Screen)));
+        assert!(!is_allowed(Role::Listener, Action::CreateTrack(TrackKind::Audio)));
+        assert!(!is_allowed(Role::Listener, Action::CreateTrack(TrackKind::Video)));
+        assert!(is_allowed(Role::Listener, Action::CreateTrack(TrackKind::Data)));
+    }
+
+    #[test]
+    fn manage_others() {
+        assert!(is_allowed(Role::Moderator, Action::ManageTrack));
+        assert!(is_allowed(Role::Owner, Action::UpdateMember));
+        assert!(!is_allowed(Role::Speaker, Action::ManageTrack));
+        assert!(!is_allowed(Role::Speaker, Action::UpdateMember));
+    }
+
+    #[test]
+    fn remove_member() {
+        assert!(is_allowed(Role::Moderator, Action::RemoveMember(Role::Speaker)));
+        assert!(!is_allowed(Role::Moderator, Action::RemoveMember(Role::Moderator)));
+        assert!(!is_allowed(Role::Moderator, Action::RemoveMember(Role::Owner)));
+        assert!(is_allowed(Role::Owner, Action::RemoveMember(Role::Owner)));
+        assert!(!is_allowed(Role::Speaker, Action::RemoveMember(Role::Listener)));
+    }
+
+    #[test]
+    fn manage_bans() {
+        assert!(is_allowed(Role::Moderator, Action::ManageBans));
+        assert!(!is_allowed(Role::Speaker, Action::ManageBans));
+    }
+
+    #[test]
+    fn manage_lobby() {
+        assert!(is_allowed(Role::Owner, Action::ManageLobby));
+        assert!(is_allowed(Role::Moderator, Action::ManageLobby));
+        assert!(!is_allowed(Role::Speaker, Action::ManageLobby));
+        assert!(!is_allowed(Role::Listener, Action::ManageLobby));
+    }
+
+    #[test]
+    fn manage_invites() {
+        assert!(is_allowed(Role::Moderator, Action::ManageInvites));
+        assert!(!is_allowed(Role::Speaker, Action::ManageInvites));
+    }
+
+    #[test]
+    fn set_role() {
+        assert!(is_allowed(Role::Moderator, Action::SetRole(Role::Moderator)));
+        assert!(!is_allowed(Role::Moderator, Action::SetRole(Role::Owner)));
+        assert!(is_allowed(Role::Owner, Action::SetRole(Role::Owner)));
+        assert!(!is_allowed(Role::Speaker, Action::SetRole(Role::Listener)));
+    }
+
+    #[test]
+    fn delete_room() {
+        assert!(is_allowed(Role::Owner, Action::DeleteRoom));
+        assert!(!is_allowed(Role::Moderator, Action::DeleteRoom));
+    }
+}
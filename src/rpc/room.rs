@@ -1,20 +1,28 @@
 use diesel;
 use diesel::prelude::*;
+use jsonrpc_macros::Trailing;
+use serde_json;
+use uuid::Uuid;
 
 use models;
 use rpc;
-use rpc::error::Result;
-use schema::room;
+use rpc::agent::{delete_member, notify_leave, remove_member};
+use rpc::error::{Error, Result};
+use rpc::permission::{self, Action};
+use rpc::topology;
+use schema::{account, agent, lobby_request, room, room_agent, room_ban};
 
-use messages::room::{CreateResponse, DeleteRequest, DeleteResponse, ListResponse, ReadRequest,
-                     ReadResponse};
+use messages::room::{BanRequest, BanResponse, CreateRequest, CreateResponse, DeleteRequest,
+                     DeleteResponse, KickRequest, KickResponse, ListBansRequest,
+                     ListBansResponse, ListResponse, ReadRequest, ReadResponse, UnbanRequest,
+                     UnbanResponse};
 
 build_rpc_trait! {
     pub trait Rpc {
         type Metadata;
 
         #[rpc(meta, name = "room.create")]
-        fn create(&self, Self::Metadata) -> Result<CreateResponse>;
+        fn create(&self, Self::Metadata, Trailing<CreateRequest>) -> Result<CreateResponse>;
 
         #[rpc(meta, name = "room.read")]
         fn read(&self, Self::Metadata, ReadRequest) -> Result<ReadResponse>;
@@ -24,6 +32,18 @@ build_rpc_trait! {
 
         #[rpc(meta, name = "room.list")]
         fn list(&self, Self::Metadata) -> Result<ListResponse>;
+
+        #[rpc(meta, name = "room.kick")]
+        fn kick(&self, Self::Metadata, KickRequest) -> Result<KickResponse>;
+
+        #[rpc(meta, name = "room.ban")]
+        fn ban(&self, Self::Metadata, BanRequest) -> Result<BanResponse>;
+
+        #[rpc(meta, name = "room.unban")]
+        fn unban(&self, Self::Metadata, UnbanRequest) -> Result<UnbanResponse>;
+
+        #[rpc(meta, name = "room.list_bans")]
+        fn list_bans(&self, Self::Metadata, ListBansRequest) -> Result<ListBansResponse>;
     }
 }
 
@@ -32,12 +52,41 @@ pub struct RpcImpl;
 impl Rpc for RpcImpl {
     type Metadata = rpc::Meta;
 
-    fn create(&self, meta: rpc::Meta) -> Result<CreateResponse> {
+    fn create(&self, meta: rpc::Meta, req: Trailing<CreateRequest>) -> Result<CreateResponse> {
+        let req = req.unwrap_or_default();
+        req.data.validate().map_err(Error::InvalidValue)?;
+
         let conn = establish_connection!(meta.db_pool.unwrap());
 
-        let room: models::Room = diesel::insert_into(room::table)
-            .default_values()
-            .get_result(conn)?;
+        let sdp_policy = serde_json::to_value(&req.data.sdp_policy)
+            .expect("Error serializing an SDP policy");
+        let topology = req.data.topology.as_ref().map(|topology| {
+            serde_json::to_value(topology).expect("Error serializing a topology policy")
+        });
+
+        // The room is owned by the account of the caller and served in its namespace.
+        let account_id = meta.subject.account_id;
+        let service = meta.service.clone();
+
+        let room = conn.transaction::<_, Error, _>(|| {
+            diesel::insert_into(account::table)
+                .values(&models::NewAccount { id: account_id })
+                .on_conflict_do_nothing()
+                .execute(conn)?;
+
+            let changeset = models::NewRoom {
+                lobby: req.data.lobby,
+                sdp_policy,
+                topology,
+                owner_account_id: Some(account_id),
+                service: Some(service),
+            };
+
+            diesel::insert_into(room::table)
+                .values(&changeset)
+                .get_result::<models::Room>(conn)
+                .map_err(Error::from)
+        })?;
 
         Ok(CreateResponse::new(&room))
     }
@@ -53,8 +102,17 @@ impl Rpc for RpcImpl {
     fn delete(&self, meta: rpc::Meta, req: DeleteRequest) -> Result<DeleteResponse> {
         let conn = establish_connection!(meta.db_pool.unwrap());
 
-        let room = room::table.find(req.room_id);
-        let room: models::Room = diesel::delete(room).get_result(conn)?;
+        let room: models::Room = room::table.find(req.room_id).first(conn)?;
+
+        // The account which has created the room may delete it, as may its owners.
+        if room.owner_account_id != Some(meta.subject.account_id) {
+            let subject_id = meta.subject.agent_id;
+            permission::authorize_member(conn, subject_id, room.id, Action::DeleteRoom)?;
+        }
+
+        let room: models::Room = diesel::delete(room::table.find(room.id)).get_result(conn)?;
+        meta.planner.lock().unwrap().remove_room(room.id);
+        meta.sessions.lock().unwrap().remove_room(room.id);
 
         Ok(DeleteResponse::new(&room))
     }
@@ -66,4 +124,357 @@ impl Rpc for RpcImpl {
 
         Ok(ListResponse::new(&rooms))
     }
+
+    fn kick(&self, meta: rpc::Meta, req: KickRequest) -> Result<KickResponse> {
+        let conn = establish_connection!(meta.db_pool.unwrap());
+        let subject_id = meta.subject.agent_id;
+
+        let room_agent = room_agent::table
+            .find((req.data.agent_id, req.room_id))
+            .first::<models::RoomAgent>(conn)?;
+
+        let action = Action::RemoveMember(room_agent.role);
+        permission::authorize_member(conn, subject_id, req.room_id, action)?;
+
+        let notification_tx = meta.notification_tx.unwrap();
+        let (sessions, planner) = (&meta.sessions, &meta.planner);
+        let resp = remove_member(conn, &notification_tx, sessions, planner, &room_agent)?;
+
+        let method = req.to_method("room.kick", subject_id);
+        notification_tx.send(method.into()).unwrap();
+
+        Ok(resp)
+    }
+
+    fn ban(&self, meta: rpc::Meta, req: BanRequest) -> Result<BanResponse> {
+        let conn = establish_connection!(meta.db_pool.unwrap());
+        let subject_id = meta.subject.agent_id;
+
+        permission::authorize_member(conn, subject_id, req.room_id, Action::ManageBans)?;
+
+        let account_id = agent::table
+            .find(req.data.agent_id)
+            .select(agent::account_id)
+            .first::<Option<Uuid>>(conn)?;
+
+        // The account which owns the room can't be banned, even while it's away.
+        let owner_account_id = room::table
+            .find(req.room_id)
+            .select(room::owner_account_id)
+            .first::<Option<Uuid>>(conn)?;
+
+        if account_id.is_some() && account_id == owner_account_id {
+            return Err(Error::Forbidden);
+        }
+
+        // The ban applies to the agent and every other agent of its account.
+        let agent_ids = agent::table
+            .filter(agent::id.eq(req.data.agent_id).or(agent::account_id.eq(account_id)))
+            .select(agent::id)
+            .load::<Uuid>(conn)?;
+
+        // So it's made only by those who may remove each of them from the room.
+        let room_agents = room_agent::table
+            .filter(room_agent::room_id.eq(req.room_id))
+            .filter(room_agent::agent_id.eq_any(agent_ids.clone()))
+            .load::<models::RoomAgent>(conn)?;
+
+        for room_agent in &room_agents {
+            let action = Action::RemoveMember(room_agent.role);
+            permission::authorize_member(conn, subject_id, req.room_id, action)?;
+        }
+
+        let (ban, members, requests) = conn.transaction::<_, Error, _>(|| {
+            let changeset = models::NewRoomBan {
+                room_id: req.room_id,
+                agent_id: req.data.agent_id,
+                account_id,
+                reason: req.data.reason.clone(),
+            };
+
+            let ban: models::RoomBan = diesel::insert_into(room_ban::table)
+                .values(&changeset)
+                .get_result(conn)?;
+
+            let requests = diesel::delete(
+                lobby_request::table
+                    .filter(lobby_request::room_id.eq(req.room_id))
+                    .filter(lobby_request::agent_id.eq_any(agent_ids)),
+            ).get_results::<models::LobbyRequest>(conn)?;
+
+            let mut members = Vec::with_capacity(room_agents.len());
+            for room_agent in room_agents {
+                let tracks = delete_member(conn, &room_agent)?;
+                members.push((room_agent, tracks));
+            }
+
+            Ok((ban, members, requests))
+        })?;
+
+        let notification_tx = meta.notification_tx.unwrap();
+
+        for &(ref room_agent, ref tracks) in &members {
+            notify_leave(&notification_tx, &meta.sessions, room_agent, tracks);
+        }
+
+        if !members.is_empty() {
+            topology::update(conn, &notification_tx, &meta.planner, req.room_id);
+        }
+
+        // Every agent removed from the room or its lobby learns about the ban.
+        let mut banned_ids: Vec<Uuid> = members
+            .iter()
+            .map(|&(ref room_agent, _)| room_agent.agent_id)
+            .chain(requests.iter().map(|request| request.agent_id))
+            .collect();
+        if !banned_ids.contains(&req.data.agent_id) {
+            banned_ids.push(req.data.agent_id);
+        }
+
+        for agent_id in banned_ids {
+            let method = req.to_method_of(agent_id, "room.ban", subject_id);
+            notification_tx.send(method.into()).unwrap();
+        }
+
+        Ok(BanResponse::new(&ban))
+    }
+
+    fn unban(&self, meta: rpc::Meta, req: UnbanRequest) -> Result<UnbanResponse> {
+        let conn = establish_connection!(meta.db_pool.unwrap());
+
+        let action = Action::ManageBans;
+        permission::authorize_member(conn, meta.subject.agent_id, req.room_id, action)?;
+
+        let target = room_ban::table.find((req.room_id, req.data.agent_id));
+        let ban = diesel::delete(target).get_result(conn)?;
+
+        Ok(UnbanResponse::new(&ban))
+    }
+
+    fn list_bans(&self, meta: rpc::Meta, req: ListBansRequest) -> Result<ListBansResponse> {
+        let conn = establish_connection!(meta.db_pool.unwrap());
+
+        let action = Action::ManageBans;
+        permission::authorize_member(conn, meta.subject.agent_id, req.room_id, action)?;
+
+        let bans = room_ban::table
+            .filter(room_ban::room_id.eq(req.room_id))
+            .load::<models::RoomBan>(conn)?;
+
+        Ok(ListBansResponse::new(&bans))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rpc::tests::{build_pool, call, execute, subject};
+
+    const ROOM_ID: &str = "050b7c6f-795c-4cb4-aeea-5ee3f9083de2";
+    const OWNER_ID: &str = "1154b35c-e5b0-4a42-8ab2-d4967ce38c9e";
+    const OWNER_ACCOUNT_ID: &str = "31517b3d-5a14-4a14-a6c5-2ba63f7375d3";
+    const AGENT_ID: &str = "9923576c-1ee5-4987-a797-6cb4982d45de";
+    const ACCOUNT_ID: &str = "5f9e4b5c-2f8e-4d55-9b5b-1b0e8e2a7a3c";
+    const SECOND_AGENT_ID: &str = "e19c94cf-53eb-4048-9c94-7ae74ff6d912";
+    const THIRD_AGENT_ID: &str = "058df470-73ea-43a4-b36c-e4615cad468e";
+
+    #[test]
+    #[ignore]
+    fn creator_account_owns_room() {
+        let pool = build_pool();
+        let setup = format!(
+            "insert into account (id) values ('{owner_account}'), ('{account}');
+             insert into agent (id, account_id)
+                 values ('{owner}', '{owner_account}'), ('{agent}', '{account}');",
+            owner = OWNER_ID,
+            owner_account = OWNER_ACCOUNT_ID,
+            agent = AGENT_ID,
+            account = ACCOUNT_ID,
+        );
+        execute(&pool, &setup);
+
+        let owner = subject(OWNER_ID, OWNER_ACCOUNT_ID);
+        let (resp, _) = call(&pool, owner.clone(), "room.create", "{}");
+        assert_eq!(resp["result"]["data"]["owner_account_id"], OWNER_ACCOUNT_ID);
+        let room_id = resp["result"]["id"].as_str().unwrap().to_owned();
+
+        // Being the first one in the room doesn't make an agent its owner.
+        let join = |agent_id: &str| {
+            format!(
+                r#"{{"id":"{}","room_id":"{}","data":{{"label":"a","role":"owner"}}}}"#,
+                agent_id, room_id
+            )
+        };
+        let agent = subject(AGENT_ID, ACCOUNT_ID);
+        let (resp, _) = call(&pool, agent, "agent.join_room", &join(AGENT_ID));
+        assert_eq!(resp["error"]["code"], 403);
+
+        let (resp, _) = call(&pool, owner, "agent.join_room", &join(OWNER_ID));
+        assert_eq!(resp["result"]["data"]["role"], "owner");
+    }
+
+    #[test]
+    #[ignore]
+    fn queue_joiners_of_empty_gated_room() {
+        let pool = build_pool();
+        let setup = format!(
+            "insert into account (id) values ('{owner_account}'), ('{account}');
+             insert into agent (id, account_id) values ('{agent}', '{account}');
+             insert into room (id, lobby, owner_account_id)
+                 values ('{room}', true, '{owner_account}');",
+            owner_account = OWNER_ACCOUNT_ID,
+            agent = AGENT_ID,
+            account = ACCOUNT_ID,
+            room = ROOM_ID,
+        );
+        execute(&pool, &setup);
+
+        let agent = subject(AGENT_ID, ACCOUNT_ID);
+        let params = format!(
+            r#"{{"id":"{}","room_id":"{}","data":{{"label":"a","role":"owner"}}}}"#,
+            AGENT_ID, ROOM_ID
+        );
+        let (resp, _) = call(&pool, agent, "agent.join_room", &params);
+        assert_eq!(resp["result"]["data"]["status"], "pending");
+    }
+
+    #[test]
+    #[ignore]
+    fn ban_survives_agent_delete() {
+        let pool = build_pool();
+        let setup = format!(
+            "insert into account (id) values ('{owner_account}'), ('{account}');
+             insert into agent (id, account_id)
+                 values ('{owner}', '{owner_account}'), ('{agent}', '{account}');
+             insert into room (id) values ('{room}');
+             insert into room_agent (agent_id, room_id, label, role)
+                 values ('{owner}', '{room}', 'a', 'owner'),
+                     ('{agent}', '{room}', 'b', 'speaker');",
+            owner = OWNER_ID,
+            owner_account = OWNER_ACCOUNT_ID,
+            agent = AGENT_ID,
+            account = ACCOUNT_ID,
+            room = ROOM_ID,
+        );
+        execute(&pool, &setup);
+
+        let owner = subject(OWNER_ID, OWNER_ACCOUNT_ID);
+        let params = format!(r#"{{"room_id":"{}","data":{{"agent_id":"{}"}}}}"#, ROOM_ID, AGENT_ID);
+        let (resp, _) = call(&pool, owner, "room.ban", &params);
+        assert_eq!(resp["result"]["data"]["agent_id"], AGENT_ID);
+
+        // The agent goes offline and comes back.
+        let agent = subject(AGENT_ID, ACCOUNT_ID);
+        let params = format!(r#"{{"id":"{}"}}"#, AGENT_ID);
+        let (resp, _) = call(&pool, agent.clone(), "agent.delete", &params);
+        assert_eq!(resp["result"]["id"], AGENT_ID);
+        let (resp, _) = call(&pool, agent.clone(), "agent.create", &params);
+        assert_eq!(resp["result"]["id"], AGENT_ID);
+
+        let params = format!(
+            r#"{{"id":"{}","room_id":"{}","data":{{"label":"a"}}}}"#,
+            AGENT_ID, ROOM_ID
+        );
+        let (resp, _) = call(&pool, agent, "agent.join_room", &params);
+        assert_eq!(resp["error"]["code"], 403);
+    }
+
+    #[test]
+    #[ignore]
+    fn ban_owner_account_away() {
+        let pool = build_pool();
+        let setup = format!(
+            "insert into account (id) values ('{owner_account}'), ('{account}');
+             insert into agent (id, account_id)
+                 values ('{owner}', '{owner_account}'), ('{agent}', '{account}');
+             insert into room (id, owner_account_id) values ('{room}', '{owner_account}');
+             insert into room_agent (agent_id, room_id, label, role)
+                 values ('{agent}', '{room}', 'a', 'moderator');",
+            owner = OWNER_ID,
+            owner_account = OWNER_ACCOUNT_ID,
+            agent = AGENT_ID,
+            account = ACCOUNT_ID,
+            room = ROOM_ID,
+        );
+        execute(&pool, &setup);
+
+        let moderator = subject(AGENT_ID, ACCOUNT_ID);
+        let params = format!(r#"{{"room_id":"{}","data":{{"agent_id":"{}"}}}}"#, ROOM_ID, OWNER_ID);
+        let (resp, _) = call(&pool, moderator, "room.ban", &params);
+        assert_eq!(resp["error"]["code"], 403);
+    }
+
+    #[test]
+    #[ignore]
+    fn delete_only_own_agents_and_rooms() {
+        let pool = build_pool();
+        let setup = format!(
+            "insert into account (id) values ('{owner_account}'), ('{account}');
+             insert into agent (id, account_id)
+                 values ('{owner}', '{owner_account}'), ('{agent}', '{account}');
+             insert into room (id, owner_account_id) values ('{room}', '{owner_account}');
+             insert into room_agent (agent_id, room_id, label, role)
+                 values ('{agent}', '{room}', 'a', 'moderator');",
+            owner = OWNER_ID,
+            owner_account = OWNER_ACCOUNT_ID,
+            agent = AGENT_ID,
+            account = ACCOUNT_ID,
+            room = ROOM_ID,
+        );
+        execute(&pool, &setup);
+
+        let moderator = subject(AGENT_ID, ACCOUNT_ID);
+        let params = format!(r#"{{"id":"{}"}}"#, OWNER_ID);
+        let (resp, _) = call(&pool, moderator.clone(), "agent.delete", &params);
+        assert_eq!(resp["error"]["code"], 403);
+
+        let params = format!(r#"{{"room_id":"{}"}}"#, ROOM_ID);
+        let (resp, _) = call(&pool, moderator, "room.delete", &params);
+        assert_eq!(resp["error"]["code"], 403);
+
+        let owner = subject(OWNER_ID, OWNER_ACCOUNT_ID);
+        let (resp, _) = call(&pool, owner, "room.delete", &params);
+        assert_eq!(resp["result"]["id"], ROOM_ID);
+    }
+
+    #[test]
+    #[ignore]
+    fn ban_every_agent_of_account() {
+        let pool = build_pool();
+        let setup = format!(
+            "insert into account (id) values ('{owner_account}'), ('{account}');
+             insert into agent (id, account_id) values ('{owner}', '{owner_account}'),
+                 ('{agent}', '{account}'), ('{second}', '{account}'), ('{third}', '{account}');
+             insert into room (id, lobby, owner_account_id)
+                 values ('{room}', true, '{owner_account}');
+             insert into room_agent (agent_id, room_id, label, role)
+                 values ('{owner}', '{room}', 'a', 'owner'),
+                     ('{agent}', '{room}', 'b', 'speaker'),
+                     ('{second}', '{room}', 'c', 'listener');
+             insert into lobby_request (room_id, agent_id, label, role)
+                 values ('{room}', '{third}', 'd', 'speaker');",
+            owner = OWNER_ID,
+            owner_account = OWNER_ACCOUNT_ID,
+            agent = AGENT_ID,
+            second = SECOND_AGENT_ID,
+            third = THIRD_AGENT_ID,
+            account = ACCOUNT_ID,
+            room = ROOM_ID,
+        );
+        execute(&pool, &setup);
+
+        let owner = subject(OWNER_ID, OWNER_ACCOUNT_ID);
+        let params = format!(r#"{{"room_id":"{}","data":{{"agent_id":"{}"}}}}"#, ROOM_ID, AGENT_ID);
+        let (resp, _) = call(&pool, owner.clone(), "room.ban", &params);
+        assert_eq!(resp["result"]["data"]["agent_id"], AGENT_ID);
+
+        let params = format!(r#"{{"fq":"room_id:{}"}}"#, ROOM_ID);
+        let (resp, _) = call(&pool, owner.clone(), "agent.list", &params);
+        let members = resp["result"].as_array().unwrap();
+        assert_eq!(members.len(), 1);
+        assert_eq!(members[0]["id"], OWNER_ID);
+
+        let params = format!(r#"{{"room_id":"{}"}}"#, ROOM_ID);
+        let (resp, _) = call(&pool, owner, "lobby.list", &params);
+        assert!(resp["result"].as_array().unwrap().is_empty());
+    }
 }
@@ -1,24 +1,167 @@
-use messages::subscription::{CreateRequest, CreateResponse};
-use rpc::error::Result;
-use topic::AppTopic;
+use diesel;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+
+use std::str::FromStr;
+
+use messages::query_parameters::{Expr, Filter};
+use messages::subscription::{CreateRequest, CreateResponse, DeleteRequest, DeleteResponse,
+                             ListRequest, ListResponse};
+use models;
+use rpc;
+use rpc::error::{Error, Result};
+use schema::{room, room_agent, subscription};
 
 build_rpc_trait! {
     pub trait Rpc {
-        #[rpc(name = "subscription.create")]
-        fn create(&self, CreateRequest) -> Result<CreateResponse>;
+        type Metadata;
+
+        #[rpc(meta, name = "subscription.create")]
+        fn create(&self, Self::Metadata, CreateRequest) -> Result<CreateResponse>;
+
+        #[rpc(meta, name = "subscription.delete")]
+        fn delete(&self, Self::Metadata, DeleteRequest) -> Result<DeleteResponse>;
+
+        #[rpc(meta, name = "subscription.list")]
+        fn list(&self, Self::Metadata, ListRequest) -> Result<ListResponse>;
     }
 }
 
 pub struct RpcImpl;
 
 impl Rpc for RpcImpl {
-    fn create(&self, req: CreateRequest) -> Result<CreateResponse> {
-        let topic = AppTopic {
+    type Metadata = rpc::Meta;
+
+    fn create(&self, meta: rpc::Meta, req: CreateRequest) -> Result<CreateResponse> {
+        // Subscriptions grant access to topics, so agents manage only their own.
+        if req.agent_id != meta.subject.agent_id {
+            return Err(Error::Forbidden);
+        }
+
+        let conn = establish_connection!(meta.db_pool.unwrap());
+
+        let room: models::Room = room::table.find(req.room_id).first(conn)?;
+        if !room.is_served_in(&meta.service) {
+            return Err(Error::Forbidden);
+        }
+
+        let is_member = diesel::select(diesel::dsl::exists(
+            room_agent::table.find((req.agent_id, req.room_id)),
+        )).get_result::<bool>(conn)?;
+
+        if !is_member {
+            return Err(Error::Forbidden);
+        }
+
+        let changeset = models::NewSubscription {
+            agent_id: req.agent_id,
             room_id: req.room_id,
             resource: req.data.resource,
         };
 
-        let resp = CreateResponse::new(topic);
-        Ok(resp)
+        let subscription: models::Subscription = diesel::insert_into(subscription::table)
+            .values(&changeset)
+            .get_result(conn)?;
+
+        Ok(CreateResponse::new(&subscription, &meta.service))
+    }
+
+    fn delete(&self, meta: rpc::Meta, req: DeleteRequest) -> Result<DeleteResponse> {
+        if req.agent_id != meta.subject.agent_id {
+            return Err(Error::Forbidden);
+        }
+
+        let conn = establish_connection!(meta.db_pool.unwrap());
+
+        let target = subscription::table.find((req.agent_id, req.room_id, req.data.resource));
+        let subscription = diesel::delete(target).get_result(conn)?;
+
+        Ok(DeleteResponse::new(&subscription, &meta.service))
+    }
+
+    fn list(&self, meta: rpc::Meta, req: ListRequest) -> Result<ListResponse> {
+        let conn = establish_connection!(meta.db_pool.unwrap());
+
+        let mut query = subscription::table
+            .filter(subscription::agent_id.eq(meta.subject.agent_id))
+            .into_boxed();
+
+        if let Some(fq) = req.fq {
+            let expr = Expr::from_str(&fq)?;
+            query = filter_query(query, expr)?;
+        }
+
+        let subscriptions = query.load::<models::Subscription>(conn)?;
+
+        Ok(ListResponse::new(&subscriptions, &meta.service))
+    }
+}
+
+fn filter_query<'a>(
+    query: subscription::BoxedQuery<'a, Pg>,
+    expr: Expr,
+) -> Result<subscription::BoxedQuery<'a, Pg>> {
+    match expr {
+        Expr::Value(Filter::RoomId(id)) => Ok(query.filter(subscription::room_id.eq(id))),
+        Expr::Value(Filter::AgentId(id)) => Ok(query.filter(subscription::agent_id.eq(id))),
+        Expr::And(lhs, rhs) => {
+            let query = filter_query(query, *lhs)?;
+            filter_query(query, *rhs)
+        }
+        _ => Err(Error::BadRequest),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rpc::tests::{build_pool, call, execute, subject};
+
+    const ROOM_ID: &str = "050b7c6f-795c-4cb4-aeea-5ee3f9083de2";
+    const AGENT_ID: &str = "1154b35c-e5b0-4a42-8ab2-d4967ce38c9e";
+    const OTHER_AGENT_ID: &str = "e19c94cf-53eb-4048-9c94-7ae74ff6d912";
+    const ACCOUNT_ID: &str = "31517b3d-5a14-4a14-a6c5-2ba63f7375d3";
+
+    #[test]
+    #[ignore]
+    fn manage_own_subscriptions() {
+        let pool = build_pool();
+        let setup = format!(
+            "insert into account (id) values ('{account}');
+             insert into agent (id, account_id) values ('{agent}', '{account}'), ('{other}', null);
+             insert into room (id) values ('{room}');
+             insert into room_agent (agent_id, room_id, label, role)
+                 values ('{agent}', '{room}', 'a', 'moderator'),
+                     ('{other}', '{room}', 'b', 'speaker');
+             insert into subscription (agent_id, room_id, resource)
+                 values ('{other}', '{room}', 'agents');",
+            agent = AGENT_ID,
+            other = OTHER_AGENT_ID,
+            account = ACCOUNT_ID,
+            room = ROOM_ID,
+        );
+        execute(&pool, &setup);
+
+        let params = |agent_id: &str| {
+            format!(
+                r#"{{"room_id":"{}","agent_id":"{}","data":{{"resource":"tracks"}}}}"#,
+                ROOM_ID, agent_id
+            )
+        };
+        let agent = subject(AGENT_ID, ACCOUNT_ID);
+
+        let (resp, _) = call(&pool, agent.clone(), "subscription.create", &params(OTHER_AGENT_ID));
+        assert_eq!(resp["error"]["code"], 403);
+
+        let other_subscription = params(OTHER_AGENT_ID).replace("tracks", "agents");
+        let (resp, _) = call(&pool, agent.clone(), "subscription.delete", &other_subscription);
+        assert_eq!(resp["error"]["code"], 403);
+
+        let (resp, _) = call(&pool, agent.clone(), "subscription.create", &params(AGENT_ID));
+        assert_eq!(resp["result"]["agent_id"], AGENT_ID);
+
+        let (resp, _) = call(&pool, agent, "subscription.list", "{}");
+        let subscriptions = resp["result"].as_array().unwrap();
+        assert_eq!(subscriptions.len(), 1);
+        assert_eq!(subscriptions[0]["agent_id"], AGENT_ID);
     }
 }
@@ -0,0 +1,88 @@
+use diesel::prelude::*;
+use diesel::PgConnection;
+use serde_json::{self, Value};
+use uuid::Uuid;
+
+use std::sync::mpsc::Sender;
+use std::sync::Mutex;
+
+use messages::webrtc::{ConnectNotification, DisconnectNotification};
+use messages::Notification;
+use rpc::error::Result;
+use schema::{room, room_agent, track};
+use topology::{self, Planner};
+
+/// Plans peer connections of the room anew once its members or their tracks
+/// have changed, and tells members which connections to make and to drop.
+/// Rooms without a topology policy are left to agents themselves.
+///
+/// The change of the room has been made by then, so errors are only logged.
+pub fn update(
+    conn: &PgConnection,
+    notification_tx: &Sender<Notification>,
+    planner: &Mutex<Planner>,
+    room_id: Uuid,
+) {
+    if let Err(e) = replan(conn, notification_tx, planner, room_id) {
+        println!("Topology error in room {}: {}", room_id, e);
+    }
+}
+
+fn replan(
+    conn: &PgConnection,
+    notification_tx: &Sender<Notification>,
+    planner: &Mutex<Planner>,
+    room_id: Uuid,
+) -> Result<()> {
+    let policy = room::table
+        .find(room_id)
+        .select(room::topology)
+        .first::<Option<Value>>(conn)
+        .optional()?
+        .and_then(|policy| policy);
+
+    let policy = match policy.map(serde_json::from_value::<topology::Policy>) {
+        Some(Ok(policy)) => policy,
+        Some(Err(e)) => {
+            println!("Invalid topology policy of room {}: {}", room_id, e);
+            return Ok(());
+        }
+        None => return Ok(()),
+    };
+
+    let members = room_agent::table
+        .filter(room_agent::room_id.eq(room_id))
+        .select(room_agent::agent_id)
+        .order(room_agent::agent_id)
+        .load::<Uuid>(conn)?;
+
+    // Tracks without a room are shared with every room of the owner.
+    let presenters = track::table
+        .filter(track::owner_id.eq_any(members.clone()))
+        .filter(track::room_id.eq(room_id).or(track::room_id.is_null()))
+        .select(track::owner_id)
+        .distinct()
+        .load::<Uuid>(conn)?;
+
+    let graph = topology::plan(&policy, &members, &presenters);
+    let changes = planner.lock().unwrap().update(room_id, graph);
+
+    for (a, b) in changes.connect {
+        for &(agent_id, peer_id) in &[(a, b), (b, a)] {
+            let method = ConnectNotification::new(room_id, agent_id, peer_id).into_method(agent_id);
+            notification_tx.send(method.into()).unwrap();
+        }
+    }
+
+    // Agents which have left the room have nothing to drop.
+    for (a, b) in changes.disconnect {
+        for &(agent_id, peer_id) in &[(a, b), (b, a)] {
+            if members.contains(&agent_id) {
+                let method = DisconnectNotification::new(room_id, peer_id).into_method(agent_id);
+                notification_tx.send(method.into()).unwrap();
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -6,22 +6,33 @@ use std::str::FromStr;
 
 use messages::query_parameters;
 use messages::track::{CreateEvent, CreateRequest, CreateResponse, DeleteEvent, DeleteRequest,
-                      DeleteResponse, ListRequest, ListResponse};
+                      DeleteResponse, ForceMuteRequest, ForceMuteResponse, ListRequest,
+                      ListResponse, UpdateEvent, UpdateRequest, UpdateResponse};
 use messages::EventKind;
 use models;
 use rpc;
 use rpc::error::{Error, Result};
+use rpc::permission::{self, Action};
+use rpc::topology;
 use schema::{agent, room_agent, track};
 
 macro_rules! and_filter {
     ($query:ident, $filter:ident) => {
         match $filter {
             query_parameters::Filter::RoomId(id) => {
-                $query = $query.filter(room_agent::room_id.eq(id));
+                $query = $query.filter(
+                    track::room_id
+                        .eq(id)
+                        .or(track::room_id.is_null().and(room_agent::room_id.eq(id))),
+                );
             }
             query_parameters::Filter::OwnerId(id) => {
                 $query = $query.filter(track::owner_id.eq(id));
             }
+            query_parameters::Filter::Kind(kind) => {
+                $query = $query.filter(track::kind.eq(kind));
+            }
+            _ => Err(Error::BadRequest)?,
         }
     };
 }
@@ -33,6 +44,12 @@ build_rpc_trait! {
         #[rpc(meta, name = "track.create")]
         fn create(&self, Self::Metadata, CreateRequest) -> Result<CreateResponse>;
 
+        #[rpc(meta, name = "track.update")]
+        fn update(&self, Self::Metadata, UpdateRequest) -> Result<UpdateResponse>;
+
+        #[rpc(meta, name = "track.force_mute")]
+        fn force_mute(&self, Self::Metadata, ForceMuteRequest) -> Result<ForceMuteResponse>;
+
         #[rpc(meta, name = "track.delete")]
         fn delete(&self, Self::Metadata, DeleteRequest) -> Result<DeleteResponse>;
 
@@ -47,9 +64,32 @@ impl Rpc for RpcImpl {
     type Metadata = rpc::Meta;
 
     fn create(&self, meta: rpc::Meta, req: CreateRequest) -> Result<CreateResponse> {
+        req.data.validate().map_err(Error::InvalidValue)?;
+
         let conn = establish_connection!(meta.db_pool.unwrap());
 
-        let agent_id = req.data.owner_id;
+        // Agents create tracks of their own only.
+        let owner_id = req.data.owner_id;
+        if owner_id != meta.subject.agent_id {
+            return Err(Error::Forbidden);
+        }
+
+        let action = Action::CreateTrack(req.data.kind);
+        match req.data.room_id {
+            Some(room_id) => permission::authorize_member(conn, owner_id, room_id, action)?,
+            // The track goes to every room of the owner, so does the check.
+            None => {
+                let roles = room_agent::table
+                    .filter(room_agent::agent_id.eq(owner_id))
+                    .select(room_agent::role)
+                    .load::<models::Role>(conn)?;
+
+                for role in roles {
+                    permission::authorize(role, action)?;
+                }
+            }
+        }
+
         let changeset = models::NewTrack::from(req);
 
         let track: models::Track = diesel::insert_into(track::table)
@@ -59,30 +99,122 @@ impl Rpc for RpcImpl {
         let resp = CreateResponse::new(&track);
 
         let notification_tx = meta.notification_tx.unwrap();
-        let room_ids = get_agent_room_ids(conn, agent_id)?;
+        let room_ids = get_track_room_ids(conn, &track)?;
         for room_id in room_ids {
             let event = CreateEvent::new(room_id, resp.clone());
             let event_kind = EventKind::from(event);
             notification_tx.send(event_kind.into()).unwrap();
+
+            // The owner may have become a presenter.
+            topology::update(conn, &notification_tx, &meta.planner, room_id);
         }
 
         Ok(resp)
     }
 
+    fn update(&self, meta: rpc::Meta, req: UpdateRequest) -> Result<UpdateResponse> {
+        let id = req.id;
+        let data = req.data;
+        if data.is_empty() {
+            Err(Error::BadRequest)?
+        }
+        data.validate().map_err(Error::InvalidValue)?;
+
+        let conn = establish_connection!(meta.db_pool.unwrap());
+        let subject_id = meta.subject.agent_id;
+
+        let track = conn.transaction::<_, Error, _>(|| {
+            let target = track::table.find(id);
+            let track: models::Track = target.for_update().get_result(conn)?;
+            authorize_track(conn, subject_id, &track)?;
+
+            let metadata = data.patch_metadata(track.metadata).map_err(Error::InvalidValue)?;
+
+            let changeset = models::TrackChangeset {
+                metadata,
+                enabled: data.enabled,
+                muted: data.muted,
+                label: data.label,
+            };
+
+            diesel::update(target)
+                .set(&changeset)
+                .get_result(conn)
+                .map_err(Error::from)
+        })?;
+
+        let resp = UpdateResponse::new(&track);
+
+        let notification_tx = meta.notification_tx.unwrap();
+        let room_ids = get_track_room_ids(conn, &track)?;
+        for room_id in room_ids {
+            let event = UpdateEvent::new(room_id, resp.clone());
+            let event_kind = EventKind::from(event);
+            notification_tx.send(event_kind.into()).unwrap();
+        }
+
+        Ok(resp)
+    }
+
+    fn force_mute(&self, meta: rpc::Meta, req: ForceMuteRequest) -> Result<ForceMuteResponse> {
+        let conn = establish_connection!(meta.db_pool.unwrap());
+        let subject_id = meta.subject.agent_id;
+
+        let track: models::Track = track::table.find(req.id).first(conn)?;
+
+        // Unlike track.update, the owner itself isn't enough here.
+        match track.room_id {
+            Some(room_id) => {
+                permission::authorize_member(conn, subject_id, room_id, Action::ManageTrack)?
+            }
+            None => Err(Error::Forbidden)?,
+        }
+
+        let track: models::Track = diesel::update(&track)
+            .set(track::muted.eq(true))
+            .get_result(conn)?;
+
+        let resp = ForceMuteResponse::new(&track);
+
+        let notification_tx = meta.notification_tx.unwrap();
+        let room_ids = get_track_room_ids(conn, &track)?;
+        for room_id in room_ids {
+            let event = UpdateEvent::new(room_id, resp.clone());
+            let event_kind = EventKind::from(event);
+            notification_tx.send(event_kind.into()).unwrap();
+        }
+
+        let method = req.to_method(track.owner_id, subject_id);
+        notification_tx.send(method.into()).unwrap();
+
+        Ok(resp)
+    }
+
     fn delete(&self, meta: rpc::Meta, req: DeleteRequest) -> Result<DeleteResponse> {
         let conn = establish_connection!(meta.db_pool.unwrap());
 
-        let target = track::table.find(req.id);
-        let track = diesel::delete(target).get_result(conn)?;
+        let subject_id = meta.subject.agent_id;
+
+        let track = conn.transaction::<_, Error, _>(|| {
+            let target = track::table.find(req.id);
+            let track: models::Track = target.for_update().get_result(conn)?;
+            authorize_track(conn, subject_id, &track)?;
+
+            diesel::delete(target)
+                .get_result(conn)
+                .map_err(Error::from)
+        })?;
 
         let resp = DeleteResponse::new(&track);
 
         let notification_tx = meta.notification_tx.unwrap();
-        let room_ids = get_agent_room_ids(conn, track.owner_id)?;
+        let room_ids = get_track_room_ids(conn, &track)?;
         for room_id in room_ids {
             let event = DeleteEvent::new(room_id, resp.clone());
             let event_kind = EventKind::from(event);
             notification_tx.send(event_kind.into()).unwrap();
+
+            topology::update(conn, &notification_tx, &meta.planner, room_id);
         }
 
         Ok(resp)
@@ -129,6 +261,27 @@ impl Rpc for RpcImpl {
     }
 }
 
+/// Tracks are managed by their owners and by moderators of the track's room.
+fn authorize_track(conn: &PgConnection, agent_id: Uuid, track: &models::Track) -> Result<()> {
+    if track.owner_id == agent_id {
+        return Ok(());
+    }
+
+    match track.room_id {
+        Some(room_id) => permission::authorize_member(conn, agent_id, room_id, Action::ManageTrack),
+        None => Err(Error::Forbidden),
+    }
+}
+
+/// Rooms the track is shared with: its own room or, for a track without one,
+/// every room of the owner.
+fn get_track_room_ids(conn: &PgConnection, track: &models::Track) -> Result<Vec<Uuid>> {
+    match track.room_id {
+        Some(room_id) => Ok(vec![room_id]),
+        None => get_agent_room_ids(conn, track.owner_id),
+    }
+}
+
 fn get_agent_room_ids(conn: &PgConnection, agent_id: Uuid) -> Result<Vec<Uuid>> {
     room_agent::table
         .select(room_agent::room_id)
@@ -136,3 +289,47 @@ fn get_agent_room_ids(conn: &PgConnection, agent_id: Uuid) -> Result<Vec<Uuid>>
         .get_results(conn)
         .map_err(Error::from)
 }
+
+#[cfg(test)]
+mod tests {
+    use rpc::tests::{build_pool, call, execute, subject};
+
+    const ROOM_ID: &str = "050b7c6f-795c-4cb4-aeea-5ee3f9083de2";
+    const AGENT_ID: &str = "1154b35c-e5b0-4a42-8ab2-d4967ce38c9e";
+    const ACCOUNT_ID: &str = "31517b3d-5a14-4a14-a6c5-2ba63f7375d3";
+
+    #[test]
+    #[ignore]
+    fn create_track_without_room() {
+        let pool = build_pool();
+        let setup = format!(
+            "insert into account (id) values ('{account}');
+             insert into agent (id, account_id) values ('{agent}', '{account}');
+             insert into room (id) values ('{room}');
+             insert into room_agent (agent_id, room_id, label, role)
+                 values ('{agent}', '{room}', 'a', 'listener');",
+            agent = AGENT_ID,
+            account = ACCOUNT_ID,
+            room = ROOM_ID,
+        );
+        execute(&pool, &setup);
+
+        let track = |owner_id: &str, kind: &str| {
+            format!(
+                r#"{{"data":{{"owner_id":"{}","kind":"{}","stream_id":"s"}}}}"#,
+                owner_id, kind
+            )
+        };
+        let agent = subject(AGENT_ID, ACCOUNT_ID);
+
+        // A listener's video would reach its room anyway.
+        let (resp, _) = call(&pool, agent.clone(), "track.create", &track(AGENT_ID, "video"));
+        assert_eq!(resp["error"]["code"], 403);
+
+        let (resp, _) = call(&pool, agent.clone(), "track.create", &track(ROOM_ID, "data"));
+        assert_eq!(resp["error"]["code"], 403);
+
+        let (resp, _) = call(&pool, agent, "track.create", &track(AGENT_ID, "data"));
+        assert_eq!(resp["result"]["data"]["kind"], "data");
+    }
+}
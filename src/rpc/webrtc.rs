@@ -1,7 +1,23 @@
-use messages::webrtc::{AnswerRequest, CandidateRequest, OfferRequest};
-use messages::Method;
+use chrono::{Duration, Utc};
+use diesel;
+use diesel::prelude::*;
+use diesel::PgConnection;
+use serde_json::{self, Value};
+use uuid::Uuid;
+
+use std::sync::mpsc::Sender;
+
+use messages::webrtc::{AnswerRequest, CandidateRequest, EndOfCandidatesRequest, HangupRequest,
+                       IceRestartRequest, IceServersRequest, IceServersResponse, OfferRequest,
+                       RenegotiateRequest, Request, Route, SessionsRequest, SessionsResponse};
+use messages::{EnvelopeSubject, Method, Notification};
+use models::Role;
 use rpc;
-use rpc::error::Result;
+use rpc::error::{Error, Result};
+use rpc::permission;
+use schema::{room, room_agent};
+use sdp::Policy;
+use turn::Credentials;
 
 build_rpc_trait! {
     pub trait Rpc {
@@ -15,6 +31,24 @@ build_rpc_trait! {
 
         #[rpc(meta, name = "webrtc.candidate")]
         fn candidate(&self, Self::Metadata, CandidateRequest) -> Result<Vec<()>>;
+
+        #[rpc(meta, name = "webrtc.renegotiate")]
+        fn renegotiate(&self, Self::Metadata, RenegotiateRequest) -> Result<Vec<()>>;
+
+        #[rpc(meta, name = "webrtc.ice_restart")]
+        fn ice_restart(&self, Self::Metadata, IceRestartRequest) -> Result<Vec<()>>;
+
+        #[rpc(meta, name = "webrtc.hangup")]
+        fn hangup(&self, Self::Metadata, HangupRequest) -> Result<Vec<()>>;
+
+        #[rpc(meta, name = "webrtc.end_of_candidates")]
+        fn end_of_candidates(&self, Self::Metadata, EndOfCandidatesRequest) -> Result<Vec<()>>;
+
+        #[rpc(meta, name = "webrtc.sessions")]
+        fn sessions(&self, Self::Metadata, SessionsRequest) -> Result<SessionsResponse>;
+
+        #[rpc(meta, name = "webrtc.ice_servers")]
+        fn ice_servers(&self, Self::Metadata, IceServersRequest) -> Result<IceServersResponse>;
     }
 }
 
@@ -23,27 +57,228 @@ pub struct RpcImpl;
 impl Rpc for RpcImpl {
     type Metadata = rpc::Meta;
 
-    fn offer(&self, meta: rpc::Meta, req: OfferRequest) -> Result<Vec<()>> {
+    fn offer(&self, meta: rpc::Meta, mut req: OfferRequest) -> Result<Vec<()>> {
+        let conn = establish_connection!(meta.db_pool.unwrap());
+        authorize_route(conn, &meta.subject, &req)?;
+
+        // Malformed descriptions don't reach the other agent.
+        let desc = req.description()?;
+
+        let (room_id, (from, to)) = (req.room_id(), req.route());
+        if let Some((policy, is_listener)) = room_policy(conn, room_id, meta.subject.agent_id)? {
+            req.apply_policy(&desc, &policy, is_listener)?;
+        }
+
+        let candidates = meta.sessions.lock().unwrap().offer(room_id, from, to)?;
+
         let method = Method::from(req);
         let notification_tx = meta.notification_tx.unwrap();
         notification_tx.send(method.into()).unwrap();
+        relay_candidates(&notification_tx, room_id, from, to, candidates);
 
         Ok(vec![])
     }
 
-    fn answer(&self, meta: rpc::Meta, req: AnswerRequest) -> Result<Vec<()>> {
+    fn answer(&self, meta: rpc::Meta, mut req: AnswerRequest) -> Result<Vec<()>> {
+        let conn = establish_connection!(meta.db_pool.unwrap());
+        authorize_route(conn, &meta.subject, &req)?;
+        let desc = req.description()?;
+
+        let (room_id, (from, to)) = (req.room_id(), req.route());
+        if let Some((policy, is_listener)) = room_policy(conn, room_id, meta.subject.agent_id)? {
+            req.apply_policy(&desc, &policy, is_listener)?;
+        }
+
+        let candidates = meta.sessions.lock().unwrap().answer(room_id, from, to)?;
+
         let method = Method::from(req);
         let notification_tx = meta.notification_tx.unwrap();
         notification_tx.send(method.into()).unwrap();
+        relay_candidates(&notification_tx, room_id, from, to, candidates);
 
         Ok(vec![])
     }
 
-    fn candidate(&self, meta: rpc::Meta, req: CandidateRequest) -> Result<Vec<()>> {
-        let method = Method::from(req);
+    fn candidate(&self, meta: rpc::Meta, mut req: CandidateRequest) -> Result<Vec<()>> {
+        let conn = establish_connection!(meta.db_pool.unwrap());
+        authorize_route(conn, &meta.subject, &req)?;
+
+        let (room_id, (from, to)) = (req.room_id(), req.route());
+        if let Some((policy, _)) = room_policy(conn, room_id, meta.subject.agent_id)? {
+            // Filtered candidates are dropped silently, the way a firewall would.
+            if !req.apply_policy(&policy) {
+                return Ok(vec![]);
+            }
+        }
+
+        // Candidates sent before the description they belong to wait for it.
+        let candidate = req.into_candidate();
+        let candidate = meta.sessions
+            .lock()
+            .unwrap()
+            .candidate(room_id, from, to, candidate)?;
+
+        if let Some(candidate) = candidate {
+            let notification_tx = meta.notification_tx.unwrap();
+            relay_candidates(&notification_tx, room_id, from, to, vec![candidate]);
+        }
+
+        Ok(vec![])
+    }
+
+    fn renegotiate(&self, meta: rpc::Meta, req: RenegotiateRequest) -> Result<Vec<()>> {
+        let conn = establish_connection!(meta.db_pool.unwrap());
+        authorize_route(conn, &meta.subject, &req)?;
+
         let notification_tx = meta.notification_tx.unwrap();
-        notification_tx.send(method.into()).unwrap();
+        notification_tx.send(Method::from(req).into()).unwrap();
+
+        Ok(vec![])
+    }
+
+    fn ice_restart(&self, meta: rpc::Meta, req: IceRestartRequest) -> Result<Vec<()>> {
+        let conn = establish_connection!(meta.db_pool.unwrap());
+        authorize_route(conn, &meta.subject, &req)?;
+
+        let (room_id, (from, to)) = (req.room_id(), req.route());
+        meta.sessions.lock().unwrap().restart_ice(room_id, from, to);
+
+        let notification_tx = meta.notification_tx.unwrap();
+        notification_tx.send(Method::from(req).into()).unwrap();
 
         Ok(vec![])
     }
+
+    fn hangup(&self, meta: rpc::Meta, req: HangupRequest) -> Result<Vec<()>> {
+        let conn = establish_connection!(meta.db_pool.unwrap());
+        authorize_route(conn, &meta.subject, &req)?;
+
+        let (room_id, (from, to)) = (req.room_id(), req.route());
+        meta.sessions.lock().unwrap().hangup(room_id, from, to);
+
+        let notification_tx = meta.notification_tx.unwrap();
+        notification_tx.send(Method::from(req).into()).unwrap();
+
+        Ok(vec![])
+    }
+
+    fn end_of_candidates(&self, meta: rpc::Meta, req: EndOfCandidatesRequest) -> Result<Vec<()>> {
+        let conn = establish_connection!(meta.db_pool.unwrap());
+        authorize_route(conn, &meta.subject, &req)?;
+
+        // The end goes after candidates waiting for the description, it waits too
+        // if they do.
+        let (room_id, (from, to)) = (req.room_id(), req.route());
+        let end = meta.sessions
+            .lock()
+            .unwrap()
+            .candidate(room_id, from, to, Value::Null)?;
+
+        if end.is_some() {
+            let notification_tx = meta.notification_tx.unwrap();
+            notification_tx.send(Method::from(req).into()).unwrap();
+        }
+
+        Ok(vec![])
+    }
+
+    fn sessions(&self, meta: rpc::Meta, req: SessionsRequest) -> Result<SessionsResponse> {
+        let conn = establish_connection!(meta.db_pool.unwrap());
+
+        let role = permission::member_role(conn, meta.subject.agent_id, req.room_id)?;
+        role.ok_or(Error::Forbidden)?;
+
+        let sessions = meta.sessions.lock().unwrap();
+
+        Ok(SessionsResponse::new(&sessions.list(req.room_id)))
+    }
+
+    fn ice_servers(&self, meta: rpc::Meta, req: IceServersRequest) -> Result<IceServersResponse> {
+        let conn = establish_connection!(meta.db_pool.unwrap());
+
+        // TURN servers relay traffic at our expense, so only members get credentials.
+        let role = permission::member_role(conn, meta.subject.agent_id, req.room_id)?;
+        role.ok_or(Error::Forbidden)?;
+
+        let config = &meta.config;
+        let turn = if config.turn_urls.is_empty() {
+            None
+        } else {
+            let ttl = Duration::seconds(config.turn_ttl.as_secs() as i64);
+            let expires_at = Utc::now() + ttl;
+            let user = meta.subject.agent_id.to_string();
+            let credentials =
+                Credentials::issue(&config.turn_secret, &user, expires_at.timestamp())?;
+
+            Some((config.turn_urls.as_slice(), credentials, expires_at))
+        };
+
+        Ok(IceServersResponse::new(req.room_id, &config.stun_urls, turn))
+    }
+}
+
+fn relay_candidates(
+    notification_tx: &Sender<Notification>,
+    room_id: Uuid,
+    from: Uuid,
+    to: Uuid,
+    candidates: Vec<Value>,
+) {
+    for candidate in candidates {
+        // The end of candidates waits among them as a `null` one.
+        let method = if candidate.is_null() {
+            Method::from(EndOfCandidatesRequest::new(room_id, from, to))
+        } else {
+            Method::from(CandidateRequest::new(room_id, from, to, candidate))
+        };
+        notification_tx.send(method.into()).unwrap();
+    }
+}
+
+/// SDP policy of the room unless it's empty, and whether the agent is a listener.
+/// The agent is the caller, not the sender named in the request.
+fn room_policy(
+    conn: &PgConnection,
+    room_id: Uuid,
+    agent_id: Uuid,
+) -> Result<Option<(Policy, bool)>> {
+    let policy = room::table
+        .find(room_id)
+        .select(room::sdp_policy)
+        .first::<Value>(conn)?;
+
+    // Policies are validated when rooms are created.
+    let policy = serde_json::from_value::<Policy>(policy).unwrap_or_default();
+    if policy.is_empty() {
+        return Ok(None);
+    }
+
+    let role = permission::member_role(conn, agent_id, room_id)?;
+    Ok(Some((policy, role == Some(Role::Listener))))
+}
+
+/// Signaling goes only between members of the room, so agents waiting
+/// in the lobby get no offers. Agents signal only on their own behalf.
+fn authorize_route<T: Route>(
+    conn: &PgConnection,
+    subject: &EnvelopeSubject,
+    req: &Request<T>,
+) -> Result<()> {
+    let (from, to) = req.route();
+    if from != subject.agent_id {
+        return Err(Error::Forbidden);
+    }
+
+    let members = room_agent::table
+        .filter(room_agent::room_id.eq(req.room_id()))
+        .filter(room_agent::agent_id.eq_any(vec![from, to]))
+        .count()
+        .get_result::<i64>(conn)?;
+
+    let expected = if from == to { 1 } else { 2 };
+    if members == expected {
+        Ok(())
+    } else {
+        Err(Error::Forbidden)
+    }
 }
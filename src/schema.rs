@@ -1,6 +1,46 @@
+table! {
+    account (id) {
+        id -> Uuid,
+        created_at -> Timestamp,
+    }
+}
+
 table! {
     agent (id) {
         id -> Uuid,
+        account_id -> Nullable<Uuid>,
+        display_name -> Nullable<Text>,
+        avatar_url -> Nullable<Text>,
+        user_agent -> Nullable<Text>,
+        data -> Jsonb,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        service -> Nullable<Text>,
+    }
+}
+
+table! {
+    invite (id) {
+        id -> Uuid,
+        room_id -> Uuid,
+        label -> Nullable<Text>,
+        role -> Text,
+        single_use -> Bool,
+        uses -> Int4,
+        expires_at -> Timestamp,
+        created_by -> Nullable<Uuid>,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    lobby_request (room_id, agent_id) {
+        room_id -> Uuid,
+        agent_id -> Uuid,
+        label -> Text,
+        role -> Text,
+        data -> Jsonb,
+        created_at -> Timestamp,
     }
 }
 
@@ -8,6 +48,11 @@ table! {
     room (id) {
         id -> Uuid,
         created_at -> Timestamp,
+        lobby -> Bool,
+        sdp_policy -> Jsonb,
+        topology -> Nullable<Jsonb>,
+        owner_account_id -> Nullable<Uuid>,
+        service -> Nullable<Text>,
     }
 }
 
@@ -17,6 +62,28 @@ table! {
         room_id -> Uuid,
         label -> Text,
         created_at -> Timestamp,
+        data -> Jsonb,
+        version -> Int4,
+        role -> Text,
+    }
+}
+
+table! {
+    room_ban (room_id, agent_id) {
+        room_id -> Uuid,
+        agent_id -> Uuid,
+        account_id -> Nullable<Uuid>,
+        reason -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    subscription (agent_id, room_id, resource) {
+        agent_id -> Uuid,
+        room_id -> Uuid,
+        resource -> Text,
+        created_at -> Timestamp,
     }
 }
 
@@ -25,11 +92,38 @@ table! {
         id -> Uuid,
         owner_id -> Uuid,
         metadata -> Jsonb,
+        enabled -> Bool,
+        muted -> Bool,
+        kind -> Text,
+        label -> Text,
+        stream_id -> Text,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        room_id -> Nullable<Uuid>,
     }
 }
 
+joinable!(agent -> account (account_id));
+joinable!(invite -> agent (created_by));
+joinable!(invite -> room (room_id));
+joinable!(lobby_request -> agent (agent_id));
+joinable!(lobby_request -> room (room_id));
+joinable!(room -> account (owner_account_id));
 joinable!(room_agent -> agent (agent_id));
 joinable!(room_agent -> room (room_id));
+joinable!(room_ban -> room (room_id));
+joinable!(subscription -> agent (agent_id));
+joinable!(subscription -> room (room_id));
 joinable!(track -> agent (owner_id));
 
-allow_tables_to_appear_in_same_query!(agent, room, room_agent, track,);
+allow_tables_to_appear_in_same_query!(
+    account,
+    agent,
+    invite,
+    lobby_request,
+    room,
+    room_agent,
+    room_ban,
+    subscription,
+    track,
+);
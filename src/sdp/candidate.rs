@@ -0,0 +1,149 @@
+use std::net::{IpAddr, SocketAddr};
+
+/// Type of an ICE candidate. Host candidates with mDNS hostnames are told apart,
+/// since they don't reveal the address of the agent.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CandidateType {
+    Host,
+    Mdns,
+    Srflx,
+    Prflx,
+    Relay,
+}
+
+const REDACTED: &str = "x.x.x.x";
+
+/// Parses the type of a candidate attribute, with or without the `candidate:` prefix:
+///
+/// `candidate:<foundation> <component> <transport> <priority> <address> <port> typ <type> ...`
+pub fn candidate_type(candidate: &str) -> Option<CandidateType> {
+    let fields: Vec<&str> = candidate.split_whitespace().collect();
+    if fields.len() < 8 || fields[6] != "typ" {
+        return None;
+    }
+
+    let kind = match fields[7] {
+        "host" if fields[4].ends_with(".local") => CandidateType::Mdns,
+        "host" => CandidateType::Host,
+        "srflx" => CandidateType::Srflx,
+        "prflx" => CandidateType::Prflx,
+        "relay" => CandidateType::Relay,
+        _ => return None,
+    };
+
+    Some(kind)
+}
+
+/// Replaces the related address and port of a candidate, which is the address
+/// of the agent behind a reflexive or relayed one.
+pub fn hide_related_address(candidate: &str) -> String {
+    let mut fields: Vec<&str> = candidate.split_whitespace().collect();
+    for i in 8..fields.len().saturating_sub(1) {
+        match fields[i] {
+            "raddr" => fields[i + 1] = "0.0.0.0",
+            "rport" => fields[i + 1] = "0",
+            _ => (),
+        }
+    }
+
+    fields.join(" ")
+}
+
+fn is_address_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '.' || c == ':' || c == '-'
+}
+
+fn is_address(word: &str) -> bool {
+    word.ends_with(".local") || word.parse::<IpAddr>().is_ok()
+}
+
+/// Redacts the word if it's an address, keeping the port of `ip:port` forms.
+fn redact_word(word: &str) -> String {
+    if is_address(word) {
+        return REDACTED.to_owned();
+    }
+
+    match word.parse::<SocketAddr>() {
+        Ok(addr) => format!("{}:{}", REDACTED, addr.port()),
+        Err(_) => word.to_owned(),
+    }
+}
+
+/// Replaces IP addresses and mDNS hostnames in the text, so that logs don't reveal
+/// addresses of agents. IPv6 addresses in `[ip]:port` forms are matched without
+/// brackets.
+pub fn redact(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find(is_address_char) {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let end = rest.find(|c| !is_address_char(c)).unwrap_or(rest.len());
+        let word = &rest[..end];
+        out.push_str(&redact_word(word));
+        rest = &rest[end..];
+    }
+
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SRFLX: &str = "candidate:2 1 udp 1686052607 203.0.113.7 54400 typ srflx \
+                         raddr 192.168.1.5 rport 54400";
+
+    #[test]
+    fn parse_candidate_type() {
+        let cases = [
+            ("candidate:1 1 udp 1 192.168.1.5 54400 typ host", Some(CandidateType::Host)),
+            ("candidate:1 1 udp 1 4b8f1c2e.local 54400 typ host", Some(CandidateType::Mdns)),
+            (SRFLX, Some(CandidateType::Srflx)),
+            ("candidate:3 1 udp 1 198.51.100.2 3478 typ relay", Some(CandidateType::Relay)),
+            ("candidate:4 1 udp 1 198.51.100.2 3478 typ unknown", None),
+            ("", None),
+        ];
+
+        for &(candidate, expected) in cases.iter() {
+            assert_eq!(candidate_type(candidate), expected, "{}", candidate);
+        }
+    }
+
+    #[test]
+    fn hide_related_address_of_candidate() {
+        assert_eq!(
+            hide_related_address(SRFLX),
+            "candidate:2 1 udp 1686052607 203.0.113.7 54400 typ srflx raddr 0.0.0.0 rport 0"
+        );
+    }
+
+    #[test]
+    fn redact_addresses() {
+        let sdp = r"c=IN IP6 fe80::1\r\na=candidate:1 1 udp 1 4b8f1c2e-54c1.local 9 typ host";
+        let text = format!(
+            r#"{{"candidate":"{}","sdp":"{}","created_at":"2018-05-02T10:12:45.340121"}}"#,
+            SRFLX, sdp
+        );
+
+        let redacted = redact(&text);
+        for address in &["203.0.113.7", "192.168.1.5", "fe80::1", "4b8f1c2e-54c1.local"] {
+            assert!(!redacted.contains(address), "{}", redacted);
+        }
+        assert!(redacted.contains("typ srflx raddr x.x.x.x rport 54400"));
+        assert!(redacted.contains("2018-05-02T10:12:45.340121"));
+    }
+
+    #[test]
+    fn redact_addresses_with_ports() {
+        assert_eq!(
+            redact("Connected 192.168.1.5:54400 to [2001:db8::7]:3478"),
+            "Connected x.x.x.x:54400 to [x.x.x.x]:3478"
+        );
+        assert_eq!(redact("at 10:12:45 from 4b8f1c2e.local"), "at 10:12:45 from x.x.x.x");
+    }
+}
@@ -0,0 +1,597 @@
+use serde_json::Value;
+
+use std::collections::HashSet;
+use std::str::FromStr;
+
+mod candidate;
+mod policy;
+
+pub use sdp::candidate::{redact, CandidateType};
+pub use sdp::policy::Policy;
+
+#[derive(Debug, Fail, PartialEq)]
+pub enum Error {
+    #[fail(display = "jsep must be an object with string type and sdp")]
+    InvalidJsep,
+
+    #[fail(display = "jsep type must be {}, got {}", expected, actual)]
+    UnexpectedType {
+        expected: &'static str,
+        actual: String,
+    },
+
+    #[fail(display = "line {}: malformed line", _0)]
+    MalformedLine(usize),
+
+    #[fail(display = "line {}: malformed {} line", _0, _1)]
+    Malformed(usize, &'static str),
+
+    #[fail(display = "description must start with v=0")]
+    MissingVersion,
+
+    #[fail(display = "missing {} line", _0)]
+    Missing(&'static str),
+
+    #[fail(display = "media section {} has no mid", _0)]
+    MissingMid(usize),
+
+    #[fail(display = "mid {} is used more than once", _0)]
+    DuplicateMid(String),
+
+    #[fail(display = "media section {} has no ICE credentials", _0)]
+    MissingIceCredentials(String),
+
+    #[fail(display = "media section {} has no rtpmap for payload type {}", _0, _1)]
+    MissingRtpmap(String, u8),
+
+    #[fail(display = "media section {} has no payload type {}", _0, _1)]
+    UnknownPayloadType(String, u8),
+
+    #[fail(display = "BUNDLE group refers to unknown mid {}", _0)]
+    UnknownBundleMid(String),
+
+    #[fail(display = "track {} has no msid in the description", _0)]
+    UnknownTrack(String),
+
+    #[fail(display = "media section {} has no codecs allowed in the room", _0)]
+    NoAllowedCodecs(String),
+}
+
+/// A parsed SDP, only the parts signaling cares about.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SessionDescription {
+    pub ice_ufrag: Option<String>,
+    pub ice_pwd: Option<String>,
+    /// Mids of the BUNDLE group.
+    pub bundle: Vec<String>,
+    pub media: Vec<Media>,
+}
+
+/// A media section, `m=` and the lines up to the next one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Media {
+    /// `audio`, `video` or `application`.
+    pub kind: String,
+    pub port: u16,
+    pub protocol: String,
+    pub formats: Vec<String>,
+    pub mid: Option<String>,
+    pub msids: Vec<Msid>,
+    pub direction: Direction,
+    pub codecs: Vec<Codec>,
+    pub ice_ufrag: Option<String>,
+    pub ice_pwd: Option<String>,
+}
+
+/// `a=msid:$STREAM_ID $TRACK_ID`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Msid {
+    pub stream_id: String,
+    pub track_id: Option<String>,
+}
+
+/// `a=rtpmap:$PAYLOAD_TYPE $NAME/$CLOCK_RATE[/$CHANNELS]`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Codec {
+    pub payload_type: u8,
+    pub name: String,
+    pub clock_rate: u32,
+    pub channels: Option<u8>,
+    /// `a=fmtp:$PAYLOAD_TYPE $PARAMETERS`
+    pub parameters: Option<String>,
+}
+
+impl Codec {
+    /// The payload type a retransmission codec is for.
+    pub fn apt(&self) -> Option<u8> {
+        self.parameters
+            .as_ref()?
+            .split(';')
+            .filter_map(|param| {
+                let mut parts = param.trim().splitn(2, '=');
+                match (parts.next(), parts.next()) {
+                    (Some("apt"), Some(value)) => value.parse().ok(),
+                    _ => None,
+                }
+            })
+            .next()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    SendRecv,
+    SendOnly,
+    RecvOnly,
+    Inactive,
+}
+
+impl Direction {
+    fn parse(attr: &str) -> Option<Direction> {
+        match attr {
+            "sendrecv" => Some(Direction::SendRecv),
+            "sendonly" => Some(Direction::SendOnly),
+            "recvonly" => Some(Direction::RecvOnly),
+            "inactive" => Some(Direction::Inactive),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Direction::SendRecv => "sendrecv",
+            Direction::SendOnly => "sendonly",
+            Direction::RecvOnly => "recvonly",
+            Direction::Inactive => "inactive",
+        }
+    }
+}
+
+impl Media {
+    fn new(kind: &str, port: u16, protocol: &str, formats: Vec<String>) -> Media {
+        Media {
+            kind: kind.to_owned(),
+            port,
+            protocol: protocol.to_owned(),
+            formats,
+            mid: None,
+            msids: vec![],
+            direction: Direction::SendRecv,
+            codecs: vec![],
+            ice_ufrag: None,
+            ice_pwd: None,
+        }
+    }
+
+    pub fn is_rtp(&self) -> bool {
+        self.protocol.contains("RTP/")
+    }
+
+    pub fn codec(&self, payload_type: u8) -> Option<&Codec> {
+        self.codecs
+            .iter()
+            .find(|codec| codec.payload_type == payload_type)
+    }
+}
+
+/// Parses `jsep` of a signaling message, which must be of the type.
+pub fn parse_jsep(jsep: &Value, expected: &'static str) -> Result<SessionDescription, Error> {
+    let kind = jsep.get("type").and_then(Value::as_str);
+    let sdp = jsep.get("sdp").and_then(Value::as_str);
+
+    let (kind, sdp) = match (kind, sdp) {
+        (Some(kind), Some(sdp)) => (kind, sdp),
+        _ => return Err(Error::InvalidJsep),
+    };
+
+    if kind != expected {
+        return Err(Error::UnexpectedType {
+            expected,
+            actual: kind.to_owned(),
+        });
+    }
+
+    SessionDescription::from_str(sdp)
+}
+
+impl SessionDescription {
+    /// Checks that every track is announced by an msid, either as a stream or as a track.
+    pub fn check_tracks<I, S>(&self, track_ids: I) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let ids: HashSet<&str> = self.media
+            .iter()
+            .flat_map(|media| media.msids.iter())
+            .flat_map(|msid| {
+                let track_id = msid.track_id.as_ref().map(String::as_str);
+                Some(msid.stream_id.as_str()).into_iter().chain(track_id)
+            })
+            .collect();
+
+        for track_id in track_ids {
+            let track_id = track_id.as_ref();
+            if !ids.contains(track_id) {
+                return Err(Error::UnknownTrack(track_id.to_owned()));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        let mut mids = HashSet::new();
+
+        for (index, media) in self.media.iter().enumerate() {
+            let mid = media.mid.as_ref().ok_or(Error::MissingMid(index))?;
+            if !mids.insert(mid.as_str()) {
+                return Err(Error::DuplicateMid(mid.clone()));
+            }
+
+            // Rejected sections with the zero port need no transport.
+            let ufrag = media.ice_ufrag.as_ref().or(self.ice_ufrag.as_ref());
+            let pwd = media.ice_pwd.as_ref().or(self.ice_pwd.as_ref());
+            if media.port != 0 && (ufrag.is_none() || pwd.is_none()) {
+                return Err(Error::MissingIceCredentials(mid.clone()));
+            }
+
+            if media.is_rtp() {
+                for format in &media.formats {
+                    let payload_type = format.parse::<u8>().unwrap_or(0);
+                    // Static payload types may go without rtpmap.
+                    if payload_type >= 96 && media.codec(payload_type).is_none() {
+                        return Err(Error::MissingRtpmap(mid.clone(), payload_type));
+                    }
+                }
+            }
+        }
+
+        for mid in &self.bundle {
+            if !mids.contains(mid.as_str()) {
+                return Err(Error::UnknownBundleMid(mid.clone()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for SessionDescription {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut desc = SessionDescription {
+            ice_ufrag: None,
+            ice_pwd: None,
+            bundle: vec![],
+            media: vec![],
+        };
+        let mut direction = None;
+        let mut seen = HashSet::new();
+
+        for (index, line) in s.lines().enumerate() {
+            let number = index + 1;
+            if line.is_empty() {
+                continue;
+            }
+
+            let bytes = line.as_bytes();
+            if bytes.len() < 2 || bytes[1] != b'=' || !bytes[0].is_ascii_lowercase() {
+                return Err(Error::MalformedLine(number));
+            }
+
+            let (kind, value) = (bytes[0], &line[2..]);
+            if seen.is_empty() && (kind != b'v' || value != "0") {
+                return Err(Error::MissingVersion);
+            }
+            seen.insert(kind);
+
+            match kind {
+                b'm' => {
+                    let mut media = parse_media(value).ok_or(Error::Malformed(number, "m="))?;
+                    // The session-level direction is the default one of media sections.
+                    media.direction = direction.unwrap_or(Direction::SendRecv);
+                    desc.media.push(media);
+                }
+                b'a' => {
+                    let (name, value) = match value.find(':') {
+                        Some(pos) => (&value[..pos], Some(&value[pos + 1..])),
+                        None => (value, None),
+                    };
+
+                    match desc.media.last_mut() {
+                        Some(media) => parse_media_attribute(media, name, value, number)?,
+                        None => {
+                            if let Some(dir) = Direction::parse(name) {
+                                direction = Some(dir);
+                            }
+                            parse_session_attribute(&mut desc, name, value, number)?;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if seen.is_empty() {
+            return Err(Error::MissingVersion);
+        }
+
+        for &(kind, name) in &[(b'o', "o="), (b's', "s="), (b't', "t=")] {
+            if !seen.contains(&kind) {
+                return Err(Error::Missing(name));
+            }
+        }
+
+        desc.validate()?;
+
+        Ok(desc)
+    }
+}
+
+fn parse_media(value: &str) -> Option<Media> {
+    let mut parts = value.split_whitespace();
+    let kind = parts.next()?;
+    let port = parts.next()?.split('/').next()?.parse().ok()?;
+    let protocol = parts.next()?;
+    let formats: Vec<String> = parts.map(str::to_owned).collect();
+
+    if formats.is_empty() {
+        return None;
+    }
+
+    Some(Media::new(kind, port, protocol, formats))
+}
+
+fn parse_session_attribute(
+    desc: &mut SessionDescription,
+    name: &str,
+    value: Option<&str>,
+    number: usize,
+) -> Result<(), Error> {
+    match name {
+        "ice-ufrag" => desc.ice_ufrag = Some(required(value, number, "a=ice-ufrag")?),
+        "ice-pwd" => desc.ice_pwd = Some(required(value, number, "a=ice-pwd")?),
+        "group" => {
+            let value = required(value, number, "a=group")?;
+            let mut parts = value.split_whitespace();
+            if parts.next() == Some("BUNDLE") {
+                desc.bundle = parts.map(str::to_owned).collect();
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+fn parse_media_attribute(
+    media: &mut Media,
+    name: &str,
+    value: Option<&str>,
+    number: usize,
+) -> Result<(), Error> {
+    if let Some(direction) = Direction::parse(name) {
+        media.direction = direction;
+        return Ok(());
+    }
+
+    match name {
+        "mid" => media.mid = Some(required(value, number, "a=mid")?),
+        "ice-ufrag" => media.ice_ufrag = Some(required(value, number, "a=ice-ufrag")?),
+        "ice-pwd" => media.ice_pwd = Some(required(value, number, "a=ice-pwd")?),
+        "msid" => {
+            let value = required(value, number, "a=msid")?;
+            let mut parts = value.split_whitespace();
+            let stream_id = parts.next().ok_or(Error::Malformed(number, "a=msid"))?;
+
+            media.msids.push(Msid {
+                stream_id: stream_id.to_owned(),
+                track_id: parts.next().map(str::to_owned),
+            });
+        }
+        "rtpmap" => {
+            let value = value.unwrap_or("");
+            let codec = parse_rtpmap(value).ok_or(Error::Malformed(number, "a=rtpmap"))?;
+
+            if !media.formats.contains(&codec.payload_type.to_string()) {
+                let mid = media.mid.clone().unwrap_or_default();
+                return Err(Error::UnknownPayloadType(mid, codec.payload_type));
+            }
+
+            media.codecs.push(codec);
+        }
+        "fmtp" => {
+            let value = required(value, number, "a=fmtp")?;
+            let mut parts = value.splitn(2, ' ');
+            let payload_type = parts.next().and_then(|pt| pt.parse::<u8>().ok());
+            let payload_type = payload_type.ok_or(Error::Malformed(number, "a=fmtp"))?;
+
+            // Parameters of a codec go after its rtpmap.
+            let codec = media
+                .codecs
+                .iter_mut()
+                .find(|codec| codec.payload_type == payload_type);
+
+            if let Some(codec) = codec {
+                codec.parameters = parts.next().map(|params| params.trim().to_owned());
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+fn parse_rtpmap(value: &str) -> Option<Codec> {
+    let mut parts = value.splitn(2, ' ');
+    let payload_type = parts.next()?.parse().ok()?;
+    let mut encoding = parts.next()?.trim().split('/');
+    let name = encoding.next()?;
+    if name.is_empty() {
+        return None;
+    }
+
+    let clock_rate = encoding.next()?.parse().ok()?;
+    let channels = match encoding.next() {
+        Some(channels) => Some(channels.parse().ok()?),
+        None => None,
+    };
+
+    Some(Codec {
+        payload_type,
+        name: name.to_owned(),
+        clock_rate,
+        channels,
+        parameters: None,
+    })
+}
+
+fn required(value: Option<&str>, number: usize, name: &'static str) -> Result<String, Error> {
+    match value.map(str::trim) {
+        Some(value) if !value.is_empty() => Ok(value.to_owned()),
+        _ => Err(Error::Malformed(number, name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OFFER: &str = "v=0\r
+o=- 4611731400430051336 2 IN IP4 127.0.0.1\r
+s=-\r
+t=0 0\r
+a=group:BUNDLE 0 1\r
+a=msid-semantic: WMS 915adba9-5586-4743-a22e-47cc57260e37\r
+m=audio 9 UDP/TLS/RTP/SAVPF 111 0\r
+c=IN IP4 0.0.0.0\r
+a=ice-ufrag:EsAw\r
+a=ice-pwd:bP+XJMM09aR8AiX1jdukzR6Y\r
+a=mid:0\r
+a=sendrecv\r
+a=msid:915adba9-5586-4743-a22e-47cc57260e37 b5a8f4a6-5c3e-4d8b-9e0a-1c1f0a7a3c2d\r
+a=rtpmap:111 opus/48000/2\r
+a=fmtp:111 minptime=10;useinbandfec=1\r
+m=video 9 UDP/TLS/RTP/SAVPF 96 98\r
+c=IN IP4 0.0.0.0\r
+a=ice-ufrag:EsAw\r
+a=ice-pwd:bP+XJMM09aR8AiX1jdukzR6Y\r
+a=mid:1\r
+a=sendonly\r
+a=msid:915adba9-5586-4743-a22e-47cc57260e37 2f1f7e8a-3a5e-4c4b-8f61-6f4b6c0c1e4e\r
+a=rtpmap:96 VP8/90000\r
+a=rtpmap:98 H264/90000\r
+";
+
+    #[test]
+    fn parse_offer() {
+        let desc = SessionDescription::from_str(OFFER).unwrap();
+
+        assert_eq!(desc.bundle, vec!["0", "1"]);
+        assert_eq!(desc.media.len(), 2);
+
+        let audio = &desc.media[0];
+        assert_eq!(audio.kind, "audio");
+        assert_eq!(audio.port, 9);
+        assert_eq!(audio.mid, Some("0".to_owned()));
+        assert_eq!(audio.direction, Direction::SendRecv);
+        assert_eq!(audio.ice_ufrag, Some("EsAw".to_owned()));
+        assert_eq!(
+            audio.codecs,
+            vec![Codec {
+                payload_type: 111,
+                name: "opus".to_owned(),
+                clock_rate: 48000,
+                channels: Some(2),
+                parameters: Some("minptime=10;useinbandfec=1".to_owned()),
+            }]
+        );
+        assert_eq!(
+            audio.msids,
+            vec![Msid {
+                stream_id: "915adba9-5586-4743-a22e-47cc57260e37".to_owned(),
+                track_id: Some("b5a8f4a6-5c3e-4d8b-9e0a-1c1f0a7a3c2d".to_owned()),
+            }]
+        );
+
+        let video = &desc.media[1];
+        assert_eq!(video.direction, Direction::SendOnly);
+        assert_eq!(video.codecs[1].name, "H264");
+    }
+
+    #[test]
+    fn parse_jsep_of_type() {
+        let jsep = json_jsep("offer", OFFER);
+        assert!(parse_jsep(&jsep, "offer").is_ok());
+
+        assert_eq!(
+            parse_jsep(&jsep, "answer"),
+            Err(Error::UnexpectedType {
+                expected: "answer",
+                actual: "offer".to_owned(),
+            })
+        );
+        assert_eq!(
+            parse_jsep(&Value::from("v=0"), "offer"),
+            Err(Error::InvalidJsep)
+        );
+    }
+
+    #[test]
+    fn check_tracks() {
+        let desc = SessionDescription::from_str(OFFER).unwrap();
+
+        assert!(desc.check_tracks(vec!["915adba9-5586-4743-a22e-47cc57260e37"]).is_ok());
+        assert_eq!(
+            desc.check_tracks(vec!["050b7c6f-795c-4cb4-aeea-5ee3f9083de2"]),
+            Err(Error::UnknownTrack("050b7c6f-795c-4cb4-aeea-5ee3f9083de2".to_owned()))
+        );
+    }
+
+    #[test]
+    fn reject_malformed() {
+        let cases = vec![
+            ("", Error::MissingVersion),
+            ("v=1\r\n", Error::MissingVersion),
+            ("v=0\r\nfoo\r\n", Error::MalformedLine(2)),
+            ("v=0\r\no=- 1 1 IN IP4 0.0.0.0\r\nt=0 0\r\n", Error::Missing("s=")),
+            (
+                "v=0\r\no=-\r\ns=-\r\nt=0 0\r\nm=audio x RTP/AVP 0\r\n",
+                Error::Malformed(5, "m="),
+            ),
+            (
+                "v=0\r\no=-\r\ns=-\r\nt=0 0\r\nm=audio 9 RTP/AVP 0\r\n",
+                Error::MissingMid(0),
+            ),
+            (
+                "v=0\r\no=-\r\ns=-\r\nt=0 0\r\nm=audio 9 RTP/AVP 0\r\na=mid:0\r\n",
+                Error::MissingIceCredentials("0".to_owned()),
+            ),
+        ];
+
+        for (sdp, err) in cases {
+            assert_eq!(SessionDescription::from_str(sdp), Err(err), "{:?}", sdp);
+        }
+
+        let sdp = OFFER.replace("a=rtpmap:98 H264/90000\r\n", "");
+        assert_eq!(
+            SessionDescription::from_str(&sdp),
+            Err(Error::MissingRtpmap("1".to_owned(), 98))
+        );
+
+        let sdp = OFFER.replace("a=mid:1", "a=mid:0");
+        assert_eq!(
+            SessionDescription::from_str(&sdp),
+            Err(Error::DuplicateMid("0".to_owned()))
+        );
+    }
+
+    fn json_jsep(kind: &str, sdp: &str) -> Value {
+        let mut jsep = ::serde_json::Map::new();
+        jsep.insert("type".to_owned(), Value::from(kind));
+        jsep.insert("sdp".to_owned(), Value::from(sdp));
+        Value::Object(jsep)
+    }
+}
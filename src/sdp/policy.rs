@@ -0,0 +1,411 @@
+use std::collections::{BTreeMap, HashSet};
+
+use sdp::candidate::{candidate_type, hide_related_address, CandidateType};
+use sdp::{Direction, Error, Media, SessionDescription};
+
+/// Rewrites of descriptions relayed in a room.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Policy {
+    /// Codecs which go first, in order of preference, e.g. `["VP8", "opus"]`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub codec_order: Vec<String>,
+    /// Other codecs are stripped. Retransmission follows the codec it's for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_codecs: Option<Vec<String>>,
+    /// Bandwidth limits of media kinds in kbps, e.g. `{"video": 1500}`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub bandwidth: BTreeMap<String, u32>,
+    /// Whether listeners only receive media.
+    #[serde(default)]
+    pub listener_recvonly: bool,
+    /// Other candidates are dropped, e.g. `["relay"]` to hide addresses of agents.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub candidate_types: Option<Vec<CandidateType>>,
+}
+
+const RTX: &str = "rtx";
+
+fn contains_name(names: &[String], name: &str) -> bool {
+    names.iter().any(|n| n.eq_ignore_ascii_case(name))
+}
+
+/// The payload type `a=rtpmap`, `a=fmtp` and `a=rtcp-fb` lines refer to.
+fn payload_type(line: &str) -> Option<&str> {
+    ["a=rtpmap:", "a=fmtp:", "a=rtcp-fb:"]
+        .iter()
+        .find(|prefix| line.starts_with(*prefix))
+        .and_then(|prefix| line[prefix.len()..].split_whitespace().next())
+}
+
+fn is_direction(line: &str) -> bool {
+    line.starts_with("a=") && Direction::parse(&line[2..]).is_some()
+}
+
+impl Policy {
+    pub fn is_empty(&self) -> bool {
+        *self == Policy::default()
+    }
+
+    /// Filters a candidate attribute by its type. Related addresses of candidates
+    /// which pass are hidden.
+    pub fn filter_candidate(&self, candidate: &str) -> Option<String> {
+        match self.candidate_types {
+            Some(ref types) => match candidate_type(candidate) {
+                Some(kind) if types.contains(&kind) => Some(hide_related_address(candidate)),
+                _ => None,
+            },
+            None => Some(candidate.to_owned()),
+        }
+    }
+
+    /// Filters candidates of the description and replaces default addresses,
+    /// since those are addresses of candidates too.
+    fn apply_line(&self, line: &str) -> Option<String> {
+        if self.candidate_types.is_none() {
+            return Some(line.to_owned());
+        }
+
+        if line.starts_with("a=candidate:") {
+            self.filter_candidate(&line[2..])
+                .map(|candidate| format!("a={}", candidate))
+        } else if line.starts_with("c=IN IP6 ") {
+            Some("c=IN IP6 ::".to_owned())
+        } else if line.starts_with("c=") {
+            Some("c=IN IP4 0.0.0.0".to_owned())
+        } else if line.starts_with("a=rtcp:") {
+            Some("a=rtcp:9 IN IP4 0.0.0.0".to_owned())
+        } else {
+            Some(line.to_owned())
+        }
+    }
+
+    /// Rewrites the SDP the description has been parsed from. Listeners' media
+    /// sections become `recvonly` or `inactive` if the policy says so.
+    pub fn apply(
+        &self,
+        sdp: &str,
+        desc: &SessionDescription,
+        is_listener: bool,
+    ) -> Result<String, Error> {
+        let mut sections: Vec<Vec<&str>> = vec![vec![]];
+        for line in sdp.lines().filter(|line| !line.is_empty()) {
+            if line.starts_with("m=") {
+                sections.push(vec![]);
+            }
+            if let Some(section) = sections.last_mut() {
+                section.push(line);
+            }
+        }
+
+        let mut lines: Vec<String> = sections[0]
+            .iter()
+            .filter_map(|line| self.apply_line(line))
+            .collect();
+        for (media, section) in desc.media.iter().zip(&sections[1..]) {
+            let recvonly = is_listener && self.listener_recvonly && media.is_rtp();
+            lines.extend(self.apply_media(media, section, recvonly)?);
+        }
+
+        lines.push(String::new());
+        Ok(lines.join("\r\n"))
+    }
+
+    fn apply_media(
+        &self,
+        media: &Media,
+        section: &[&str],
+        recvonly: bool,
+    ) -> Result<Vec<String>, Error> {
+        let formats = if media.is_rtp() {
+            self.formats(media)?
+        } else {
+            media.formats.clone()
+        };
+
+        let removed: HashSet<&str> = media
+            .formats
+            .iter()
+            .filter(|format| !formats.contains(format))
+            .map(String::as_str)
+            .collect();
+
+        let direction = match media.direction {
+            Direction::SendRecv | Direction::RecvOnly => Direction::RecvOnly,
+            Direction::SendOnly | Direction::Inactive => Direction::Inactive,
+        };
+        let bandwidth = self.bandwidth.get(&media.kind);
+
+        let mut lines = vec![];
+        for line in section {
+            if line.starts_with("m=") {
+                let mut head: Vec<&str> = line[2..].split_whitespace().take(3).collect();
+                if self.candidate_types.is_some() && media.port != 0 {
+                    head[1] = "9";
+                }
+
+                lines.push(format!("m={} {}", head.join(" "), formats.join(" ")));
+                continue;
+            }
+
+            let is_dropped = (bandwidth.is_some() && line.starts_with("b=AS:"))
+                || (recvonly && is_direction(line))
+                || payload_type(line).map_or(false, |pt| removed.contains(pt));
+
+            if !is_dropped {
+                lines.extend(self.apply_line(line));
+            }
+        }
+
+        if let Some(kbps) = bandwidth {
+            // `b=` goes after `i=` and `c=` lines.
+            let pos = lines
+                .iter()
+                .position(|line| !["m=", "i=", "c="].iter().any(|p| line.starts_with(p)))
+                .unwrap_or(lines.len());
+            lines.insert(pos, format!("b=AS:{}", kbps));
+        }
+
+        if recvonly {
+            lines.push(format!("a={}", direction.as_str()));
+        }
+
+        Ok(lines)
+    }
+
+    /// Allowed formats of the media section, preferred ones first.
+    fn formats(&self, media: &Media) -> Result<Vec<String>, Error> {
+        let name = |format: &str| {
+            format
+                .parse()
+                .ok()
+                .and_then(|pt| media.codec(pt))
+                .map(|codec| codec.name.as_str())
+        };
+
+        let mut formats: Vec<&String> = match self.allowed_codecs {
+            Some(ref allowed) => {
+                let codecs: Vec<&String> = media
+                    .formats
+                    .iter()
+                    .filter(|format| match name(format) {
+                        Some(name) => name != RTX && contains_name(allowed, name),
+                        None => false,
+                    })
+                    .collect();
+
+                // Retransmission of allowed codecs stays.
+                media
+                    .formats
+                    .iter()
+                    .filter(|format| {
+                        if codecs.contains(format) {
+                            return true;
+                        }
+
+                        let apt = format
+                            .parse()
+                            .ok()
+                            .and_then(|pt| media.codec(pt))
+                            .and_then(|codec| {
+                                if codec.name.eq_ignore_ascii_case(RTX) {
+                                    codec.apt()
+                                } else {
+                                    None
+                                }
+                            });
+
+                        apt.map_or(false, |apt| codecs.contains(&&apt.to_string()))
+                    })
+                    .collect()
+            }
+            None => media.formats.iter().collect(),
+        };
+
+        // A rejected section still needs a format in its m= line, and
+        // none of them is going to be used anyway.
+        if formats.is_empty() {
+            if media.port != 0 {
+                let mid = media.mid.clone().unwrap_or_default();
+                return Err(Error::NoAllowedCodecs(mid));
+            }
+
+            formats = media.formats.iter().collect();
+        }
+
+        let rank = |format: &str| {
+            name(format)
+                .and_then(|name| {
+                    self.codec_order
+                        .iter()
+                        .position(|n| n.eq_ignore_ascii_case(name))
+                })
+                .unwrap_or(self.codec_order.len())
+        };
+        formats.sort_by_key(|format| rank(format));
+
+        Ok(formats.into_iter().cloned().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    const OFFER: &str = "v=0\r
+o=- 4611731400430051336 2 IN IP4 127.0.0.1\r
+s=-\r
+t=0 0\r
+a=group:BUNDLE 0 1\r
+m=audio 54400 UDP/TLS/RTP/SAVPF 111 0\r
+c=IN IP4 203.0.113.7\r
+a=rtcp:54400 IN IP4 203.0.113.7\r
+a=candidate:1 1 udp 2122260223 192.168.1.5 54400 typ host\r
+a=candidate:2 1 udp 1686052607 203.0.113.7 54400 typ srflx raddr 192.168.1.5 rport 54400\r
+a=candidate:3 1 udp 41885439 198.51.100.2 3478 typ relay raddr 203.0.113.7 rport 54400\r
+a=ice-ufrag:EsAw\r
+a=ice-pwd:bP+XJMM09aR8AiX1jdukzR6Y\r
+a=mid:0\r
+a=sendrecv\r
+a=rtpmap:111 opus/48000/2\r
+a=fmtp:111 minptime=10;useinbandfec=1\r
+m=video 9 UDP/TLS/RTP/SAVPF 96 97 98 99\r
+c=IN IP4 0.0.0.0\r
+b=AS:4000\r
+a=ice-ufrag:EsAw\r
+a=ice-pwd:bP+XJMM09aR8AiX1jdukzR6Y\r
+a=mid:1\r
+a=sendrecv\r
+a=rtpmap:96 VP8/90000\r
+a=rtcp-fb:96 nack\r
+a=rtpmap:97 rtx/90000\r
+a=fmtp:97 apt=96\r
+a=rtpmap:98 H264/90000\r
+a=rtcp-fb:98 nack\r
+a=rtpmap:99 rtx/90000\r
+a=fmtp:99 apt=98\r
+";
+
+    fn apply(policy: &Policy, is_listener: bool) -> SessionDescription {
+        let desc = SessionDescription::from_str(OFFER).unwrap();
+        let sdp = policy.apply(OFFER, &desc, is_listener).unwrap();
+        SessionDescription::from_str(&sdp).unwrap()
+    }
+
+    #[test]
+    fn empty_policy() {
+        let desc = SessionDescription::from_str(OFFER).unwrap();
+        let policy = Policy::default();
+
+        assert!(policy.is_empty());
+        assert_eq!(policy.apply(OFFER, &desc, true).unwrap(), OFFER);
+    }
+
+    #[test]
+    fn order_codecs() {
+        let policy = Policy {
+            codec_order: vec!["h264".to_owned(), "PCMU".to_owned()],
+            ..Default::default()
+        };
+
+        let desc = apply(&policy, false);
+        assert_eq!(desc.media[0].formats, vec!["111", "0"]);
+        assert_eq!(desc.media[1].formats, vec!["98", "96", "97", "99"]);
+    }
+
+    #[test]
+    fn strip_codecs() {
+        let policy = Policy {
+            allowed_codecs: Some(vec!["opus".to_owned(), "VP8".to_owned()]),
+            ..Default::default()
+        };
+
+        let desc = SessionDescription::from_str(OFFER).unwrap();
+        let sdp = policy.apply(OFFER, &desc, false).unwrap();
+        assert!(!sdp.contains("a=rtcp-fb:98"));
+        assert!(!sdp.contains("a=fmtp:99"));
+
+        let desc = SessionDescription::from_str(&sdp).unwrap();
+        assert_eq!(desc.media[0].formats, vec!["111"]);
+        assert_eq!(desc.media[1].formats, vec!["96", "97"]);
+
+        let policy = Policy {
+            allowed_codecs: Some(vec!["opus".to_owned()]),
+            ..Default::default()
+        };
+
+        let desc = SessionDescription::from_str(OFFER).unwrap();
+        assert_eq!(
+            policy.apply(OFFER, &desc, false),
+            Err(Error::NoAllowedCodecs("1".to_owned()))
+        );
+
+        let offer = OFFER.replace("m=video 9", "m=video 0");
+        let desc = SessionDescription::from_str(&offer).unwrap();
+        let sdp = policy.apply(&offer, &desc, false).unwrap();
+        assert!(sdp.contains("m=video 0 UDP/TLS/RTP/SAVPF 96 97 98 99\r\n"), "{}", sdp);
+
+        let desc = SessionDescription::from_str(&sdp).unwrap();
+        assert_eq!(desc.media[0].formats, vec!["111"]);
+        assert_eq!(desc.media[1].formats, vec!["96", "97", "98", "99"]);
+    }
+
+    #[test]
+    fn limit_bandwidth() {
+        let mut bandwidth = BTreeMap::new();
+        bandwidth.insert("audio".to_owned(), 64);
+        bandwidth.insert("video".to_owned(), 1500);
+        let policy = Policy {
+            bandwidth,
+            ..Default::default()
+        };
+
+        let desc = SessionDescription::from_str(OFFER).unwrap();
+        let sdp = policy.apply(OFFER, &desc, false).unwrap();
+        assert!(sdp.contains("c=IN IP4 203.0.113.7\r\nb=AS:64\r\na=rtcp"));
+        assert!(sdp.contains("c=IN IP4 0.0.0.0\r\nb=AS:1500\r\na=ice-ufrag"));
+        assert!(!sdp.contains("b=AS:4000"));
+    }
+
+    #[test]
+    fn listener_recvonly() {
+        let policy = Policy {
+            listener_recvonly: true,
+            ..Default::default()
+        };
+
+        let desc = apply(&policy, true);
+        assert_eq!(desc.media[0].direction, Direction::RecvOnly);
+        assert_eq!(desc.media[1].direction, Direction::RecvOnly);
+
+        let desc = apply(&policy, false);
+        assert_eq!(desc.media[0].direction, Direction::SendRecv);
+    }
+
+    #[test]
+    fn filter_candidates() {
+        let policy = Policy {
+            candidate_types: Some(vec![CandidateType::Relay]),
+            ..Default::default()
+        };
+
+        let desc = SessionDescription::from_str(OFFER).unwrap();
+        let sdp = policy.apply(OFFER, &desc, false).unwrap();
+        for address in &["192.168.1.5", "203.0.113.7", "54400"] {
+            assert!(!sdp.contains(address), "{}", sdp);
+        }
+        assert!(sdp.contains("m=audio 9 UDP/TLS/RTP/SAVPF 111 0\r\nc=IN IP4 0.0.0.0\r\n"));
+        assert!(sdp.contains(
+            "a=candidate:3 1 udp 41885439 198.51.100.2 3478 typ relay raddr 0.0.0.0 rport 0\r\n"
+        ));
+
+        assert_eq!(
+            policy.filter_candidate("candidate:1 1 udp 2122260223 192.168.1.5 54400 typ host"),
+            None
+        );
+        assert_eq!(
+            policy.filter_candidate("candidate:3 1 udp 41885439 198.51.100.2 3478 typ relay"),
+            Some("candidate:3 1 udp 41885439 198.51.100.2 3478 typ relay".to_owned())
+        );
+    }
+}
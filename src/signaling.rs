@@ -0,0 +1,544 @@
+use chrono::{DateTime, Duration, Utc};
+use serde_json::Value;
+use uuid::Uuid;
+
+use std::collections::HashMap;
+
+/// An offer left unanswered for this long is considered abandoned.
+const OFFER_TIMEOUT_SECS: i64 = 30;
+
+/// Candidates wait for the description they belong to for this long at most.
+const CANDIDATE_TIMEOUT_SECS: i64 = 30;
+
+/// How many candidates of an agent may wait for its description.
+const MAX_QUEUED_CANDIDATES: usize = 64;
+
+/// Sessions without signaling for this long are dropped.
+const IDLE_TIMEOUT_SECS: i64 = 3600;
+
+/// Idle sessions are dropped once in this many updates.
+const PURGE_INTERVAL: u64 = 256;
+
+#[derive(Debug, Fail, PartialEq)]
+pub enum Error {
+    /// Both agents have offered at once and the offer of the polite one is rejected.
+    #[fail(display = "Glare: an offer from the other agent is pending")]
+    Glare,
+
+    #[fail(display = "No pending offer to answer")]
+    NoPendingOffer,
+
+    #[fail(display = "Too many candidates are waiting for the description")]
+    CandidateQueueFull,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum State {
+    Stable,
+    HaveOffer,
+}
+
+/// Negotiation of a peer connection between two agents in a room.
+#[derive(Clone, Debug)]
+pub struct Session {
+    pub room_id: Uuid,
+    /// The agents, ordered by their identifiers.
+    pub agents: (Uuid, Uuid),
+    /// The agent whose offer is pending.
+    pub offerer: Option<Uuid>,
+    pub offered_at: Option<DateTime<Utc>>,
+    /// How many times the agents have offered at once.
+    pub glares: u32,
+    pub updated_at: DateTime<Utc>,
+    peers: [Peer; 2],
+}
+
+/// An agent of a session.
+#[derive(Clone, Debug, Default)]
+struct Peer {
+    /// Whether an offer or an answer of the agent has been relayed.
+    described: bool,
+    /// Candidates waiting for the description, in order they have been sent.
+    candidates: Vec<(DateTime<Utc>, Value)>,
+    /// Whether the end of candidates has been sent, till ICE restarts.
+    ended: bool,
+}
+
+impl Session {
+    fn new(room_id: Uuid, agents: (Uuid, Uuid), now: DateTime<Utc>) -> Session {
+        Session {
+            room_id,
+            agents,
+            offerer: None,
+            offered_at: None,
+            glares: 0,
+            updated_at: now,
+            peers: Default::default(),
+        }
+    }
+
+    fn peer_mut(&mut self, agent_id: Uuid) -> &mut Peer {
+        if agent_id == self.agents.0 {
+            &mut self.peers[0]
+        } else {
+            &mut self.peers[1]
+        }
+    }
+
+    /// Candidates of the agent waiting for its description.
+    pub fn queued(&self, agent_id: Uuid) -> usize {
+        let peer = if agent_id == self.agents.0 {
+            &self.peers[0]
+        } else {
+            &self.peers[1]
+        };
+
+        peer.candidates.len()
+    }
+
+    /// Marks the description of the agent as relayed and returns candidates
+    /// which have been waiting for it.
+    fn describe(&mut self, agent_id: Uuid) -> Vec<Value> {
+        let peer = self.peer_mut(agent_id);
+        peer.described = true;
+        peer.candidates.drain(..).map(|(_, candidate)| candidate).collect()
+    }
+
+    fn expire_candidates(&mut self, now: DateTime<Utc>) {
+        let timeout = Duration::seconds(CANDIDATE_TIMEOUT_SECS);
+        for peer in &mut self.peers {
+            peer.candidates.retain(|&(at, _)| now - at <= timeout);
+        }
+    }
+
+    pub fn state(&self) -> State {
+        match self.offerer {
+            Some(_) => State::HaveOffer,
+            None => State::Stable,
+        }
+    }
+
+    /// The agent which yields in glare: the one with the greater identifier.
+    /// The offer of the other one wins.
+    pub fn polite(&self) -> Uuid {
+        self.agents.1
+    }
+
+    fn expire_offer(&mut self, now: DateTime<Utc>) {
+        let is_expired = self.offered_at
+            .map_or(false, |at| now - at > Duration::seconds(OFFER_TIMEOUT_SECS));
+
+        if is_expired {
+            self.offerer = None;
+            self.offered_at = None;
+        }
+    }
+}
+
+type Key = (Uuid, (Uuid, Uuid));
+
+fn key(room_id: Uuid, a: Uuid, b: Uuid) -> Key {
+    if a <= b {
+        (room_id, (a, b))
+    } else {
+        (room_id, (b, a))
+    }
+}
+
+/// Negotiation state of every pair of agents signaling each other.
+#[derive(Debug, Default)]
+pub struct Sessions {
+    sessions: HashMap<Key, Session>,
+    updates: u64,
+}
+
+impl Sessions {
+    /// Accounts for an offer from one agent to another. A new offer replaces
+    /// the pending offer of the same agent. Returns candidates of the agent
+    /// to relay after the offer.
+    pub fn offer(&mut self, room_id: Uuid, from: Uuid, to: Uuid) -> Result<Vec<Value>, Error> {
+        self.offer_at(room_id, from, to, Utc::now())
+    }
+
+    fn offer_at(
+        &mut self,
+        room_id: Uuid,
+        from: Uuid,
+        to: Uuid,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Value>, Error> {
+        self.tick(now);
+
+        let key = key(room_id, from, to);
+        let session = self.sessions
+            .entry(key)
+            .or_insert_with(|| Session::new(key.0, key.1, now));
+        session.expire_offer(now);
+
+        match session.offerer {
+            Some(offerer) if offerer != from => {
+                session.glares += 1;
+                if from == session.polite() {
+                    // Candidates of the rejected offer are of no use.
+                    session.peer_mut(from).candidates.clear();
+                    return Err(Error::Glare);
+                }
+            }
+            _ => {}
+        }
+
+        session.offerer = Some(from);
+        session.offered_at = Some(now);
+        session.updated_at = now;
+        session.expire_candidates(now);
+
+        Ok(session.describe(from))
+    }
+
+    /// Accounts for an answer to the pending offer of the other agent.
+    /// Returns candidates of the agent to relay after the answer.
+    pub fn answer(&mut self, room_id: Uuid, from: Uuid, to: Uuid) -> Result<Vec<Value>, Error> {
+        self.answer_at(room_id, from, to, Utc::now())
+    }
+
+    fn answer_at(
+        &mut self,
+        room_id: Uuid,
+        from: Uuid,
+        to: Uuid,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Value>, Error> {
+        self.tick(now);
+
+        let session = self.sessions
+            .get_mut(&key(room_id, from, to))
+            .ok_or(Error::NoPendingOffer)?;
+        session.expire_offer(now);
+
+        if session.offerer != Some(to) {
+            return Err(Error::NoPendingOffer);
+        }
+
+        session.offerer = None;
+        session.offered_at = None;
+        session.updated_at = now;
+        session.expire_candidates(now);
+
+        Ok(session.describe(from))
+    }
+
+    /// Returns the candidate back if it may be relayed right away, that is the
+    /// description of the agent has been relayed. Otherwise the candidate waits
+    /// for the description. A `null` candidate marks the end of candidates, it
+    /// doesn't count against the limit. Candidates after the end, including
+    /// another end, are dropped.
+    pub fn candidate(
+        &mut self,
+        room_id: Uuid,
+        from: Uuid,
+        to: Uuid,
+        candidate: Value,
+    ) -> Result<Option<Value>, Error> {
+        self.candidate_at(room_id, from, to, candidate, Utc::now())
+    }
+
+    fn candidate_at(
+        &mut self,
+        room_id: Uuid,
+        from: Uuid,
+        to: Uuid,
+        candidate: Value,
+        now: DateTime<Utc>,
+    ) -> Result<Option<Value>, Error> {
+        self.tick(now);
+
+        let key = key(room_id, from, to);
+        let session = self.sessions
+            .entry(key)
+            .or_insert_with(|| Session::new(key.0, key.1, now));
+        session.expire_candidates(now);
+        session.updated_at = now;
+
+        let peer = session.peer_mut(from);
+        if peer.ended {
+            return Ok(None);
+        }
+
+        if candidate.is_null() {
+            peer.ended = true;
+        } else if !peer.described && peer.candidates.len() >= MAX_QUEUED_CANDIDATES {
+            return Err(Error::CandidateQueueFull);
+        }
+
+        if peer.described {
+            return Ok(Some(candidate));
+        }
+
+        peer.candidates.push((now, candidate));
+
+        Ok(None)
+    }
+
+    /// Starts negotiation of the agents over: candidates of the former ICE
+    /// generation are dropped, and new ones wait for the next description of
+    /// their agent, since they're of no use without its new ICE credentials.
+    pub fn restart_ice(&mut self, room_id: Uuid, from: Uuid, to: Uuid) {
+        self.restart_ice_at(room_id, from, to, Utc::now())
+    }
+
+    fn restart_ice_at(&mut self, room_id: Uuid, from: Uuid, to: Uuid, now: DateTime<Utc>) {
+        self.tick(now);
+
+        let key = key(room_id, from, to);
+        let session = self.sessions
+            .entry(key)
+            .or_insert_with(|| Session::new(key.0, key.1, now));
+        session.peers = Default::default();
+        session.updated_at = now;
+    }
+
+    /// Drops the session of the agents once their peer connection is closed.
+    pub fn hangup(&mut self, room_id: Uuid, from: Uuid, to: Uuid) {
+        self.sessions.remove(&key(room_id, from, to));
+    }
+
+    /// Drops sessions of the agent in the room, e.g. once it has left the room.
+    pub fn remove_agent(&mut self, room_id: Uuid, agent_id: Uuid) {
+        self.sessions.retain(|&(session_room_id, (a, b)), _| {
+            session_room_id != room_id || (a != agent_id && b != agent_id)
+        });
+    }
+
+    /// Drops every session in the room, e.g. once it has been deleted.
+    pub fn remove_room(&mut self, room_id: Uuid) {
+        self.sessions
+            .retain(|&(session_room_id, _), _| session_room_id != room_id);
+    }
+
+    /// Sessions in the room, ordered by agents.
+    pub fn list(&self, room_id: Uuid) -> Vec<&Session> {
+        let mut sessions: Vec<&Session> = self.sessions
+            .values()
+            .filter(|session| session.room_id == room_id)
+            .collect();
+        sessions.sort_by_key(|session| session.agents);
+        sessions
+    }
+
+    fn tick(&mut self, now: DateTime<Utc>) {
+        self.updates += 1;
+        if self.updates % PURGE_INTERVAL == 0 {
+            let timeout = Duration::seconds(IDLE_TIMEOUT_SECS);
+            self.sessions
+                .retain(|_, session| now - session.updated_at <= timeout);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids() -> (Uuid, Uuid, Uuid) {
+        let room_id = Uuid::parse_str("050b7c6f-795c-4cb4-aeea-5ee3f9083de2").unwrap();
+        let impolite = Uuid::parse_str("1154b35c-e5b0-4a42-8ab2-d4967ce38c9e").unwrap();
+        let polite = Uuid::parse_str("9923576c-1ee5-4987-a797-6cb4982d45de").unwrap();
+        (room_id, impolite, polite)
+    }
+
+    #[test]
+    fn offer_and_answer() {
+        let (room_id, a, b) = ids();
+        let mut sessions = Sessions::default();
+        let now = Utc::now();
+
+        assert_eq!(sessions.offer_at(room_id, b, a, now), Ok(vec![]));
+        assert_eq!(sessions.list(room_id)[0].state(), State::HaveOffer);
+        assert_eq!(sessions.list(room_id)[0].offerer, Some(b));
+
+        sessions.answer_at(room_id, a, b, now).unwrap();
+        assert_eq!(sessions.list(room_id)[0].state(), State::Stable);
+    }
+
+    #[test]
+    fn reject_stray_answer() {
+        let (room_id, a, b) = ids();
+        let mut sessions = Sessions::default();
+        let now = Utc::now();
+
+        assert_eq!(sessions.answer_at(room_id, a, b, now), Err(Error::NoPendingOffer));
+
+        sessions.offer_at(room_id, a, b, now).unwrap();
+        assert_eq!(sessions.answer_at(room_id, a, b, now), Err(Error::NoPendingOffer));
+
+        let later = now + Duration::seconds(OFFER_TIMEOUT_SECS + 1);
+        assert_eq!(sessions.answer_at(room_id, b, a, later), Err(Error::NoPendingOffer));
+    }
+
+    #[test]
+    fn resolve_glare() {
+        let (room_id, impolite, polite) = ids();
+        let now = Utc::now();
+
+        // The polite agent's offer is rejected.
+        let mut sessions = Sessions::default();
+        sessions.offer_at(room_id, impolite, polite, now).unwrap();
+        assert_eq!(
+            sessions.offer_at(room_id, polite, impolite, now),
+            Err(Error::Glare)
+        );
+        assert_eq!(sessions.list(room_id)[0].offerer, Some(impolite));
+
+        // The impolite agent's offer replaces the pending one.
+        let mut sessions = Sessions::default();
+        sessions.offer_at(room_id, polite, impolite, now).unwrap();
+        sessions.offer_at(room_id, impolite, polite, now).unwrap();
+        assert_eq!(sessions.list(room_id)[0].offerer, Some(impolite));
+        assert_eq!(sessions.list(room_id)[0].glares, 1);
+        sessions.answer_at(room_id, polite, impolite, now).unwrap();
+    }
+
+    #[test]
+    fn buffer_candidates() {
+        let (room_id, a, b) = ids();
+        let mut sessions = Sessions::default();
+        let now = Utc::now();
+
+        let candidate = |n| Value::from(format!("candidate:{} 1 udp", n));
+
+        assert_eq!(sessions.candidate_at(room_id, a, b, candidate(1), now), Ok(None));
+        assert_eq!(sessions.candidate_at(room_id, a, b, Value::Null, now), Ok(None));
+        assert_eq!(sessions.list(room_id)[0].queued(a), 2);
+
+        let flushed = sessions.offer_at(room_id, a, b, now).unwrap();
+        assert_eq!(flushed, vec![candidate(1), Value::Null]);
+        assert_eq!(sessions.list(room_id)[0].queued(a), 0);
+
+        // Nothing goes after the end of candidates.
+        assert_eq!(sessions.candidate_at(room_id, a, b, candidate(2), now), Ok(None));
+        assert_eq!(sessions.list(room_id)[0].queued(a), 0);
+
+        // The answerer's candidates wait for its answer.
+        assert_eq!(sessions.candidate_at(room_id, b, a, candidate(3), now), Ok(None));
+        let flushed = sessions.answer_at(room_id, b, a, now).unwrap();
+        assert_eq!(flushed, vec![candidate(3)]);
+    }
+
+    #[test]
+    fn limit_candidate_queue() {
+        let (room_id, a, b) = ids();
+        let mut sessions = Sessions::default();
+        let now = Utc::now();
+
+        for _ in 0..MAX_QUEUED_CANDIDATES {
+            sessions.candidate_at(room_id, a, b, Value::from("candidate"), now).unwrap();
+        }
+        assert_eq!(
+            sessions.candidate_at(room_id, a, b, Value::from("candidate"), now),
+            Err(Error::CandidateQueueFull)
+        );
+        assert_eq!(sessions.candidate_at(room_id, a, b, Value::Null, now), Ok(None));
+        assert_eq!(sessions.list(room_id)[0].queued(a), MAX_QUEUED_CANDIDATES + 1);
+
+        sessions.restart_ice_at(room_id, a, b, now);
+        let later = now + Duration::seconds(CANDIDATE_TIMEOUT_SECS + 1);
+        assert_eq!(sessions.candidate_at(room_id, a, b, Value::from("candidate"), later), Ok(None));
+        assert_eq!(sessions.list(room_id)[0].queued(a), 1);
+    }
+
+    #[test]
+    fn end_of_candidates() {
+        let (room_id, a, b) = ids();
+        let mut sessions = Sessions::default();
+        let now = Utc::now();
+
+        // Only the first end waits for the description, nothing goes after it.
+        for _ in 0..3 {
+            assert_eq!(sessions.candidate_at(room_id, a, b, Value::Null, now), Ok(None));
+        }
+        sessions.candidate_at(room_id, a, b, Value::from("candidate"), now).unwrap();
+        assert_eq!(sessions.list(room_id)[0].queued(a), 1);
+        assert_eq!(sessions.offer_at(room_id, a, b, now), Ok(vec![Value::Null]));
+
+        assert_eq!(sessions.candidate_at(room_id, a, b, Value::Null, now), Ok(None));
+        assert_eq!(sessions.candidate_at(room_id, b, a, Value::Null, now), Ok(None));
+        sessions.answer_at(room_id, b, a, now).unwrap();
+
+        sessions.restart_ice_at(room_id, a, b, now);
+        sessions.offer_at(room_id, a, b, now).unwrap();
+        assert_eq!(
+            sessions.candidate_at(room_id, a, b, Value::Null, now),
+            Ok(Some(Value::Null))
+        );
+        assert_eq!(
+            sessions.candidate_at(room_id, a, b, Value::from("candidate"), now),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn drop_candidates_of_rejected_offer() {
+        let (room_id, impolite, polite) = ids();
+        let mut sessions = Sessions::default();
+        let now = Utc::now();
+
+        sessions.offer_at(room_id, impolite, polite, now).unwrap();
+        sessions.candidate_at(room_id, polite, impolite, Value::from("candidate"), now).unwrap();
+        assert!(sessions.offer_at(room_id, polite, impolite, now).is_err());
+        assert_eq!(sessions.list(room_id)[0].queued(polite), 0);
+    }
+
+    #[test]
+    fn remove_agent() {
+        let (room_id, a, b) = ids();
+        let mut sessions = Sessions::default();
+
+        sessions.offer(room_id, a, b).unwrap();
+        sessions.remove_agent(room_id, b);
+        assert!(sessions.list(room_id).is_empty());
+    }
+
+    #[test]
+    fn remove_room() {
+        let (room_id, a, b) = ids();
+        let other_room_id = Uuid::parse_str("e2a9b7f4-6a0e-4b7c-9a53-0f1d7c3c8e21").unwrap();
+        let mut sessions = Sessions::default();
+
+        sessions.offer(room_id, a, b).unwrap();
+        sessions.offer(other_room_id, a, b).unwrap();
+        sessions.remove_room(room_id);
+        assert!(sessions.list(room_id).is_empty());
+        assert_eq!(sessions.list(other_room_id).len(), 1);
+    }
+
+    #[test]
+    fn restart_ice() {
+        let (room_id, a, b) = ids();
+        let mut sessions = Sessions::default();
+        let now = Utc::now();
+
+        sessions.offer_at(room_id, a, b, now).unwrap();
+        sessions.answer_at(room_id, b, a, now).unwrap();
+        sessions.candidate_at(room_id, a, b, Value::from("candidate"), now).unwrap();
+
+        sessions.restart_ice_at(room_id, b, a, now);
+        assert_eq!(sessions.candidate_at(room_id, a, b, Value::from("candidate"), now), Ok(None));
+        assert_eq!(sessions.candidate_at(room_id, b, a, Value::from("candidate"), now), Ok(None));
+
+        let flushed = sessions.offer_at(room_id, b, a, now).unwrap();
+        assert_eq!(flushed, vec![Value::from("candidate")]);
+        assert_eq!(sessions.list(room_id)[0].queued(a), 1);
+    }
+
+    #[test]
+    fn hangup() {
+        let (room_id, a, b) = ids();
+        let mut sessions = Sessions::default();
+
+        sessions.offer(room_id, a, b).unwrap();
+        sessions.hangup(room_id, b, a);
+        assert!(sessions.list(room_id).is_empty());
+        assert_eq!(sessions.answer(room_id, b, a), Err(Error::NoPendingOffer));
+    }
+}
@@ -13,27 +13,31 @@ named!(pub topic<CompleteStr, Topic>,
         agent_id: map_res!(take_until_s!("/"), |s: CompleteStr| FromStr::from_str(s.0)) >>
         tag_s!("/") >>
         kind: alt!(map!(tag_s!("in"), |_| AgentTopicKind::In) | map!(tag_s!("out"), |_| AgentTopicKind::Out)) >>
-        tag_s!("/signals.netology-group.services/api/") >>
+        tag_s!("/") >>
+        service: map!(take_until_s!("/api/"), |s: CompleteStr| s.0.to_owned()) >>
+        tag_s!("/api/") >>
         version: map!(tag_s!("v1"), |_| Version::V1) >>
         opt!(tag_s!("/")) >>
         eof!() >>
 
-        (Topic::Agent(AgentTopic { kind, agent_id, version }))
+        (Topic::Agent(AgentTopic { kind, agent_id, service, version }))
     )
 );
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct AgentTopic {
     pub kind: AgentTopicKind,
     pub agent_id: Uuid,
+    pub service: String,
     pub version: Version,
 }
 
 impl AgentTopic {
-    pub fn new_in(agent_id: Uuid) -> AgentTopic {
+    pub fn new_in(agent_id: Uuid, service: &str) -> AgentTopic {
         AgentTopic {
             kind: AgentTopicKind::In,
             agent_id,
+            service: service.to_owned(),
             version: Version::V1,
         }
     }
@@ -43,8 +47,8 @@ impl fmt::Display for AgentTopic {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "agents/{}/{}/signals.netology-group.services/api/{}",
-            self.agent_id, self.kind, self.version
+            "agents/{}/{}/{}/api/{}",
+            self.agent_id, self.kind, self.service, self.version
         )
     }
 }
@@ -61,12 +65,13 @@ impl Reversible for AgentTopic {
         AgentTopic {
             kind,
             agent_id: self.agent_id,
+            service: self.service.clone(),
             version: self.version,
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum AgentTopicKind {
     In,
     Out,
@@ -92,6 +97,7 @@ mod tests {
         let topic_exp = Topic::Agent(AgentTopic {
             kind: AgentTopicKind::Out,
             agent_id: Uuid::parse_str("e19c94cf-53eb-4048-9c94-7ae74ff6d912").unwrap(),
+            service: "signals.netology-group.services".to_owned(),
             version: Version::V1,
         });
         assert_eq!(t, Ok((CompleteStr(""), topic_exp)));
@@ -100,6 +106,7 @@ mod tests {
         let topic_exp = Topic::Agent(AgentTopic {
             kind: AgentTopicKind::Out,
             agent_id: Uuid::parse_str("e19c94cf-53eb-4048-9c94-7ae74ff6d912").unwrap(),
+            service: "signals.netology-group.services".to_owned(),
             version: Version::V1,
         });
         assert_eq!(t, Ok((CompleteStr(""), topic_exp)));
@@ -123,17 +130,33 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_topic_with_custom_service() {
+        let t = topic(CompleteStr(
+            "agents/e19c94cf-53eb-4048-9c94-7ae74ff6d912/in/signals.staging.example.org/api/v1",
+        ));
+        let topic_exp = Topic::Agent(AgentTopic {
+            kind: AgentTopicKind::In,
+            agent_id: Uuid::parse_str("e19c94cf-53eb-4048-9c94-7ae74ff6d912").unwrap(),
+            service: "signals.staging.example.org".to_owned(),
+            version: Version::V1,
+        });
+        assert_eq!(t, Ok((CompleteStr(""), topic_exp)));
+    }
+
     #[test]
     fn get_reverse_topic() {
         let out_topic = AgentTopic {
             kind: AgentTopicKind::Out,
             agent_id: Uuid::parse_str("e19c94cf-53eb-4048-9c94-7ae74ff6d912").unwrap(),
+            service: "signals.netology-group.services".to_owned(),
             version: Version::V1,
         };
 
         let expected = AgentTopic {
             kind: AgentTopicKind::In,
             agent_id: Uuid::parse_str("e19c94cf-53eb-4048-9c94-7ae74ff6d912").unwrap(),
+            service: "signals.netology-group.services".to_owned(),
             version: Version::V1,
         };
 
@@ -145,6 +168,7 @@ mod tests {
         let topic = Topic::Agent(AgentTopic {
             kind: AgentTopicKind::Out,
             agent_id: Uuid::parse_str("e19c94cf-53eb-4048-9c94-7ae74ff6d912").unwrap(),
+            service: "signals.netology-group.services".to_owned(),
             version: Version::V1,
         });
         let expected = "agents/e19c94cf-53eb-4048-9c94-7ae74ff6d912/out/signals.netology-group.services/api/v1";
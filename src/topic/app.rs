@@ -1,20 +1,64 @@
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Text;
+use nom::types::CompleteStr;
+use nom::ErrorKind;
 use serde::{Serialize, Serializer};
 use uuid::Uuid;
 
 use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
 
-#[derive(Debug, PartialEq)]
+use error;
+use topic::Topic;
+use version::Version;
+
+named!(pub topic<CompleteStr, Topic>,
+    do_parse!(
+        tag_s!("apps/") >>
+        service: map!(take_until_s!("/api/"), |s: CompleteStr| s.0.to_owned()) >>
+        tag_s!("/api/") >>
+        version: map!(tag_s!("v1"), |_| Version::V1) >>
+        tag_s!("/rooms/") >>
+        room_id: map_res!(take_until_s!("/"), |s: CompleteStr| FromStr::from_str(s.0)) >>
+        tag_s!("/") >>
+        resource: alt!(
+            map!(tag_s!("agents"), |_| ResourceKind::Agents) |
+            map!(tag_s!("tracks"), |_| ResourceKind::Tracks)
+        ) >>
+        eof!() >>
+
+        (Topic::App(AppTopic { room_id, resource, service, version }))
+    )
+);
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct AppTopic {
     pub room_id: Uuid,
     pub resource: ResourceKind,
+    pub service: String,
+    pub version: Version,
+}
+
+impl AppTopic {
+    pub fn new(room_id: Uuid, resource: ResourceKind, service: &str) -> AppTopic {
+        AppTopic {
+            room_id,
+            resource,
+            service: service.to_owned(),
+            version: Version::V1,
+        }
+    }
 }
 
 impl fmt::Display for AppTopic {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "apps/signals.netology-group.services/api/v1/rooms/{}/{}",
-            self.room_id, self.resource
+            "apps/{}/api/{}/rooms/{}/{}",
+            self.service, self.version, self.room_id, self.resource
         )
     }
 }
@@ -28,7 +72,8 @@ impl Serialize for AppTopic {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(AsExpression, FromSqlRow, Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+#[sql_type = "Text"]
 #[serde(rename_all = "lowercase")]
 pub enum ResourceKind {
     Agents,
@@ -42,6 +87,33 @@ impl fmt::Display for ResourceKind {
     }
 }
 
+impl FromStr for ResourceKind {
+    type Err = error::ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "agents" => Ok(ResourceKind::Agents),
+            "tracks" => Ok(ResourceKind::Tracks),
+            _ => Err(error::ParseError::from(ErrorKind::Tag)),
+        }
+    }
+}
+
+impl ToSql<Text, Pg> for ResourceKind {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        ToSql::<Text, Pg>::to_sql(&self.to_string(), out)
+    }
+}
+
+impl FromSql<Text, Pg> for ResourceKind {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Pg>>::from_sql(bytes)?;
+        value
+            .parse()
+            .map_err(|_| format!("Unknown resource kind: {}", value).into())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -49,11 +121,28 @@ mod tests {
 
     use topic::Topic;
 
+    #[test]
+    fn parse_topic() {
+        let t = topic(CompleteStr("apps/signals.netology-group.services/api/v1/rooms/058df470-73ea-43a4-b36c-e4615cad468e/tracks"));
+        let topic_exp = Topic::App(AppTopic {
+            room_id: Uuid::parse_str("058df470-73ea-43a4-b36c-e4615cad468e").unwrap(),
+            resource: ResourceKind::Tracks,
+            service: "signals.netology-group.services".to_owned(),
+            version: Version::V1,
+        });
+        assert_eq!(t, Ok((CompleteStr(""), topic_exp)));
+
+        let t = topic(CompleteStr("apps/signals.netology-group.services/api/v1/rooms/058df470-73ea-43a4-b36c-e4615cad468e/rooms"));
+        assert!(t.is_err());
+    }
+
     #[test]
     fn display_topic() {
         let topic = Topic::App(AppTopic {
             room_id: Uuid::parse_str("058df470-73ea-43a4-b36c-e4615cad468e").unwrap(),
             resource: ResourceKind::Agents,
+            service: "signals.netology-group.services".to_owned(),
+            version: Version::V1,
         });
         let expected = "apps/signals.netology-group.services/api/v1/rooms/058df470-73ea-43a4-b36c-e4615cad468e/agents";
         assert_eq!(topic.to_string(), expected);
@@ -67,11 +156,20 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_resource_kind() {
+        assert_eq!("agents".parse::<ResourceKind>().unwrap(), ResourceKind::Agents);
+        assert_eq!("tracks".parse::<ResourceKind>().unwrap(), ResourceKind::Tracks);
+        assert!("rooms".parse::<ResourceKind>().is_err());
+    }
+
     #[test]
     fn serialize_topic() {
         let topic = AppTopic {
             room_id: Uuid::parse_str("050b7c6f-795c-4cb4-aeea-5ee3f9083de2").unwrap(),
             resource: ResourceKind::Agents,
+            service: "signals.netology-group.services".to_owned(),
+            version: Version::V1,
         };
 
         let expected = r#""apps/signals.netology-group.services/api/v1/rooms/050b7c6f-795c-4cb4-aeea-5ee3f9083de2/agents""#;
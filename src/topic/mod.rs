@@ -10,16 +10,18 @@ mod ping;
 mod state;
 
 use topic::agent::topic as agent_topic;
-pub use topic::agent::AgentTopic;
+pub use topic::agent::{AgentTopic, AgentTopicKind};
+use topic::app::topic as app_topic;
 pub use topic::app::{AppTopic, ResourceKind};
-use topic::ping::{topic as ping_topic, PingTopicKind};
+use topic::ping::topic as ping_topic;
+pub use topic::ping::PingTopicKind;
 pub use topic::state::{topic as state_topic, StateTopic};
 
 named!(topic<CompleteStr, Topic>,
-    alt!(ping_topic | agent_topic | state_topic)
+    alt!(ping_topic | agent_topic | state_topic | app_topic)
 );
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Topic {
     Ping(PingTopicKind),
     Agent(AgentTopic),
@@ -64,8 +66,58 @@ pub trait Reversible {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use quickcheck::{quickcheck, Arbitrary, Gen};
     use uuid::Uuid;
 
+    impl Arbitrary for Topic {
+        fn arbitrary<G: Gen>(g: &mut G) -> Topic {
+            let mut bytes = [0u8; 16];
+            g.fill_bytes(&mut bytes);
+            let id = Uuid::from_random_bytes(bytes);
+            let service = g.choose(&["signals.netology-group.services", "signals.example.org"])
+                .unwrap();
+
+            match g.gen_range(0, 7) {
+                0 => Topic::Ping(PingTopicKind::Ping),
+                1 => Topic::Ping(PingTopicKind::Pong),
+                2 => Topic::Agent(AgentTopic::new_in(id, service)),
+                3 => Topic::Agent(AgentTopic::new_in(id, service).get_reverse()),
+                4 => Topic::State(StateTopic::new(id)),
+                5 => Topic::App(AppTopic::new(id, ResourceKind::Agents, service)),
+                _ => Topic::App(AppTopic::new(id, ResourceKind::Tracks, service)),
+            }
+        }
+    }
+
+    #[test]
+    fn parse_displayed_topic() {
+        fn prop(topic: Topic) -> bool {
+            Topic::parse(&topic.to_string()).ok() == Some(topic)
+        }
+
+        quickcheck(prop as fn(Topic) -> bool);
+    }
+
+    #[test]
+    fn parse_app_topic() {
+        use version::Version;
+
+        let topic = Topic::parse("apps/signals.netology-group.services/api/v1/rooms/058df470-73ea-43a4-b36c-e4615cad468e/agents");
+        let expected = Topic::App(AppTopic {
+            room_id: Uuid::parse_str("058df470-73ea-43a4-b36c-e4615cad468e").unwrap(),
+            resource: ResourceKind::Agents,
+            service: "signals.netology-group.services".to_owned(),
+            version: Version::V1,
+        });
+        assert_eq!(topic.unwrap(), expected);
+    }
+
+    #[test]
+    fn parse_unknown_topic() {
+        assert!(Topic::parse("pings").is_err());
+        assert!(Topic::parse("apps/signals.netology-group.services/api/v1/rooms").is_err());
+    }
+
     #[test]
     fn parse_ping_topic() {
         let topic = Topic::parse("ping");
@@ -90,6 +142,7 @@ mod tests {
 
         if let Topic::Agent(t) = topic.unwrap() {
             assert_eq!(t.kind, AgentTopicKind::Out);
+            assert_eq!(t.service, "signals.netology-group.services");
             assert_eq!(
                 t.agent_id,
                 Uuid::parse_str("e19c94cf-53eb-4048-9c94-7ae74ff6d912").unwrap()
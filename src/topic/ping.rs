@@ -5,13 +5,16 @@ use std::fmt;
 use topic::{Reversible, Topic};
 
 named!(pub topic<CompleteStr, Topic>,
-    alt!(
-        map!(tag_s!("ping"), |_| Topic::Ping(PingTopicKind::Ping)) |
-        map!(tag_s!("pong"), |_| Topic::Ping(PingTopicKind::Pong))
+    terminated!(
+        alt!(
+            map!(tag_s!("ping"), |_| Topic::Ping(PingTopicKind::Ping)) |
+            map!(tag_s!("pong"), |_| Topic::Ping(PingTopicKind::Pong))
+        ),
+        eof!()
     )
 );
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum PingTopicKind {
     Ping,
     Pong,
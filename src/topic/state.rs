@@ -19,7 +19,7 @@ named!(pub topic<CompleteStr, Topic>,
     )
 );
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct StateTopic {
     pub agent_id: Uuid,
     pub version: Version,
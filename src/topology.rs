@@ -0,0 +1,147 @@
+use uuid::Uuid;
+
+use std::collections::{BTreeSet, HashMap};
+
+/// How agents of a room are connected to each other: each to each while there
+/// are at most `mesh_limit` members or no presenters, otherwise in a star around
+/// presenters, that is members with tracks in the room.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Policy {
+    pub mesh_limit: usize,
+}
+
+/// A peer connection between two agents, ordered by their identifiers. The
+/// first agent is the impolite one, so it makes the offer.
+pub type Edge = (Uuid, Uuid);
+
+fn edge(a: Uuid, b: Uuid) -> Edge {
+    if a <= b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+/// Peer connections the members of a room should have. Presenters connect
+/// to each other as well as to everyone else in a star. Without presenters
+/// there's no star to fall back to, so members stay in a mesh.
+pub fn plan(policy: &Policy, members: &[Uuid], presenters: &[Uuid]) -> BTreeSet<Edge> {
+    let is_mesh = members.len() <= policy.mesh_limit
+        || !members.iter().any(|member| presenters.contains(member));
+
+    let mut graph = BTreeSet::new();
+    for (i, &a) in members.iter().enumerate() {
+        for &b in &members[i + 1..] {
+            if a != b && (is_mesh || presenters.contains(&a) || presenters.contains(&b)) {
+                graph.insert(edge(a, b));
+            }
+        }
+    }
+
+    graph
+}
+
+/// Peer connections to make and to drop to move from one graph to another.
+#[derive(Debug, Default, PartialEq)]
+pub struct Changes {
+    pub connect: Vec<Edge>,
+    pub disconnect: Vec<Edge>,
+}
+
+/// The graphs agents of each room have been told to connect in.
+#[derive(Debug, Default)]
+pub struct Planner {
+    graphs: HashMap<Uuid, BTreeSet<Edge>>,
+}
+
+impl Planner {
+    /// Replaces the graph of the room, returns what agents have to change.
+    pub fn update(&mut self, room_id: Uuid, graph: BTreeSet<Edge>) -> Changes {
+        let current = self.graphs.remove(&room_id).unwrap_or_default();
+        let changes = Changes {
+            connect: graph.difference(&current).cloned().collect(),
+            disconnect: current.difference(&graph).cloned().collect(),
+        };
+
+        if !graph.is_empty() {
+            self.graphs.insert(room_id, graph);
+        }
+
+        changes
+    }
+
+    /// Forgets the graph of the room, e.g. once it has been deleted.
+    pub fn remove_room(&mut self, room_id: Uuid) {
+        self.graphs.remove(&room_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(n: usize) -> Vec<Uuid> {
+        (1..n + 1)
+            .map(|i| format!("5f1c0a2e-4b6d-4c8e-9a3b-00000000000{}", i).parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn plan_mesh_and_star() {
+        let agents = ids(4);
+        let policy = Policy { mesh_limit: 3 };
+
+        let graph = plan(&policy, &agents[..3], &[]);
+        assert_eq!(
+            graph.into_iter().collect::<Vec<Edge>>(),
+            vec![
+                (agents[0], agents[1]),
+                (agents[0], agents[2]),
+                (agents[1], agents[2]),
+            ]
+        );
+
+        let graph = plan(&policy, &agents, &[agents[2], agents[3]]);
+        assert_eq!(
+            graph.into_iter().collect::<Vec<Edge>>(),
+            vec![
+                (agents[0], agents[2]),
+                (agents[0], agents[3]),
+                (agents[1], agents[2]),
+                (agents[1], agents[3]),
+                (agents[2], agents[3]),
+            ]
+        );
+
+        assert_eq!(plan(&policy, &agents, &[]).len(), 6);
+    }
+
+    #[test]
+    fn update_graph() {
+        let agents = ids(3);
+        let room_id = "050b7c6f-795c-4cb4-aeea-5ee3f9083de2".parse().unwrap();
+        let policy = Policy { mesh_limit: 2 };
+        let mut planner = Planner::default();
+
+        let changes = planner.update(room_id, plan(&policy, &agents[..2], &[]));
+        assert_eq!(changes.connect, vec![(agents[0], agents[1])]);
+        assert!(changes.disconnect.is_empty());
+
+        // The third member turns the mesh into a star around the presenter.
+        let changes = planner.update(room_id, plan(&policy, &agents, &[agents[2]]));
+        assert_eq!(
+            changes,
+            Changes {
+                connect: vec![(agents[0], agents[2]), (agents[1], agents[2])],
+                disconnect: vec![(agents[0], agents[1])],
+            }
+        );
+
+        let changes = planner.update(room_id, plan(&policy, &agents, &[agents[2]]));
+        assert_eq!(changes, Changes::default());
+
+        planner.remove_room(room_id);
+        let changes = planner.update(room_id, plan(&policy, &agents, &[agents[2]]));
+        assert_eq!(changes.connect.len(), 2);
+    }
+}
@@ -0,0 +1,52 @@
+use openssl::error::ErrorStack;
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+
+use base64;
+
+/// Short-lived credentials of a TURN server by the TURN REST API scheme: the
+/// username is `$EXPIRY:$USER` and the password is HMAC-SHA1 of the username
+/// keyed with the secret shared with the TURN server, in base64. The server
+/// computes the same password and rejects usernames which have expired.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Credentials {
+    pub username: String,
+    pub credential: String,
+}
+
+impl Credentials {
+    /// Issues credentials of the user valid until the Unix timestamp.
+    pub fn issue(secret: &[u8], user: &str, expires_at: i64) -> Result<Credentials, ErrorStack> {
+        let username = format!("{}:{}", expires_at, user);
+
+        let key = PKey::hmac(secret)?;
+        let mut signer = Signer::new(MessageDigest::sha1(), &key)?;
+        signer.update(username.as_bytes())?;
+        let credential = base64::encode(&signer.sign_to_vec()?);
+
+        Ok(Credentials {
+            username,
+            credential,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn issue_credentials() {
+        let user = "1154b35c-e5b0-4a42-8ab2-d4967ce38c9e";
+        let credentials = Credentials::issue(b"secret", user, 1_525_689_600).unwrap();
+
+        assert_eq!(
+            credentials,
+            Credentials {
+                username: "1525689600:1154b35c-e5b0-4a42-8ab2-d4967ce38c9e".to_owned(),
+                credential: "hXm2drE/GRUQqatWqIjftOmh6qQ=".to_owned(),
+            }
+        );
+    }
+}